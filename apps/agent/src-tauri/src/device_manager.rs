@@ -1,14 +1,17 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::io::AsyncWriteExt;
 use whoami;
-use std::process::Command;
-use crate::logger::log_to_file;
-
-#[cfg(target_os = "windows")]
-use std::os::windows::process::CommandExt;
+use crate::error::AgentError;
+use crate::settings_migrations::{migrate_settings, CURRENT_SCHEMA_VERSION};
+use log::{error, info, warn};
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Settings {
+    #[serde(default)]
+    pub schema_version: u64, // See settings_migrations for the history of each version
     pub site_id: String,
     pub device_id: Option<String>,
     pub guid: Option<String>,
@@ -17,6 +20,33 @@ pub struct Settings {
     pub installed_at: String,
     pub registered_at: Option<String>,
     pub show_tray: Option<bool>, // Show system tray icon - defaults to false if not set
+    pub heartbeat_interval_secs: Option<u64>, // Server-provided, defaults to 10 minutes
+    pub log_level: Option<String>, // Server-provided minimum log level
+    pub log_targets: Option<HashMap<String, String>>, // Per-module level overrides, e.g. {"heartbeat": "DEBUG"}
+    pub log_format: Option<String>, // "text" (default) or "json"
+    pub log_max_total_mb: Option<u64>, // Size budget for the logs directory, defaults to 100MB
+    pub log_max_age_days: Option<u64>, // Older log files are deleted, defaults to 30 days
+    pub log_redact_patterns: Option<Vec<String>>, // Extra regexes masked in log messages
+    pub screenshot_format: Option<String>, // "jpeg" (default) or "webp", used for ticket attachments
+    pub screenshot_quality: Option<u8>, // JPEG quality 1-100, defaults to 80; ignored for webp
+    pub screenshot_max_dimension: Option<u32>, // Longest side in pixels, defaults to 2560
+    pub screenshot_max_upload_kb: Option<u64>, // Attachment size cap, defaults to 5MB, 0 for no cap
+    pub file_read_paths: Option<Vec<String>>, // Extra directories the UI may read files from
+    pub registry_read_paths: Option<Vec<String>>, // Registry keys the UI may read, e.g. "HKLM\\SOFTWARE\\Vendor"
+    pub config_read_keys: Option<Vec<String>>, // Other settings the UI may query, e.g. "sysctl:kernel.*"
+    pub features: Option<HashMap<String, bool>>, // Server-provided feature toggles, e.g. "run_scripts"
+    pub script_signing_key: Option<String>, // Base64 ed25519 public key, pinned at install time
+    #[serde(flatten)]
+    pub extra: serde_json::Map<String, serde_json::Value>, // Unknown fields, kept so re-saving doesn't drop them
+}
+
+impl Settings {
+    pub fn is_feature_enabled(&self, name: &str) -> bool {
+        self.features
+            .as_ref()
+            .and_then(|features| features.get(name).copied())
+            .unwrap_or(false)
+    }
 }
 
 pub fn get_config_dir() -> PathBuf {
@@ -34,25 +64,198 @@ pub fn get_config_dir() -> PathBuf {
     }
 }
 
+/// The current user's own directory for agent data. Unlike the config dir it is not shared
+/// with the agents other users run on the same machine.
+pub fn get_user_data_dir() -> PathBuf {
+    #[cfg(target_os = "windows")]
+    {
+        std::env::var_os("LOCALAPPDATA")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("MSPAgent")
+    }
+    #[cfg(target_os = "macos")]
+    {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_else(std::env::temp_dir)
+            .join("Library/Application Support/MSPAgent")
+    }
+    #[cfg(target_os = "linux")]
+    {
+        std::env::var_os("XDG_DATA_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| {
+                std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".local/share"))
+            })
+            .unwrap_or_else(std::env::temp_dir)
+            .join("mspagent")
+    }
+}
+
 pub fn get_settings_path() -> PathBuf {
     get_config_dir().join("settings.json")
 }
 
-pub async fn get_settings() -> Result<Settings, Box<dyn std::error::Error>> {
+pub fn get_settings_backup_path() -> PathBuf {
+    get_config_dir().join("settings.json.bak")
+}
+
+/// Parses settings, upgrading older schema versions. Returns whether a migration ran.
+fn parse_settings(content: &str) -> Result<(Settings, bool), AgentError> {
+    let content = content.trim_start_matches('\u{FEFF}');
+    let mut value: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| AgentError::SettingsCorrupt(e.to_string()))?;
+    let migrated = migrate_settings(&mut value).map_err(AgentError::SettingsCorrupt)?;
+    let settings = serde_json::from_value(value)
+        .map_err(|e| AgentError::SettingsCorrupt(e.to_string()))?;
+    Ok((settings, migrated))
+}
+
+async fn read_settings_file(path: &Path) -> Result<(Settings, bool), AgentError> {
+    let content = tokio::fs::read_to_string(path)
+        .await
+        .map_err(|e| AgentError::SettingsCorrupt(format!("{}: {}", path.display(), e)))?;
+    parse_settings(&content).map_err(|e| match e {
+        AgentError::SettingsCorrupt(e) => {
+            AgentError::SettingsCorrupt(format!("{}: {}", path.display(), e))
+        }
+        e => e,
+    })
+}
+
+/// Held for the whole of a settings save, so the backup rotation and rename of one save
+/// can't interleave with another
+static SETTINGS_WRITE_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Overwrites `path` in place, for files this process may write but not replace. Not atomic,
+/// which is why settings keep a backup copy.
+async fn overwrite_in_place(path: &Path, content: &[u8]) -> Result<(), std::io::Error> {
+    let mut file = tokio::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(path)
+        .await?;
+    file.write_all(content).await?;
+    file.sync_all().await
+}
+
+/// Permissions a replacement for `path` should get: those of the file it replaces, or for
+/// a new file in a world-writable directory (the shared config dir on macOS) read and write
+/// for everyone, so the agents of other users can keep updating it
+#[cfg(unix)]
+async fn replacement_permissions(path: &Path) -> Option<std::fs::Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        return Some(metadata.permissions());
+    }
+    let parent = tokio::fs::metadata(path.parent()?).await.ok()?;
+    (parent.permissions().mode() & 0o002 != 0).then(|| std::fs::Permissions::from_mode(0o666))
+}
+
+/// Writes to a temp file, fsyncs it and renames it over `path`, so a crash
+/// mid-write leaves either the old or the new file, never a truncated one.
+/// Every call gets its own temp file so concurrent writers can't mix their contents.
+///
+/// A file owned by another user is overwritten in place instead: in a sticky directory
+/// it can't be replaced, and replacing it elsewhere would take it away from its owner.
+pub(crate) async fn write_file_atomic(path: &Path, content: &[u8]) -> Result<(), std::io::Error> {
+    #[cfg(unix)]
+    if let Ok(metadata) = tokio::fs::metadata(path).await {
+        use std::os::unix::fs::MetadataExt;
+        if metadata.uid() != unsafe { libc::getuid() } {
+            return overwrite_in_place(path, content).await;
+        }
+    }
+
+    static NEXT_TMP_ID: AtomicU64 = AtomicU64::new(0);
+    let tmp_path = path.with_extension(format!(
+        "{}.{}-{}.tmp",
+        path.extension().and_then(|ext| ext.to_str()).unwrap_or(""),
+        std::process::id(),
+        NEXT_TMP_ID.fetch_add(1, Ordering::Relaxed)
+    ));
+
+    let written = async {
+        let mut file = tokio::fs::File::create(&tmp_path).await?;
+        file.write_all(content).await?;
+        file.sync_all().await?;
+        drop(file);
+        #[cfg(unix)]
+        if let Some(permissions) = replacement_permissions(path).await {
+            tokio::fs::set_permissions(&tmp_path, permissions).await?;
+        }
+        tokio::fs::rename(&tmp_path, path).await
+    }
+    .await;
+    if written.is_err() {
+        let _ = tokio::fs::remove_file(&tmp_path).await;
+    }
+    match written {
+        // E.g. the file became someone else's after the ownership check
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && path.exists() => {
+            return overwrite_in_place(path, content).await;
+        }
+        written => written?,
+    }
+
+    // Persist the rename itself; directories can't be opened for sync on Windows
+    #[cfg(unix)]
+    if let Some(parent) = path.parent() {
+        if let Ok(dir) = tokio::fs::File::open(parent).await {
+            let _ = dir.sync_all().await;
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn get_settings() -> Result<Settings, AgentError> {
     let settings_path = get_settings_path();
+    let backup_path = get_settings_backup_path();
 
-    if !settings_path.exists() {
-        return Err("No settings found. Please reinstall the application.".into());
+    if !settings_path.exists() && !backup_path.exists() {
+        return Err(AgentError::SettingsMissing);
+    }
+
+    let primary_error = match read_settings_file(&settings_path).await {
+        Ok((settings, migrated)) => {
+            if migrated {
+                info!("Migrated settings to schema version {}", CURRENT_SCHEMA_VERSION);
+                if let Err(e) = save_settings(&settings).await {
+                    warn!("Failed to save migrated settings: {}", e);
+                }
+            }
+            return Ok(settings);
+        }
+        Err(e) => e,
+    };
+
+    error!("Failed to read settings ({}), falling back to backup", primary_error);
+
+    let (settings, _) = read_settings_file(&backup_path).await.map_err(|backup_error| {
+        AgentError::SettingsCorrupt(format!(
+            "no usable backup was found ({}; {}). Please reinstall the application.",
+            primary_error, backup_error
+        ))
+    })?;
+
+    warn!("Recovered settings from backup, restoring settings.json");
+
+    // Restore the primary copy so later reads don't hit the corrupt file again
+    let content = serde_json::to_string_pretty(&settings)?;
+    let _lock = SETTINGS_WRITE_LOCK.lock().await;
+    if let Err(e) = write_file_atomic(&settings_path, content.as_bytes()).await {
+        error!("Failed to restore settings.json from backup: {}", e);
     }
 
-    let content = tokio::fs::read_to_string(&settings_path).await?;
-    let content = content.trim_start_matches('\u{FEFF}');
-    let settings: Settings = serde_json::from_str(&content)?;
-    
     Ok(settings)
 }
 
-pub async fn save_settings(settings: &Settings) -> Result<(), Box<dyn std::error::Error>> {
+pub async fn save_settings(settings: &Settings) -> Result<(), AgentError> {
+    let _lock = SETTINGS_WRITE_LOCK.lock().await;
     let settings_path = get_settings_path();
 
     // Ensure directory exists
@@ -60,17 +263,43 @@ pub async fn save_settings(settings: &Settings) -> Result<(), Box<dyn std::error
         tokio::fs::create_dir_all(parent).await?;
     }
 
-    let content = serde_json::to_string_pretty(settings)?;
-    tokio::fs::write(&settings_path, content).await?;
+    // Rotate the current file into the backup, but only if it is still a good copy
+    let mut previous: Option<Settings> = None;
+    if let Ok(current) = tokio::fs::read_to_string(&settings_path).await {
+        if let Ok((parsed, _)) = parse_settings(&current) {
+            write_file_atomic(&get_settings_backup_path(), current.as_bytes()).await?;
+            previous = Some(parsed);
+        }
+    }
+    if previous.is_none() {
+        previous = read_settings_file(&get_settings_backup_path())
+            .await
+            .ok()
+            .map(|(settings, _)| settings);
+    }
+
+    // The server identity must survive even if a caller saves a stale copy
+    let mut settings = settings.clone();
+    if let Some(previous) = previous {
+        if settings.device_id.is_none() {
+            settings.device_id = previous.device_id;
+        }
+        if settings.guid.is_none() {
+            settings.guid = previous.guid;
+        }
+    }
+
+    let content = serde_json::to_string_pretty(&settings)?;
+    write_file_atomic(&settings_path, content.as_bytes()).await?;
 
     Ok(())
 }
 
 pub fn get_hostname() -> Option<String> {
-    return Some(hostname::get().ok()?.to_string_lossy().to_string());
+    Some(hostname::get().ok()?.to_string_lossy().to_string())
 }
 
-pub async fn complete_settings() -> Result<Settings, Box<dyn std::error::Error>> {
+pub async fn complete_settings() -> Result<Settings, AgentError> {
     let mut settings = get_settings().await?;
 
     settings.hostname = get_hostname();
@@ -83,7 +312,7 @@ pub async fn update_from_registration(
     settings: &mut Settings,
     device_id: String,
     guid: String,
-) -> Result<(), Box<dyn std::error::Error>> {
+) -> Result<(), AgentError> {
     settings.device_id = Some(device_id);
     settings.guid = Some(guid);
     settings.registered_at = Some(chrono::Utc::now().to_rfc3339());
@@ -99,213 +328,191 @@ pub async fn is_device_registered() -> bool {
     }
 }
 
-pub async fn get_machine_id() -> Result<String, Box<dyn std::error::Error>> {
-    let mut settings = get_settings().await?;
-    if let Some(guid) = settings.guid {
-        return Ok(guid);
-    }
+pub async fn get_machine_id() -> Result<String, AgentError> {
+    let settings = get_settings().await?;
+    settings.guid.ok_or(AgentError::NotRegistered)
+}
 
-    Err("Could not find GUID in settings".into())
+// Helper to get API endpoint
+pub async fn get_api_endpoint(path: &str) -> Result<String, AgentError> {
+    let settings = get_settings().await?;
+    Ok(format!("{}{}", settings.api_host, path))
 }
 
-pub fn get_serial_number() -> Option<String> {
-    #[cfg(target_os = "windows")]
-    {
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        let output = Command::new("wmic")
-            .args(&["bios", "get", "serialnumber"])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .ok()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines().skip(1) {
-            let trimmed = line.trim();
-            if !trimmed.is_empty() && trimmed != "SerialNumber" {
-                return Some(trimmed.to_string());
-            }
-        }
+pub async fn get_username() -> Option<String> {
+    Some(whoami::username())
+}
 
-        None
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! fixture {
+        ($name:literal) => {
+            include_str!(concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/settings/",
+                $name
+            ))
+        };
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        let output = Command::new("system_profiler")
-            .args(&["SPHardwareDataType"])
-            .output()
-            .ok()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines() {
-            if line.contains("Serial Number") {
-                if let Some(serial) = line.split(':').nth(1) {
-                    return Some(serial.trim().to_string());
-                }
-            }
-        }
-
-        None
+    /// Loads a settings file, saves it the way `save_settings` does and loads the result
+    fn round_trip(content: &str) -> (Settings, Settings) {
+        let (loaded, migrated) = parse_settings(content).unwrap();
+        assert!(migrated, "unversioned files must be migrated");
+        let saved = serde_json::to_string_pretty(&loaded).unwrap();
+        let (reloaded, migrated) = parse_settings(&saved).unwrap();
+        assert!(!migrated, "a saved file must already be current");
+        (loaded, reloaded)
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        if let Ok(serial) = std::fs::read_to_string("/sys/class/dmi/id/product_serial") {
-            let trimmed = serial.trim();
-            if !trimmed.is_empty() {
-                return Some(trimmed.to_string());
+    #[test]
+    fn installer_files_round_trip() {
+        for (content, show_tray) in [
+            (fixture!("installer_windows.json"), false),
+            (fixture!("installer_macos.json"), true),
+            (fixture!("installer_visible_numeric.json"), true),
+        ] {
+            let (loaded, reloaded) = round_trip(content);
+            for settings in [&loaded, &reloaded] {
+                assert_eq!(settings.schema_version, CURRENT_SCHEMA_VERSION);
+                assert_eq!(settings.site_id, "5f0c2d9e-site-secret");
+                assert_eq!(settings.api_host, "https://agent.mspbyte.pro");
+                assert_eq!(settings.installed_at, "2025-03-14T09:26:53Z");
+                assert_eq!(settings.show_tray, Some(show_tray));
+                assert!(settings.device_id.is_none());
+                assert!(settings.extra.is_empty());
             }
         }
-
-        None
     }
-}
 
-pub fn get_primary_mac() -> Option<String> {
-    #[cfg(target_os = "windows")]
-    {
-        const CREATE_NO_WINDOW: u32 = 0x08000000;
-        let output = Command::new("getmac")
-            .args(&["/fo", "csv", "/nh"])
-            .creation_flags(CREATE_NO_WINDOW)
-            .output()
-            .ok()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        // Get first MAC address
-        if let Some(first_line) = output_str.lines().next() {
-            // Parse CSV format: "MAC","Transport Name"
-            if let Some(mac) = first_line.split(',').next() {
-                return Some(mac.trim_matches('"').to_string());
-            }
+    #[test]
+    fn agent_written_files_round_trip() {
+        let (loaded, reloaded) = round_trip(fixture!("baseline_registered.json"));
+        for settings in [&loaded, &reloaded] {
+            assert_eq!(settings.device_id.as_deref(), Some("3c1f7a52-device"));
+            assert_eq!(
+                settings.guid.as_deref(),
+                Some("8d2e41b0-7a9c-4f15-b3e6-0c5d9a7e21f4")
+            );
+            assert_eq!(settings.hostname.as_deref(), Some("FRONTDESK-01"));
+            assert_eq!(
+                settings.registered_at.as_deref(),
+                Some("2025-03-14T09:27:10.512Z")
+            );
+            assert_eq!(settings.show_tray, Some(false));
         }
 
-        None
-    }
-
-    #[cfg(target_os = "macos")]
-    {
-        let output = Command::new("ifconfig").arg("en0").output().ok()?;
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-
-        for line in output_str.lines() {
-            if line.contains("ether") {
-                if let Some(mac) = line.split_whitespace().nth(1) {
-                    return Some(mac.to_string());
-                }
-            }
+        let (loaded, reloaded) = round_trip(fixture!("baseline_unregistered.json"));
+        for settings in [&loaded, &reloaded] {
+            assert!(settings.device_id.is_none());
+            assert!(settings.guid.is_none());
+            assert!(settings.registered_at.is_none());
+            assert!(settings.show_tray.is_none());
         }
-
-        None
     }
 
-    #[cfg(target_os = "linux")]
-    {
-        // Try to read from /sys/class/net
-        let net_dir = std::path::Path::new("/sys/class/net");
-        if let Ok(entries) = std::fs::read_dir(net_dir) {
-            for entry in entries.flatten() {
-                let interface = entry.file_name();
-                let interface_str = interface.to_string_lossy();
-
-                // Skip loopback
-                if interface_str.starts_with("lo") {
-                    continue;
-                }
-
-                let mac_path = net_dir.join(&interface).join("address");
-                if let Ok(mac) = std::fs::read_to_string(&mac_path) {
-                    let trimmed = mac.trim();
-                    if !trimmed.is_empty() && trimmed != "00:00:00:00:00:00" {
-                        return Some(trimmed.to_string());
-                    }
-                }
+    #[test]
+    fn unknown_fields_survive_a_save() {
+        for content in [
+            fixture!("baseline_registered.json"),
+            fixture!("installer_site_reassigned.json"),
+        ] {
+            let (loaded, reloaded) = round_trip(content);
+            for settings in [&loaded, &reloaded] {
+                assert_eq!(
+                    settings.extra.get("deployment_ring"),
+                    Some(&serde_json::Value::from("pilot"))
+                );
             }
         }
 
-        None
+        // The selective update leaves a BOM and removes the registration fields
+        let (loaded, _) = round_trip(fixture!("installer_site_reassigned.json"));
+        assert_eq!(loaded.site_id, "9a7b3c1d-new-site");
+        assert!(loaded.device_id.is_none());
+        assert_eq!(loaded.show_tray, Some(true));
     }
-}
 
-/// Gets the RMM Device ID from CentraStage (if installed)
-pub fn get_rmm_device_id() -> Option<String> {
-    #[cfg(target_os = "windows")]
-    {
-        use std::fs;
-        use winreg::{enums::*, RegKey};
-
-        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-
-        // Primary method – registry (most reliable)
-        if let Ok(subkey) = hklm.open_subkey("SOFTWARE\\CentraStage") {
-            if let Ok(device_id) = subkey.get_value::<String, _>("DeviceID") {
-                let trimmed = device_id.trim();
-                if !trimmed.is_empty() {
-                    return Some(trimmed.to_string());
-                }
-            }
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keeps_owner_and_mode_of_a_file_owned_by_someone_else() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("mspagent-shared-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o1777)).unwrap();
+        let path = dir.join("settings.json");
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666)).unwrap();
+
+        // Hand the file to `nobody`, like the root-owned file the installer writes.
+        // Only possible with the privileges to do so.
+        let other = if unsafe { libc::getuid() } == 65534 {
+            65533
+        } else {
+            65534
+        };
+        if std::os::unix::fs::chown(&path, Some(other), Some(other)).is_err() {
+            std::fs::remove_dir_all(&dir).unwrap();
+            return;
         }
 
-        // Fallback – only if you really need it / have confirmed it is used
-        const SETTINGS_PATH: &str = r"C:\ProgramData\CentraStage\AEMAgent\Settings.json";
-
-        if let Ok(contents) = fs::read_to_string(SETTINGS_PATH) {
-            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&contents) {
-                if let Some(device_uid) = json["deviceUID"].as_str() {
-                    let trimmed = device_uid.trim();
-                    if !trimmed.is_empty() {
-                        return Some(trimmed.to_string());
-                    }
-                }
-            }
-        }
-
-        None
+        write_file_atomic(&path, b"{\"site_id\": \"new\"}")
+            .await
+            .unwrap();
+
+        let metadata = std::fs::metadata(&path).unwrap();
+        assert_eq!(metadata.uid(), other);
+        assert_eq!(metadata.permissions().mode() & 0o777, 0o666);
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\"site_id\": \"new\"}"
+        );
+
+        // A new file in the shared dir stays writable for everyone
+        let backup = dir.join("settings.json.bak");
+        write_file_atomic(&backup, b"{}").await.unwrap();
+        let mode = std::fs::metadata(&backup).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o666);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
-    #[cfg(target_os = "macos")]
-    {
-        use std::fs;
-
-        let settings_path = "/usr/local/share/CentraStage/AEMAgent/Settings.json";
-
-        match fs::read_to_string(settings_path) {
-            Ok(contents) => {
-                match serde_json::from_str::<serde_json::Value>(&contents) {
-                    Ok(json) => {
-                        if let Some(device_uid) = json.get("deviceUID") {
-                            if let Some(device_uid_str) = device_uid.as_str() {
-                                let trimmed = device_uid_str.trim();
-                                if !trimmed.is_empty() {
-                                    return Some(trimmed.to_string());
-                                }
-                            }
-                        }
-                        None
-                    }
-                    Err(_) => None,
-                }
-            }
-            Err(_) => None,
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_atomic_writes_never_mix() {
+        let dir = std::env::temp_dir().join(format!("mspagent-atomic-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("settings.json");
+
+        // Large enough that interleaved writes to a shared temp file would be visible
+        let contents: Vec<Vec<u8>> = (0..16u8).map(|n| vec![b'a' + n; 256 * 1024]).collect();
+        let writers: Vec<_> = contents
+            .iter()
+            .cloned()
+            .map(|content| {
+                let path = path.clone();
+                tokio::spawn(async move { write_file_atomic(&path, &content).await })
+            })
+            .collect();
+        for writer in writers {
+            writer.await.unwrap().unwrap();
         }
-    }
 
-    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
-    {
-        None
+        let written = std::fs::read(&path).unwrap();
+        assert!(contents.contains(&written));
+        let leftovers: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name() != "settings.json")
+            .collect();
+        assert!(
+            leftovers.is_empty(),
+            "temp files left behind: {:?}",
+            leftovers
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 }
-
-// Helper to get API endpoint
-pub async fn get_api_endpoint(path: &str) -> Result<String, Box<dyn std::error::Error>> {
-    let settings = get_settings().await?;
-    Ok(format!("{}{}", settings.api_host, path))
-}
-
-pub async fn get_username() -> Option<String> {
-    Some(whoami::username())
-}
@@ -1,9 +1,22 @@
 use crate::device_manager::{
-    complete_settings, get_api_endpoint, get_machine_id, get_primary_mac, get_serial_number,
-    get_username, update_from_registration,
+    complete_settings, get_api_endpoint, get_machine_id, get_settings, get_username,
+    update_from_registration, Settings,
 };
+use crate::error::AgentError;
 use crate::heartbeat::{get_external_ip, get_local_ip};
+use crate::identity::{resolve_device_identity, DeviceIdentity};
+use crate::logger::set_device_id;
+use crate::network::{collect_network_info, NetworkInfo};
+use crate::rmm::{detect_rmm_agents, RmmAgent};
+use log::{error, info, warn};
+use rand::Rng;
 use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use tauri::{AppHandle, Emitter, Manager};
+use tokio::time::Duration;
+
+const RETRY_BASE_DELAY_SECS: u64 = 5;
+const RETRY_MAX_DELAY_SECS: u64 = 60 * 30; // 30 minutes
 
 #[derive(Serialize, Debug)] // Added Debug trait
 pub struct RegistrationRequest {
@@ -18,6 +31,10 @@ pub struct RegistrationRequest {
     pub ip_address: Option<String>,
     pub ext_address: Option<String>,
     pub username: Option<String>,
+    pub network: NetworkInfo,
+    pub fingerprint: Option<String>,
+    pub identity: DeviceIdentity,
+    pub rmm_agents: Vec<RmmAgent>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -31,16 +48,20 @@ pub struct RegistrationData {
     pub guid: String,
 }
 
-pub async fn register_device_with_server(
-) -> Result<RegistrationResponse, Box<dyn std::error::Error>> {
+pub async fn register_device_with_server() -> Result<RegistrationResponse, AgentError> {
     // Complete settings with local machine info
     let mut settings = complete_settings().await?;
     let api_url = get_api_endpoint("/v1.0/register").await?;
 
     // Try to get machine GUID, but allow None if not available
     let guid = get_machine_id().await.ok();
-    let serial = get_serial_number();
-    let mac = get_primary_mac();
+    let network = tokio::task::spawn_blocking(collect_network_info).await?;
+    let mac = network.primary_mac();
+    let identity_mac = mac.clone();
+    let identity =
+        tokio::task::spawn_blocking(move || resolve_device_identity(identity_mac)).await?;
+    let serial = identity.bios_serial.clone();
+    let rmm_agents = tokio::task::spawn_blocking(detect_rmm_agents).await?;
 
     // Gather additional system info (previously collected by heartbeat)
     let ip_address = get_local_ip();
@@ -62,13 +83,35 @@ pub async fn register_device_with_server(
         ip_address,
         ext_address,
         username,
+        network,
+        fingerprint: identity.fingerprint.clone(),
+        identity,
+        rmm_agents,
     };
 
+    let result = send_registration(&api_url, &request).await?;
+
+    // Update settings with server-provided device_id and guid
+    update_from_registration(
+        &mut settings,
+        result.data.device_id.clone(),
+        result.data.guid.clone(),
+    )
+    .await?;
+
+    Ok(result)
+}
+
+/// Posts a registration request to `api_url` and parses the server's reply
+pub async fn send_registration(
+    api_url: &str,
+    request: &RegistrationRequest,
+) -> Result<RegistrationResponse, AgentError> {
     let client = reqwest::Client::new();
     let response = client
-        .post(&api_url)
+        .post(api_url)
         .header("Content-Type", "application/json") // Explicitly set content type
-        .json(&request)
+        .json(request)
         .send()
         .await?;
 
@@ -76,23 +119,388 @@ pub async fn register_device_with_server(
 
     if status.is_success() {
         let response_text = response.text().await?;
+        Ok(serde_json::from_str(&response_text)?)
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
 
-        let result: RegistrationResponse = serde_json::from_str(&response_text)?;
+/// Current state of the registration supervisor, shared with the tray and UI
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum RegistrationState {
+    Pending,
+    Registering { attempt: u32 },
+    Retrying { attempt: u32, retry_in_secs: u64, error: String },
+    Registered { device_id: Option<String> },
+}
 
-        // Update settings with server-provided device_id and guid
-        update_from_registration(
-            &mut settings,
-            result.data.device_id.clone(),
-            result.data.guid.clone(),
-        )
-        .await?;
+impl RegistrationState {
+    fn tray_tooltip(&self) -> String {
+        match self {
+            RegistrationState::Pending => "MSPAgent - Waiting to register".to_string(),
+            RegistrationState::Registering { .. } => "MSPAgent - Registering...".to_string(),
+            RegistrationState::Retrying { retry_in_secs, .. } => format!(
+                "MSPAgent - Registration failed, retrying in {}s",
+                retry_in_secs
+            ),
+            RegistrationState::Registered { .. } => "MSPAgent".to_string(),
+        }
+    }
+}
 
-        Ok(result)
-    } else {
-        let error_text = response
-            .text()
+/// App-managed holder for the latest registration state
+pub struct RegistrationStatus(pub Mutex<RegistrationState>);
+
+impl Default for RegistrationStatus {
+    fn default() -> Self {
+        RegistrationStatus(Mutex::new(RegistrationState::Pending))
+    }
+}
+
+impl RegistrationStatus {
+    pub fn current(&self) -> RegistrationState {
+        match self.0.lock() {
+            Ok(state) => state.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        }
+    }
+}
+
+/// Computes the delay before the next attempt: exponential growth capped at
+/// `max_secs`, with jitter picked from the upper half of the window so that a
+/// fleet coming back online does not retry in lockstep.
+pub fn backoff_delay(attempt: u32, base_secs: u64, max_secs: u64) -> Duration {
+    let exp = base_secs.saturating_mul(1u64 << attempt.saturating_sub(1).min(32));
+    let capped = exp.min(max_secs).max(1);
+    let jittered = rand::thread_rng().gen_range(capped / 2..=capped);
+    Duration::from_secs(jittered.max(1))
+}
+
+fn publish_state(app: &AppHandle, state: RegistrationState) {
+    if let Some(status) = app.try_state::<RegistrationStatus>() {
+        match status.0.lock() {
+            Ok(mut current) => *current = state.clone(),
+            Err(poisoned) => *poisoned.into_inner() = state.clone(),
+        }
+    }
+
+    if let Some(tray) = app.tray_by_id(crate::TRAY_ID) {
+        let _ = tray.set_tooltip(Some(state.tray_tooltip()));
+    }
+
+    let _ = app.emit("registration_state", state);
+}
+
+/// What the registration supervisor talks to. The app registers with the configured server
+/// and reads settings from disk; tests stand in for both.
+pub(crate) trait RegistrationBackend {
+    /// Makes one registration attempt
+    async fn register(&self) -> Result<RegistrationResponse, AgentError>;
+    /// Settings of a device that already registered on an earlier launch
+    async fn registered_settings(&self) -> Option<Settings>;
+    fn publish(&self, state: RegistrationState);
+}
+
+struct ServerRegistration(AppHandle);
+
+impl RegistrationBackend for ServerRegistration {
+    async fn register(&self) -> Result<RegistrationResponse, AgentError> {
+        register_device_with_server().await
+    }
+
+    async fn registered_settings(&self) -> Option<Settings> {
+        get_settings()
             .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        Err(format!("Registration failed ({}): {}", status, error_text).into())
+            .ok()
+            .filter(|settings| settings.registered_at.is_some())
+    }
+
+    fn publish(&self, state: RegistrationState) {
+        publish_state(&self.0, state);
+    }
+}
+
+/// Keeps attempting registration until it succeeds, backing off between attempts.
+/// Returns once the device has a `registered_at` timestamp in settings.
+pub async fn run_registration_supervisor(app: AppHandle) {
+    run_registration_supervisor_with(
+        &ServerRegistration(app),
+        RETRY_BASE_DELAY_SECS,
+        RETRY_MAX_DELAY_SECS,
+    )
+    .await
+}
+
+pub(crate) async fn run_registration_supervisor_with(
+    backend: &impl RegistrationBackend,
+    base_secs: u64,
+    max_secs: u64,
+) {
+    let mut attempt: u32 = 0;
+
+    loop {
+        attempt += 1;
+        backend.publish(RegistrationState::Registering { attempt });
+
+        match backend.register().await {
+            Ok(response) => {
+                info!(event = "registered"; "Device registered successfully (attempt {})", attempt);
+                info!("Device ID: {}", response.data.device_id);
+                info!("GUID: {}", response.data.guid);
+                set_device_id(Some(response.data.device_id.clone()));
+                backend.publish(RegistrationState::Registered {
+                    device_id: Some(response.data.device_id),
+                });
+                return;
+            }
+            Err(e) => {
+                error!("Failed to register device (attempt {}): {}", attempt, e);
+
+                // A previously registered device only refreshes its details on launch,
+                // so there is nothing left to retry for once registered_at is set.
+                if let Some(settings) = backend.registered_settings().await {
+                    info!("Device already registered, continuing with existing registration");
+                    backend.publish(RegistrationState::Registered {
+                        device_id: settings.device_id,
+                    });
+                    return;
+                }
+
+                let delay = backoff_delay(attempt, base_secs, max_secs);
+                warn!("Retrying registration in {} seconds", delay.as_secs());
+                backend.publish(RegistrationState::Retrying {
+                    attempt,
+                    retry_in_secs: delay.as_secs(),
+                    error: e.to_string(),
+                });
+                tokio::time::sleep(delay).await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::identity::DeviceIdentity;
+    use crate::network::NetworkInfo;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Serves registration replies on a local port, answering the first `failures`
+    /// requests with a 503. Returns the endpoint and a count of requests received.
+    fn mock_server(failures: usize) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/v1.0/register", listener.local_addr().unwrap());
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut content_length = 0;
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line == "\r\n" {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        if name.eq_ignore_ascii_case("content-length") {
+                            content_length = value.trim().parse().unwrap_or(0);
+                        }
+                    }
+                }
+                let mut body = vec![0; content_length];
+                let _ = reader.read_exact(&mut body);
+
+                let request = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                let (status, body) = if request <= failures {
+                    ("503 Service Unavailable", "down for maintenance")
+                } else {
+                    (
+                        "200 OK",
+                        r#"{"data":{"device_id":"device-1","guid":"guid-1"}}"#,
+                    )
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+            }
+        });
+
+        (url, requests)
+    }
+
+    struct MockBackend {
+        url: String,
+        registered: Option<Settings>,
+        status: RegistrationStatus,
+        published: Mutex<Vec<RegistrationState>>,
+    }
+
+    impl MockBackend {
+        fn new(url: String, registered: Option<Settings>) -> Self {
+            Self {
+                url,
+                registered,
+                status: RegistrationStatus::default(),
+                published: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn published(&self) -> Vec<RegistrationState> {
+            self.published.lock().unwrap().clone()
+        }
+    }
+
+    impl RegistrationBackend for MockBackend {
+        async fn register(&self) -> Result<RegistrationResponse, AgentError> {
+            let request = RegistrationRequest {
+                guid: None,
+                site_id: String::from("site-1"),
+                device_id: None,
+                hostname: String::from("test-host"),
+                version: String::from("0.0.0"),
+                platform: String::from("test"),
+                serial: None,
+                mac: None,
+                ip_address: None,
+                ext_address: None,
+                username: None,
+                network: NetworkInfo::default(),
+                fingerprint: None,
+                identity: DeviceIdentity::default(),
+                rmm_agents: Vec::new(),
+            };
+            send_registration(&self.url, &request).await
+        }
+
+        async fn registered_settings(&self) -> Option<Settings> {
+            self.registered.clone()
+        }
+
+        fn publish(&self, state: RegistrationState) {
+            *self.status.0.lock().unwrap() = state.clone();
+            self.published.lock().unwrap().push(state);
+        }
+    }
+
+    /// Lower and upper bound of the delay after `attempt` failed attempts
+    fn backoff_bounds(attempt: u32, base_secs: u64, max_secs: u64) -> (u64, u64) {
+        let capped = base_secs
+            .saturating_mul(1u64 << attempt.saturating_sub(1).min(32))
+            .min(max_secs)
+            .max(1);
+        ((capped / 2).max(1), capped)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retries_until_the_server_accepts() {
+        let (url, requests) = mock_server(3);
+        let backend = MockBackend::new(url, None);
+
+        run_registration_supervisor_with(&backend, 2, 5).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        let published = backend.published();
+        let attempts: Vec<u32> = published
+            .iter()
+            .filter_map(|state| match state {
+                RegistrationState::Registering { attempt } => Some(*attempt),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(attempts, vec![1, 2, 3, 4]);
+
+        let retries: Vec<&RegistrationState> = published
+            .iter()
+            .filter(|state| matches!(state, RegistrationState::Retrying { .. }))
+            .collect();
+        assert_eq!(retries.len(), 3);
+        for state in retries {
+            let RegistrationState::Retrying {
+                attempt,
+                retry_in_secs,
+                error,
+            } = state
+            else {
+                unreachable!();
+            };
+            let (low, high) = backoff_bounds(*attempt, 2, 5);
+            assert!(
+                (low..=high).contains(retry_in_secs),
+                "attempt {} retried after {}s, expected {}..={}",
+                attempt,
+                retry_in_secs,
+                low,
+                high
+            );
+            assert!(error.contains("503"), "unexpected error: {}", error);
+        }
+
+        assert!(matches!(
+            backend.status.current(),
+            RegistrationState::Registered { device_id: Some(id) } if id == "device-1"
+        ));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn stops_retrying_when_registered_on_an_earlier_launch() {
+        let (url, requests) = mock_server(usize::MAX);
+        let settings: Settings = serde_json::from_value(serde_json::json!({
+            "site_id": "site-1",
+            "api_host": "http://127.0.0.1",
+            "installed_at": "2024-01-01T00:00:00Z",
+            "registered_at": "2024-01-01T00:05:00Z",
+            "device_id": "device-0",
+        }))
+        .unwrap();
+        let backend = MockBackend::new(url, Some(settings));
+
+        run_registration_supervisor_with(&backend, 2, 5).await;
+
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(!backend
+            .published()
+            .iter()
+            .any(|state| matches!(state, RegistrationState::Retrying { .. })));
+        assert!(matches!(
+            backend.status.current(),
+            RegistrationState::Registered { device_id: Some(id) } if id == "device-0"
+        ));
+    }
+
+    #[test]
+    fn backoff_delay_grows_and_stays_capped() {
+        for attempt in 0..=70 {
+            let (low, high) = backoff_bounds(attempt, 5, 1800);
+            for _ in 0..20 {
+                let delay = backoff_delay(attempt, 5, 1800).as_secs();
+                assert!(
+                    (low..=high).contains(&delay),
+                    "attempt {} gave {}s, expected {}..={}",
+                    attempt,
+                    delay,
+                    low,
+                    high
+                );
+            }
+        }
+
+        assert_eq!(backoff_bounds(1, 5, 1800), (2, 5));
+        assert_eq!(backoff_bounds(4, 5, 1800), (20, 40));
+        assert_eq!(backoff_bounds(40, 5, 1800), (900, 1800));
+        assert_eq!(backoff_bounds(u32::MAX, u64::MAX, u64::MAX).1, u64::MAX);
     }
 }
@@ -0,0 +1,122 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+/// Error type shared by every module and returned from Tauri commands.
+/// Serializes as `{ code, message }` (plus `status` for HTTP errors) so the
+/// frontend can branch on `code` instead of matching message text.
+#[derive(Debug, Clone)]
+pub enum AgentError {
+    SettingsMissing,
+    SettingsCorrupt(String),
+    NotRegistered,
+    Network(String),
+    HttpStatus { status: u16, body: String },
+    Parse(String),
+    Io(String),
+    PlatformUnsupported(&'static str),
+    AccessDenied(String),
+}
+
+impl AgentError {
+    /// Machine-readable code sent to the frontend
+    pub fn code(&self) -> &'static str {
+        match self {
+            AgentError::SettingsMissing => "settings_missing",
+            AgentError::SettingsCorrupt(_) => "settings_corrupt",
+            AgentError::NotRegistered => "not_registered",
+            AgentError::Network(_) => "network",
+            AgentError::HttpStatus { .. } => "http_status",
+            AgentError::Parse(_) => "parse",
+            AgentError::Io(_) => "io",
+            AgentError::PlatformUnsupported(_) => "platform_unsupported",
+            AgentError::AccessDenied(_) => "access_denied",
+        }
+    }
+
+    /// Builds an `HttpStatus` error from a non-success response, keeping the body for diagnostics
+    pub async fn from_response(response: reqwest::Response) -> AgentError {
+        let status = response.status().as_u16();
+        let body = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        AgentError::HttpStatus { status, body }
+    }
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::SettingsMissing => {
+                write!(f, "No settings found. Please reinstall the application.")
+            }
+            AgentError::SettingsCorrupt(e) => write!(f, "Settings are unreadable: {}", e),
+            AgentError::NotRegistered => write!(f, "Device is not registered"),
+            AgentError::Network(e) => write!(f, "Network error: {}", e),
+            AgentError::HttpStatus { status, body } => {
+                write!(f, "Server returned {}: {}", status, body)
+            }
+            AgentError::Parse(e) => write!(f, "Parse error: {}", e),
+            AgentError::Io(e) => write!(f, "IO error: {}", e),
+            AgentError::PlatformUnsupported(what) => {
+                write!(f, "{} is not supported on {}", what, std::env::consts::OS)
+            }
+            AgentError::AccessDenied(what) => write!(f, "Access denied: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for AgentError {}
+
+impl Serialize for AgentError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let status = match self {
+            AgentError::HttpStatus { status, .. } => Some(*status),
+            _ => None,
+        };
+
+        let mut state =
+            serializer.serialize_struct("AgentError", if status.is_some() { 3 } else { 2 })?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        if let Some(status) = status {
+            state.serialize_field("status", &status)?;
+        }
+        state.end()
+    }
+}
+
+impl From<std::io::Error> for AgentError {
+    fn from(e: std::io::Error) -> Self {
+        AgentError::Io(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AgentError {
+    fn from(e: serde_json::Error) -> Self {
+        AgentError::Parse(e.to_string())
+    }
+}
+
+impl From<reqwest::Error> for AgentError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_decode() {
+            AgentError::Parse(e.to_string())
+        } else {
+            AgentError::Network(e.to_string())
+        }
+    }
+}
+
+// A blocking collector task panicked or was cancelled
+impl From<tokio::task::JoinError> for AgentError {
+    fn from(e: tokio::task::JoinError) -> Self {
+        AgentError::Io(format!("Background task failed: {}", e))
+    }
+}
+
+impl From<tauri::Error> for AgentError {
+    fn from(e: tauri::Error) -> Self {
+        AgentError::Io(e.to_string())
+    }
+}
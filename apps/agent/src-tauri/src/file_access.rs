@@ -0,0 +1,393 @@
+use crate::device_manager::{get_config_dir, get_user_data_dir};
+use crate::error::AgentError;
+use crate::screenshot::screenshot_dir;
+use log::warn;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+
+/// A file the user picked in a dialog. The token grants read access to that one file
+/// until it is released; the path is only for display.
+#[derive(Serialize, Clone, Debug)]
+pub struct PickedFile {
+    pub token: String,
+    pub path: String,
+}
+
+/// Decides which files the webview may have the agent read. Allowed are the screenshot
+/// directory and directories from settings by path, and files picked in a dialog by token.
+/// Paths are canonicalized before any comparison so `..` and symlinks can't escape.
+#[derive(Clone, Default)]
+pub struct FileAccess {
+    grants: Arc<RwLock<HashMap<String, PathBuf>>>,
+    configured: Arc<RwLock<Vec<PathBuf>>>,
+    // Captures written by the screenshot editor, the only ones that may be attached
+    redacted: Arc<RwLock<HashSet<PathBuf>>>,
+}
+
+impl FileAccess {
+    /// Grants read access to a single file and returns the token that tracks it
+    pub fn grant(&self, path: &Path) -> Result<PickedFile, AgentError> {
+        let canonical = std::fs::canonicalize(path)
+            .map_err(|e| AgentError::Io(format!("Failed to resolve {}: {}", path.display(), e)))?;
+        let token = format!("{:032x}", rand::random::<u128>());
+
+        let mut grants = match self.grants.write() {
+            Ok(grants) => grants,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        grants.insert(token.clone(), canonical.clone());
+
+        Ok(PickedFile {
+            token,
+            path: canonical.to_string_lossy().to_string(),
+        })
+    }
+
+    pub fn release(&self, token: &str) {
+        let mut grants = match self.grants.write() {
+            Ok(grants) => grants,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        grants.remove(token);
+    }
+
+    /// Replaces the directories allowed by settings. Entries that don't exist are skipped.
+    pub fn set_configured_paths(&self, paths: &[String]) {
+        let resolved = paths
+            .iter()
+            .filter_map(|path| match std::fs::canonicalize(path) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    warn!("Ignoring file access path {}: {}", path, e);
+                    None
+                }
+            })
+            .collect();
+
+        let mut configured = match self.configured.write() {
+            Ok(configured) => configured,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *configured = resolved;
+    }
+
+    /// Records a screenshot the editor produced, so it can be attached to a ticket
+    pub fn mark_redacted(&self, path: &Path) -> Result<(), AgentError> {
+        let canonical = std::fs::canonicalize(path)
+            .map_err(|e| AgentError::Io(format!("Failed to resolve {}: {}", path.display(), e)))?;
+        let mut redacted = match self.redacted.write() {
+            Ok(redacted) => redacted,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        redacted.insert(canonical);
+        Ok(())
+    }
+
+    /// Like `check`, but a capture from the screenshot directory is only allowed once it
+    /// went through the editor
+    pub fn check_attachment(&self, path: &str) -> Result<PathBuf, AgentError> {
+        self.check_attachment_in(
+            path,
+            &[get_config_dir(), get_user_data_dir()],
+            &screenshot_dir(),
+        )
+    }
+
+    fn check_attachment_in(
+        &self,
+        path: &str,
+        denied_dirs: &[PathBuf],
+        screenshot_dir: &Path,
+    ) -> Result<PathBuf, AgentError> {
+        let canonical = self.check_in(path, denied_dirs, screenshot_dir)?;
+        let is_capture =
+            std::fs::canonicalize(screenshot_dir).is_ok_and(|dir| canonical.starts_with(dir));
+        let redacted = match self.redacted.read() {
+            Ok(redacted) => redacted.contains(&canonical),
+            Err(poisoned) => poisoned.into_inner().contains(&canonical),
+        };
+        if is_capture && !redacted {
+            return Err(AgentError::AccessDenied(format!(
+                "{} has not been through the screenshot editor",
+                path
+            )));
+        }
+        Ok(canonical)
+    }
+
+    /// Looks up the file picked with `token` and returns its canonical path, as long as it
+    /// still resolves to the file that was picked. Callers must read from the returned path.
+    pub fn check_token(&self, token: &str) -> Result<PathBuf, AgentError> {
+        self.check_token_in(token, &[get_config_dir(), get_user_data_dir()])
+    }
+
+    fn check_token_in(&self, token: &str, denied_dirs: &[PathBuf]) -> Result<PathBuf, AgentError> {
+        let granted = {
+            let grants = match self.grants.read() {
+                Ok(grants) => grants,
+                Err(poisoned) => poisoned.into_inner(),
+            };
+            grants.get(token).cloned()
+        };
+        let Some(granted) = granted else {
+            return Err(AgentError::AccessDenied(
+                "Unknown or released file token".to_string(),
+            ));
+        };
+
+        // The picked file could have been replaced with a symlink since
+        let denied = || AgentError::AccessDenied(granted.display().to_string());
+        let canonical = std::fs::canonicalize(&granted).map_err(|_| denied())?;
+        if canonical != granted || is_in_any(&canonical, denied_dirs) {
+            return Err(denied());
+        }
+        Ok(canonical)
+    }
+
+    /// Resolves `path` and returns its canonical form if the webview may read it.
+    /// Callers must read from the returned path, not the one they were given.
+    pub fn check(&self, path: &str) -> Result<PathBuf, AgentError> {
+        self.check_in(
+            path,
+            &[get_config_dir(), get_user_data_dir()],
+            &screenshot_dir(),
+        )
+    }
+
+    fn check_in(
+        &self,
+        path: &str,
+        denied_dirs: &[PathBuf],
+        screenshot_dir: &Path,
+    ) -> Result<PathBuf, AgentError> {
+        let requested = Path::new(path);
+        if !requested.is_absolute() {
+            return Err(AgentError::AccessDenied(format!(
+                "{} is not an absolute path",
+                path
+            )));
+        }
+
+        // Missing files are reported as denied so the webview can't probe for them
+        let canonical = std::fs::canonicalize(requested)
+            .map_err(|_| AgentError::AccessDenied(path.to_string()))?;
+
+        if is_in_any(&canonical, denied_dirs) {
+            return Err(AgentError::AccessDenied(path.to_string()));
+        }
+
+        let configured = match self.configured.read() {
+            Ok(configured) => configured.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        let roots = std::fs::canonicalize(screenshot_dir)
+            .into_iter()
+            .chain(configured);
+        for root in roots {
+            // Component-wise, so a sibling like `screenshots-old` does not match `screenshots`
+            if canonical.starts_with(&root) && canonical.is_file() {
+                return Ok(canonical);
+            }
+        }
+
+        Err(AgentError::AccessDenied(path.to_string()))
+    }
+}
+
+/// Whether `canonical` is inside one of `dirs`. Settings, logs and the outbox live in
+/// the denied directories; nothing in them is ever readable.
+fn is_in_any(canonical: &Path, dirs: &[PathBuf]) -> bool {
+    dirs.iter()
+        .any(|dir| std::fs::canonicalize(dir).is_ok_and(|dir| canonical.starts_with(dir)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A config dir, a screenshot dir and a few files around them
+    struct Layout {
+        base: PathBuf,
+        config: PathBuf,
+        screenshots: PathBuf,
+    }
+
+    impl Layout {
+        fn new(name: &str) -> Self {
+            let base = std::env::temp_dir().join(format!(
+                "mspagent-files-{}-{}",
+                name,
+                std::process::id()
+            ));
+            let _ = std::fs::remove_dir_all(&base);
+            let config = base.join("config");
+            let screenshots = base.join("screenshots");
+            for dir in [&config, &screenshots, &base.join("screenshots-old")] {
+                std::fs::create_dir_all(dir).unwrap();
+            }
+            std::fs::write(config.join("settings.json"), "{}").unwrap();
+            std::fs::write(screenshots.join("shot.png"), "png").unwrap();
+            std::fs::write(base.join("screenshots-old").join("shot.png"), "png").unwrap();
+            std::fs::write(base.join("outside.txt"), "text").unwrap();
+            Layout {
+                base,
+                config,
+                screenshots,
+            }
+        }
+
+        fn check(&self, access: &FileAccess, path: &Path) -> Result<PathBuf, AgentError> {
+            access.check_in(
+                &path.to_string_lossy(),
+                std::slice::from_ref(&self.config),
+                &self.screenshots,
+            )
+        }
+
+        fn check_token(&self, access: &FileAccess, token: &str) -> Result<PathBuf, AgentError> {
+            access.check_token_in(token, std::slice::from_ref(&self.config))
+        }
+    }
+
+    impl Drop for Layout {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.base);
+        }
+    }
+
+    fn denied(result: Result<PathBuf, AgentError>) -> bool {
+        matches!(result, Err(AgentError::AccessDenied(_)))
+    }
+
+    #[test]
+    fn allows_files_in_the_screenshot_dir() {
+        let layout = Layout::new("allowed");
+        let access = FileAccess::default();
+        let shot = layout.screenshots.join("shot.png");
+        assert_eq!(
+            layout.check(&access, &shot).unwrap(),
+            std::fs::canonicalize(&shot).unwrap()
+        );
+    }
+
+    #[test]
+    fn rejects_traversal_out_of_the_screenshot_dir() {
+        let layout = Layout::new("traversal");
+        let access = FileAccess::default();
+        let escaped = layout.screenshots.join("..").join("outside.txt");
+        assert!(denied(layout.check(&access, &escaped)));
+        let into_config = layout
+            .screenshots
+            .join("..")
+            .join("config")
+            .join("settings.json");
+        assert!(denied(layout.check(&access, &into_config)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_symlinks_out_of_the_screenshot_dir() {
+        let layout = Layout::new("symlink");
+        let access = FileAccess::default();
+        let link = layout.screenshots.join("settings.png");
+        std::os::unix::fs::symlink(layout.config.join("settings.json"), &link).unwrap();
+        assert!(denied(layout.check(&access, &link)));
+
+        let link = layout.screenshots.join("outside.png");
+        std::os::unix::fs::symlink(layout.base.join("outside.txt"), &link).unwrap();
+        assert!(denied(layout.check(&access, &link)));
+    }
+
+    #[test]
+    fn rejects_relative_paths() {
+        let layout = Layout::new("relative");
+        let access = FileAccess::default();
+        assert!(denied(layout.check(&access, Path::new("shot.png"))));
+        assert!(denied(
+            layout.check(&access, Path::new("../screenshots/shot.png"))
+        ));
+    }
+
+    #[test]
+    fn rejects_a_sibling_sharing_the_prefix() {
+        let layout = Layout::new("sibling");
+        let access = FileAccess::default();
+        let sibling = layout.base.join("screenshots-old").join("shot.png");
+        assert!(denied(layout.check(&access, &sibling)));
+    }
+
+    #[test]
+    fn grants_end_when_released() {
+        let layout = Layout::new("release");
+        let access = FileAccess::default();
+        let outside = layout.base.join("outside.txt");
+        assert!(denied(layout.check(&access, &outside)));
+
+        let picked = access.grant(&outside).unwrap();
+        assert_eq!(
+            layout.check_token(&access, &picked.token).unwrap(),
+            std::fs::canonicalize(&outside).unwrap()
+        );
+        // The grant is tied to the token, not the path
+        assert!(denied(layout.check(&access, &outside)));
+        assert!(denied(layout.check_token(&access, "not-a-token")));
+
+        access.release(&picked.token);
+        assert!(denied(layout.check_token(&access, &picked.token)));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn rejects_a_picked_file_swapped_for_a_symlink() {
+        let layout = Layout::new("swapped");
+        let access = FileAccess::default();
+        let outside = layout.base.join("outside.txt");
+        let picked = access.grant(&outside).unwrap();
+
+        std::fs::remove_file(&outside).unwrap();
+        std::os::unix::fs::symlink(layout.config.join("settings.json"), &outside).unwrap();
+        assert!(denied(layout.check_token(&access, &picked.token)));
+    }
+
+    #[test]
+    fn attaches_captures_only_after_editing() {
+        let layout = Layout::new("attach");
+        let access = FileAccess::default();
+        let attach = |path: &Path| {
+            access.check_attachment_in(
+                &path.to_string_lossy(),
+                std::slice::from_ref(&layout.config),
+                &layout.screenshots,
+            )
+        };
+
+        let shot = layout.screenshots.join("shot.png");
+        assert!(denied(attach(&shot)));
+        access.mark_redacted(&shot).unwrap();
+        assert!(attach(&shot).is_ok());
+
+        // A picked file is only ever attached through its token
+        let outside = layout.base.join("outside.txt");
+        access.grant(&outside).unwrap();
+        assert!(denied(attach(&outside)));
+    }
+
+    #[test]
+    fn never_allows_the_config_dir() {
+        let layout = Layout::new("config");
+        let access = FileAccess::default();
+        let settings = layout.config.join("settings.json");
+
+        // Not even through a configured parent directory or a dialog pick
+        access.set_configured_paths(&[layout.base.to_string_lossy().to_string()]);
+        assert!(layout
+            .check(&access, &layout.base.join("outside.txt"))
+            .is_ok());
+        assert!(denied(layout.check(&access, &settings)));
+
+        let picked = access.grant(&settings).unwrap();
+        assert!(denied(layout.check_token(&access, &picked.token)));
+    }
+}
@@ -1,9 +1,31 @@
-use crate::device_manager::{get_api_endpoint, get_primary_mac, get_settings, get_username};
-use crate::logger::log_to_file;
+use crate::device_manager::{
+    get_api_endpoint, get_settings, get_username, save_settings, Settings,
+};
+use crate::error::AgentError;
+use crate::file_access::FileAccess;
+use crate::identity::resolve_device_identity;
+use crate::inventory::send_inventory;
+use crate::jobs::{Job, JobDispatcher};
+use crate::software::send_software_inventory;
+use crate::logger::{
+    acknowledge_dropped_messages, dropped_message_count, enforce_retention, set_device_id,
+    set_json_output, set_min_level, set_redaction, set_retention, set_target_levels, LogLevel,
+    LogRetention,
+};
+use crate::network::{collect_network_info, NetworkInfo};
+use crate::registry::RegistryAccess;
+use crate::system_config::ConfigAccess;
+use log::{error, info, warn};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use tokio::time::{interval, Duration};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+use tokio::sync::Notify;
+use tokio::time::{interval, interval_at, Duration, Instant};
+
+const DEFAULT_HEARTBEAT_INTERVAL_SECS: u64 = 60 * 10;
+const MIN_HEARTBEAT_INTERVAL_SECS: u64 = 60;
 
 #[derive(Serialize, Debug)]
 pub struct HeartbeatRequest {
@@ -14,6 +36,9 @@ pub struct HeartbeatRequest {
     pub mac_address: Option<String>,
     pub guid: Option<String>,
     pub username: Option<String>,
+    pub network: NetworkInfo,
+    pub fingerprint: Option<String>,
+    pub dropped_log_messages: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -24,16 +49,99 @@ pub struct HeartbeatResponse {
 #[derive(Deserialize, Debug)]
 pub struct HeartbeatData {
     pub guid: String,
+    pub config: Option<AgentConfig>,
+    pub jobs: Option<Vec<Job>>,
+}
+
+/// Agent configuration pushed by the server in the heartbeat reply.
+/// Fields left out by the server keep their current local value.
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct AgentConfig {
+    pub heartbeat_interval_secs: Option<u64>,
+    pub show_tray: Option<bool>,
+    pub log_level: Option<String>,
+    pub log_targets: Option<HashMap<String, String>>,
+    pub log_format: Option<String>,
+    pub features: Option<HashMap<String, bool>>,
+}
+
+/// Returns the heartbeat interval configured in settings, clamped to a sane minimum
+pub fn heartbeat_period(settings: &Settings) -> Duration {
+    let secs = settings
+        .heartbeat_interval_secs
+        .unwrap_or(DEFAULT_HEARTBEAT_INTERVAL_SECS)
+        .max(MIN_HEARTBEAT_INTERVAL_SECS);
+    Duration::from_secs(secs)
+}
+
+/// Merges server config into the stored settings.
+/// Returns the updated settings if anything changed, after saving them.
+pub async fn apply_agent_config(
+    config: &AgentConfig,
+) -> Result<Option<Settings>, AgentError> {
+    let mut settings = get_settings().await?;
+    let mut changed = false;
+
+    if let Some(secs) = config.heartbeat_interval_secs {
+        let secs = secs.max(MIN_HEARTBEAT_INTERVAL_SECS);
+        if settings.heartbeat_interval_secs != Some(secs) {
+            settings.heartbeat_interval_secs = Some(secs);
+            changed = true;
+        }
+    }
+
+    if config.show_tray.is_some() && settings.show_tray != config.show_tray {
+        settings.show_tray = config.show_tray;
+        changed = true;
+    }
+
+    if let Some(level) = &config.log_level {
+        let level = level.to_uppercase();
+        if settings.log_level.as_deref() != Some(level.as_str()) {
+            settings.log_level = Some(level);
+            changed = true;
+        }
+    }
+
+    if config.log_targets.is_some() && settings.log_targets != config.log_targets {
+        settings.log_targets = config.log_targets.clone();
+        changed = true;
+    }
+
+    if let Some(format) = &config.log_format {
+        let format = format.to_lowercase();
+        if settings.log_format.as_deref() != Some(format.as_str()) {
+            settings.log_format = Some(format);
+            changed = true;
+        }
+    }
+
+    if config.features.is_some() && settings.features != config.features {
+        settings.features = config.features.clone();
+        changed = true;
+    }
+
+    if !changed {
+        return Ok(None);
+    }
+
+    save_settings(&settings).await?;
+    Ok(Some(settings))
 }
 
 /// Gathers current system information for heartbeat
-pub async fn gather_system_info() -> Result<HeartbeatRequest, Box<dyn std::error::Error>> {
+pub async fn gather_system_info() -> Result<HeartbeatRequest, AgentError> {
     let settings = get_settings().await?;
     let hostname = settings
         .hostname
         .clone()
         .unwrap_or_else(|| hostname::get().unwrap().to_string_lossy().to_string());
-    let mac_address = get_primary_mac();
+    let network = tokio::task::spawn_blocking(collect_network_info).await?;
+    let mac_address = network.primary_mac();
+    let identity_mac = mac_address.clone();
+    let fingerprint = tokio::task::spawn_blocking(move || resolve_device_identity(identity_mac))
+        .await?
+        .fingerprint;
     let ip_address = get_local_ip();
     let ext_address = get_external_ip().await.ok();
     let username = get_username().await;
@@ -46,6 +154,9 @@ pub async fn gather_system_info() -> Result<HeartbeatRequest, Box<dyn std::error
         mac_address,
         guid: settings.guid,
         username,
+        network,
+        fingerprint,
+        dropped_log_messages: dropped_message_count(),
     })
 }
 
@@ -60,7 +171,7 @@ pub fn get_local_ip() -> Option<String> {
 }
 
 /// Gets the external IP address by querying an external service
-pub async fn get_external_ip() -> Result<String, Box<dyn std::error::Error>> {
+pub async fn get_external_ip() -> Result<String, AgentError> {
     let client = reqwest::Client::builder()
         .timeout(Duration::from_secs(5))
         .build()?;
@@ -74,19 +185,16 @@ pub async fn get_external_ip() -> Result<String, Box<dyn std::error::Error>> {
 }
 
 /// Sends a heartbeat to the server
-pub async fn send_heartbeat() -> Result<HeartbeatResponse, Box<dyn std::error::Error>> {
+pub async fn send_heartbeat() -> Result<HeartbeatResponse, AgentError> {
     let settings = get_settings().await?;
 
     // Check if device is registered
-    if settings.device_id.is_none() {
-        return Err("Device not registered, skipping heartbeat".into());
-    }
-
-    let device_id = settings.device_id.as_ref().unwrap();
+    let device_id = settings.device_id.as_ref().ok_or(AgentError::NotRegistered)?;
     let site_id = &settings.site_id;
 
     // Gather system info
     let request = gather_system_info().await?;
+    let dropped_log_messages = request.dropped_log_messages;
 
     let api_url = get_api_endpoint("/v1.0/heartbeat").await?;
 
@@ -106,46 +214,138 @@ pub async fn send_heartbeat() -> Result<HeartbeatResponse, Box<dyn std::error::E
         let response_text = response.text().await?;
         let result: HeartbeatResponse = serde_json::from_str(&response_text)?;
 
+        // The server has the count now, only messages dropped since then are reported next time
+        acknowledge_dropped_messages(dropped_log_messages);
+
         Ok(result)
     } else {
-        let error_text = response
-            .text()
-            .await
-            .unwrap_or_else(|_| "Unknown error".to_string());
-        Err(format!("Heartbeat failed ({}): {}", status, error_text).into())
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+/// Snapshot of the heartbeat task exposed to the frontend
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct HeartbeatStatus {
+    pub running: bool,
+    pub last_sent_at: Option<String>,
+    pub last_error: Option<String>,
+}
+
+/// Handle to the heartbeat background task, stored in app state
+#[derive(Clone, Default)]
+pub struct HeartbeatHandle {
+    running: Arc<AtomicBool>,
+    shutdown: Arc<Notify>,
+    status: Arc<Mutex<HeartbeatStatus>>,
+    jobs: JobDispatcher,
+}
+
+impl HeartbeatHandle {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::SeqCst)
+    }
+
+    pub fn status(&self) -> HeartbeatStatus {
+        let mut status = match self.status.lock() {
+            Ok(status) => status.clone(),
+            Err(poisoned) => poisoned.into_inner().clone(),
+        };
+        status.running = self.is_running();
+        status
+    }
+
+    /// Signals the task to stop; the loop wakes immediately instead of waiting for the next tick
+    pub fn stop(&self) {
+        if self.running.swap(false, Ordering::SeqCst) {
+            info!("Stopping heartbeat background task");
+            self.shutdown.notify_one();
+        }
+    }
+
+    fn record(&self, result: Result<(), String>) {
+        let mut status = match self.status.lock() {
+            Ok(status) => status,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match result {
+            Ok(()) => {
+                status.last_sent_at = Some(chrono::Utc::now().to_rfc3339());
+                status.last_error = None;
+            }
+            Err(e) => status.last_error = Some(e),
+        }
     }
 }
 
-/// Starts the heartbeat background task that runs every 60 seconds
-pub fn start_heartbeat_task(running: Arc<AtomicBool>) {
+/// Starts the heartbeat background task, every 10 minutes unless the server says otherwise.
+/// Does nothing if the task is already running.
+pub fn start_heartbeat_task(app: AppHandle, handle: HeartbeatHandle) {
+    if handle.running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
     tauri::async_runtime::spawn(async move {
-        log_to_file(
-            "INFO".to_string(),
-            "Starting heartbeat background task".to_string(),
-        );
+        info!("Starting heartbeat background task");
 
         // Wait 5 seconds before first heartbeat to allow app to fully initialize
-        tokio::time::sleep(Duration::from_secs(5)).await;
+        tokio::select! {
+            _ = tokio::time::sleep(Duration::from_secs(5)) => {}
+            _ = handle.shutdown.notified() => {}
+        }
 
-        let mut heartbeat_interval = interval(Duration::from_secs(60 * 10));
+        let mut period = match get_settings().await {
+            Ok(settings) => heartbeat_period(&settings),
+            Err(_) => Duration::from_secs(DEFAULT_HEARTBEAT_INTERVAL_SECS),
+        };
+        let mut heartbeat_interval = interval(period);
         let mut health_check_interval = interval(Duration::from_secs(86400)); // 24 hours
 
         // Skip first tick for health check to align with actual 24hr intervals
         health_check_interval.tick().await;
 
-        while running.load(Ordering::Relaxed) {
+        // Inventory is sent once on startup, then with the daily health check.
+        // Software only uploads what changed since the last report.
+        report_inventory().await;
+
+        while handle.is_running() {
             tokio::select! {
+                _ = handle.shutdown.notified() => {
+                    break;
+                }
                 _ = heartbeat_interval.tick() => {
                     // Send heartbeat silently (no logging unless error)
-                    match send_heartbeat().await {
-                        Ok(_response) => {
-                            // Success - no logging
+                    let (config, jobs) = match send_heartbeat().await {
+                        Ok(response) => {
+                            handle.record(Ok(()));
+                            (response.data.config, response.data.jobs)
                         }
                         Err(e) => {
-                            log_to_file(
-                                "WARN".to_string(),
-                                format!("Failed to send heartbeat: {}", e),
-                            );
+                            warn!("Failed to send heartbeat: {}", e);
+                            handle.record(Err(e.to_string()));
+                            (None, None)
+                        }
+                    };
+
+                    if let Some(jobs) = jobs {
+                        handle.jobs.dispatch(jobs);
+                    }
+
+                    if let Some(config) = config {
+                        match apply_agent_config(&config).await {
+                            Ok(Some(settings)) => {
+                                info!("Applied updated configuration from server");
+                                apply_runtime_settings(&app, &settings);
+
+                                let new_period = heartbeat_period(&settings);
+                                if new_period != period {
+                                    period = new_period;
+                                    heartbeat_interval = interval_at(Instant::now() + period, period);
+                                }
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                error!("Failed to apply server configuration: {}", e);
+                            }
                         }
                     }
                 }
@@ -153,31 +353,88 @@ pub fn start_heartbeat_task(running: Arc<AtomicBool>) {
                     // Daily health check log
                     match gather_system_info().await {
                         Ok(info) => {
-                            log_to_file(
-                                "INFO".to_string(),
-                                format!(
-                                    "Daily health check - Hostname: {}, Version: {}, IP: {}, MAC: {}",
-                                    info.hostname,
-                                    info.version,
-                                    info.ip_address.unwrap_or_else(|| "N/A".to_string()),
-                                    info.mac_address.unwrap_or_else(|| "N/A".to_string())
-                                ),
+                            info!(
+                                event = "health_check";
+                                "Daily health check - Hostname: {}, Version: {}, IP: {}, MAC: {}",
+                                info.hostname,
+                                info.version,
+                                info.ip_address.unwrap_or_else(|| "N/A".to_string()),
+                                info.mac_address.unwrap_or_else(|| "N/A".to_string())
                             );
                         }
                         Err(e) => {
-                            log_to_file(
-                                "ERROR".to_string(),
-                                format!("Daily health check failed: {}", e),
-                            );
+                            error!("Daily health check failed: {}", e);
                         }
                     }
+
+                    report_inventory().await;
+                    tokio::task::spawn_blocking(enforce_retention);
                 }
             }
         }
 
-        log_to_file(
-            "INFO".to_string(),
-            "Heartbeat background task stopped".to_string(),
-        );
+        info!("Heartbeat background task stopped");
     });
 }
+
+/// Sends hardware and software inventory, logging failures instead of returning them
+async fn report_inventory() {
+    if let Err(e) = send_inventory().await {
+        warn!("Failed to send hardware inventory: {}", e);
+    }
+
+    if let Err(e) = send_software_inventory().await {
+        warn!("Failed to send software inventory: {}", e);
+    }
+}
+
+/// Applies the parts of settings that take effect without a restart
+pub fn apply_runtime_settings(app: &AppHandle, settings: &Settings) {
+    if let Some(level) = &settings.log_level {
+        set_min_level(LogLevel::from(level.clone()));
+    }
+
+    let targets = settings
+        .log_targets
+        .iter()
+        .flatten()
+        .map(|(module, level)| (module.clone(), LogLevel::from(level.clone())))
+        .collect();
+    set_target_levels(targets);
+    set_json_output(settings.log_format.as_deref() == Some("json"));
+
+    let defaults = LogRetention::default();
+    set_retention(LogRetention {
+        max_total_bytes: settings
+            .log_max_total_mb
+            .map(|mb| mb.max(1) * 1024 * 1024)
+            .unwrap_or(defaults.max_total_bytes),
+        max_age: settings
+            .log_max_age_days
+            .map(|days| Duration::from_secs(days.max(1) * 86400))
+            .unwrap_or(defaults.max_age),
+    });
+    set_device_id(settings.device_id.clone());
+
+    // The site ID authenticates the device to the API, keep it out of logs and bundles
+    let mut secrets = vec![(settings.site_id.clone(), "site_id")];
+    if let Some(guid) = &settings.guid {
+        secrets.push((guid.clone(), "guid"));
+    }
+    let patterns = settings.log_redact_patterns.clone().unwrap_or_default();
+    for pattern in set_redaction(secrets, &patterns) {
+        warn!("Ignoring invalid log redaction pattern: {}", pattern);
+    }
+
+    if let Some(access) = app.try_state::<FileAccess>() {
+        access.set_configured_paths(settings.file_read_paths.as_deref().unwrap_or_default());
+    }
+    if let Some(access) = app.try_state::<RegistryAccess>() {
+        access.set_allowed_paths(settings.registry_read_paths.as_deref().unwrap_or_default());
+    }
+    if let Some(access) = app.try_state::<ConfigAccess>() {
+        access.set_allowed_keys(settings.config_read_keys.as_deref().unwrap_or_default());
+    }
+
+    crate::sync_tray_icon(app, settings.show_tray.unwrap_or(false));
+}
@@ -0,0 +1,303 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+// Firmware identifiers do not change while the agent runs, only query them once
+static FIRMWARE_IDENTITY: OnceLock<DeviceIdentity> = OnceLock::new();
+
+/// Placeholder values firmware vendors leave in SMBIOS fields
+const JUNK_VALUES: [&str; 16] = [
+    "to be filled by o.e.m.",
+    "to be filled by oem",
+    "default string",
+    "system serial number",
+    "chassis serial number",
+    "base board serial number",
+    "not specified",
+    "not applicable",
+    "not available",
+    "none",
+    "n/a",
+    "na",
+    "invalid",
+    "o.e.m.",
+    "0123456789",
+    "03000200-0400-0500-0006-000700080009",
+];
+
+/// Hardware identifiers gathered from every available source on this platform
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DeviceIdentity {
+    pub bios_serial: Option<String>,
+    pub system_uuid: Option<String>,
+    pub board_serial: Option<String>,
+    pub machine_id: Option<String>,
+    pub mac: Option<String>,
+    pub fingerprint: Option<String>,
+}
+
+/// Returns the trimmed value, or None if it is empty or a known placeholder
+pub fn sanitize_identifier(value: &str) -> Option<String> {
+    let trimmed = value.trim().trim_matches('\0').trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+
+    let lower = trimmed.to_lowercase();
+    if JUNK_VALUES.contains(&lower.as_str()) {
+        return None;
+    }
+
+    // All zeros / all F's (with or without dashes) and single repeated characters
+    let significant: Vec<char> = lower.chars().filter(|c| *c != '-' && *c != ' ').collect();
+    if significant.len() < 3 || significant.iter().all(|c| *c == significant[0]) {
+        return None;
+    }
+
+    Some(trimmed.to_string())
+}
+
+impl DeviceIdentity {
+    /// Builds a fingerprint from the most stable sources available. Firmware identifiers
+    /// are preferred, then machine-id. The MAC follows the default route, so it is only
+    /// used when there is nothing else.
+    fn compute_fingerprint(&self) -> Option<String> {
+        let firmware: Vec<(&str, &String)> = [
+            ("uuid", self.system_uuid.as_ref()),
+            ("bios", self.bios_serial.as_ref()),
+            ("board", self.board_serial.as_ref()),
+        ]
+        .into_iter()
+        .filter_map(|(name, value)| value.map(|value| (name, value)))
+        .collect();
+
+        let sources = if !firmware.is_empty() {
+            firmware
+        } else if let Some(machine_id) = &self.machine_id {
+            vec![("machine", machine_id)]
+        } else {
+            self.mac.iter().map(|mac| ("mac", mac)).collect()
+        };
+
+        if sources.is_empty() {
+            return None;
+        }
+
+        let mut hasher = Sha256::new();
+        for (name, value) in sources {
+            hasher.update(name.as_bytes());
+            hasher.update(b"=");
+            hasher.update(value.to_lowercase().as_bytes());
+            hasher.update(b";");
+        }
+        Some(format!("{:x}", hasher.finalize()))
+    }
+}
+
+fn query_firmware_identity() -> DeviceIdentity {
+    #[cfg(target_os = "linux")]
+    {
+        linux::resolve()
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::resolve()
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        macos::resolve()
+    }
+}
+
+/// Resolves the device identity, combining every source and computing the fingerprint
+pub fn resolve_device_identity(mac: Option<String>) -> DeviceIdentity {
+    let mut identity = FIRMWARE_IDENTITY.get_or_init(query_firmware_identity).clone();
+
+    identity.mac = mac.and_then(|mac| sanitize_identifier(&mac));
+    identity.fingerprint = identity.compute_fingerprint();
+    identity
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+
+    fn read(path: &str) -> Option<String> {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|value| sanitize_identifier(&value))
+    }
+
+    pub fn resolve() -> DeviceIdentity {
+        DeviceIdentity {
+            bios_serial: read("/sys/class/dmi/id/product_serial"),
+            system_uuid: read("/sys/class/dmi/id/product_uuid"),
+            board_serial: read("/sys/class/dmi/id/board_serial"),
+            machine_id: read("/etc/machine-id").or_else(|| read("/var/lib/dbus/machine-id")),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    // wmic has been removed from current Windows builds, query CIM through PowerShell instead
+    const IDENTITY_SCRIPT: &str = r#"
+[pscustomobject]@{
+  bios_serial = (Get-CimInstance Win32_BIOS).SerialNumber
+  system_uuid = (Get-CimInstance Win32_ComputerSystemProduct).UUID
+  board_serial = (Get-CimInstance Win32_BaseBoard | Select-Object -First 1).SerialNumber
+} | ConvertTo-Json -Compress
+"#;
+
+    #[derive(Deserialize, Default)]
+    struct RawIdentity {
+        bios_serial: Option<String>,
+        system_uuid: Option<String>,
+        board_serial: Option<String>,
+    }
+
+    fn machine_guid() -> Option<String> {
+        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
+        let key = hklm.open_subkey("SOFTWARE\\Microsoft\\Cryptography").ok()?;
+        let guid: String = key.get_value("MachineGuid").ok()?;
+        sanitize_identifier(&guid)
+    }
+
+    pub fn resolve() -> DeviceIdentity {
+        let raw = Command::new("powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", IDENTITY_SCRIPT])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()
+            .and_then(|output| serde_json::from_slice::<RawIdentity>(&output.stdout).ok())
+            .unwrap_or_default();
+
+        let clean = |value: Option<String>| value.and_then(|v| sanitize_identifier(&v));
+
+        DeviceIdentity {
+            bios_serial: clean(raw.bios_serial),
+            system_uuid: clean(raw.system_uuid),
+            board_serial: clean(raw.board_serial),
+            machine_id: machine_guid(),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+    use std::process::Command;
+
+    fn ioreg_value(output: &str, key: &str) -> Option<String> {
+        let line = output.lines().find(|line| line.contains(&format!("\"{}\"", key)))?;
+        let value = line.split('=').nth(1)?.trim().trim_matches('"');
+        sanitize_identifier(value)
+    }
+
+    pub fn resolve() -> DeviceIdentity {
+        let Ok(output) = Command::new("ioreg")
+            .args(["-rd1", "-c", "IOPlatformExpertDevice"])
+            .output()
+        else {
+            return DeviceIdentity::default();
+        };
+        let output = String::from_utf8_lossy(&output.stdout);
+
+        let system_uuid = ioreg_value(&output, "IOPlatformUUID");
+        DeviceIdentity {
+            bios_serial: ioreg_value(&output, "IOPlatformSerialNumber"),
+            machine_id: system_uuid.clone(),
+            system_uuid,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_placeholder_identifiers() {
+        for junk in [
+            "",
+            "   ",
+            "\0\0",
+            "To Be Filled By O.E.M.",
+            "Default string",
+            "Not Specified",
+            "N/A",
+            "00000000-0000-0000-0000-000000000000",
+            "FFFFFFFF-FFFF-FFFF-FFFF-FFFFFFFFFFFF",
+            "0 0 0 0",
+            "xx",
+            "03000200-0400-0500-0006-000700080009",
+        ] {
+            assert_eq!(sanitize_identifier(junk), None, "{:?}", junk);
+        }
+
+        assert_eq!(
+            sanitize_identifier("  C02XK1ABJG5H\0\n").as_deref(),
+            Some("C02XK1ABJG5H")
+        );
+        assert_eq!(
+            sanitize_identifier("4C4C4544-0042-3510-8052-B4C04F4E3732").as_deref(),
+            Some("4C4C4544-0042-3510-8052-B4C04F4E3732")
+        );
+    }
+
+    fn identity(machine_id: Option<&str>, mac: Option<&str>) -> DeviceIdentity {
+        DeviceIdentity {
+            machine_id: machine_id.map(str::to_string),
+            mac: mac.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn fingerprint_prefers_firmware_identifiers() {
+        let mut with_firmware = identity(Some("machine-a"), Some("aa:00:00:00:00:01"));
+        with_firmware.system_uuid = Some("4C4C4544-0042-3510-8052-B4C04F4E3732".to_string());
+        let mut other_sources = with_firmware.clone();
+        other_sources.machine_id = Some("machine-b".to_string());
+        other_sources.mac = None;
+
+        assert_eq!(
+            with_firmware.compute_fingerprint(),
+            other_sources.compute_fingerprint()
+        );
+    }
+
+    #[test]
+    fn fingerprint_ignores_the_mac_when_machine_id_is_known() {
+        let wired = identity(Some("machine-a"), Some("aa:00:00:00:00:01"));
+        let wireless = identity(Some("machine-a"), Some("aa:00:00:00:00:02"));
+        assert!(wired.compute_fingerprint().is_some());
+        assert_eq!(wired.compute_fingerprint(), wireless.compute_fingerprint());
+        assert_ne!(
+            wired.compute_fingerprint(),
+            identity(Some("machine-b"), Some("aa:00:00:00:00:01")).compute_fingerprint()
+        );
+    }
+
+    #[test]
+    fn fingerprint_falls_back_to_the_mac() {
+        let first = identity(None, Some("aa:00:00:00:00:01"));
+        let second = identity(None, Some("aa:00:00:00:00:02"));
+        assert!(first.compute_fingerprint().is_some());
+        assert_ne!(first.compute_fingerprint(), second.compute_fingerprint());
+        assert_eq!(identity(None, None).compute_fingerprint(), None);
+    }
+}
@@ -0,0 +1,548 @@
+use crate::device_manager::{get_api_endpoint, get_settings};
+use crate::error::AgentError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct HardwareInventory {
+    pub cpu: CpuInfo,
+    pub total_memory_bytes: Option<u64>,
+    pub disks: Vec<DiskInfo>,
+    pub bios: BiosInfo,
+    pub system: ProductInfo,
+    pub board: ProductInfo,
+    pub chassis_type: Option<String>,
+    pub os: OsInfo,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CpuInfo {
+    pub model: Option<String>,
+    pub physical_cores: Option<u32>,
+    pub logical_cores: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct DiskInfo {
+    pub mount_point: String,
+    pub filesystem: Option<String>,
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct BiosInfo {
+    pub vendor: Option<String>,
+    pub version: Option<String>,
+    pub date: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct ProductInfo {
+    pub vendor: Option<String>,
+    pub model: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct OsInfo {
+    pub name: Option<String>,
+    pub version: Option<String>,
+    pub build: Option<String>,
+    pub kernel: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct InventoryRequest {
+    pub guid: Option<String>,
+    pub version: String,
+    pub platform: String,
+    pub hardware: HardwareInventory,
+}
+
+/// Maps an SMBIOS chassis type code to a readable name
+pub fn chassis_type_name(code: u32) -> &'static str {
+    match code {
+        3 | 4 | 5 | 6 | 7 | 15 | 16 | 24 | 35 => "Desktop",
+        8 | 9 | 10 | 14 | 31 | 32 => "Laptop",
+        11 => "Handheld",
+        13 => "All in One",
+        17 | 23 | 25 | 28 | 29 => "Server",
+        30 => "Tablet",
+        34 | 36 => "Embedded",
+        1 => "Virtual",
+        _ => "Other",
+    }
+}
+
+fn clean(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// Collects the hardware inventory for the current platform. Fails instead of returning
+/// a partial inventory when the platform query itself does not work.
+pub async fn collect_hardware_inventory() -> Result<HardwareInventory, AgentError> {
+    #[cfg(target_os = "linux")]
+    {
+        Ok(tokio::task::spawn_blocking(linux::collect).await?)
+    }
+
+    #[cfg(target_os = "windows")]
+    {
+        windows::collect().await
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        Ok(tokio::task::spawn_blocking(macos::collect).await?)
+    }
+}
+
+/// Sends the hardware inventory to the server
+pub async fn send_inventory() -> Result<(), AgentError> {
+    let settings = get_settings().await?;
+
+    let device_id = settings
+        .device_id
+        .as_ref()
+        .ok_or(AgentError::NotRegistered)?;
+
+    let hardware = collect_hardware_inventory().await?;
+    let request = InventoryRequest {
+        guid: settings.guid.clone(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        platform: std::env::consts::OS.to_string(),
+        hardware,
+    };
+
+    let api_url = get_api_endpoint("/v1.0/inventory").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("Content-Type", "application/json")
+        .header("x-device-id", device_id)
+        .header("x-site-id", &settings.site_id)
+        .json(&request)
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::collections::HashSet;
+
+    const DMI_DIR: &str = "/sys/class/dmi/id";
+
+    fn read_dmi(name: &str) -> Option<String> {
+        std::fs::read_to_string(format!("{}/{}", DMI_DIR, name))
+            .ok()
+            .and_then(|value| clean(&value))
+    }
+
+    pub fn collect() -> HardwareInventory {
+        HardwareInventory {
+            cpu: read_cpu(),
+            total_memory_bytes: read_total_memory(),
+            disks: read_disks(),
+            bios: BiosInfo {
+                vendor: read_dmi("bios_vendor"),
+                version: read_dmi("bios_version"),
+                date: read_dmi("bios_date"),
+            },
+            system: ProductInfo {
+                vendor: read_dmi("sys_vendor"),
+                model: read_dmi("product_name"),
+            },
+            board: ProductInfo {
+                vendor: read_dmi("board_vendor"),
+                model: read_dmi("board_name"),
+            },
+            chassis_type: read_dmi("chassis_type")
+                .and_then(|code| code.parse::<u32>().ok())
+                .map(|code| chassis_type_name(code).to_string()),
+            os: read_os(),
+        }
+    }
+
+    fn read_cpu() -> CpuInfo {
+        let Ok(content) = std::fs::read_to_string("/proc/cpuinfo") else {
+            return CpuInfo::default();
+        };
+
+        let mut model = None;
+        let mut logical = 0u32;
+        let mut cores = HashSet::new();
+        let mut physical_id = String::new();
+
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+
+            match key.trim() {
+                "processor" => logical += 1,
+                "model name" if model.is_none() => model = clean(value),
+                "physical id" => physical_id = value.to_string(),
+                "core id" => {
+                    cores.insert(format!("{}:{}", physical_id, value));
+                }
+                _ => {}
+            }
+        }
+
+        CpuInfo {
+            model,
+            physical_cores: if cores.is_empty() {
+                None
+            } else {
+                Some(cores.len() as u32)
+            },
+            logical_cores: if logical == 0 { None } else { Some(logical) },
+        }
+    }
+
+    fn read_total_memory() -> Option<u64> {
+        let content = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let line = content.lines().find(|line| line.starts_with("MemTotal:"))?;
+        let kb: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+        Some(kb * 1024)
+    }
+
+    fn read_disks() -> Vec<DiskInfo> {
+        let Ok(content) = std::fs::read_to_string("/proc/mounts") else {
+            return Vec::new();
+        };
+
+        let mut seen = HashSet::new();
+        let mut disks = Vec::new();
+
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let (Some(device), Some(mount_point), Some(filesystem)) =
+                (parts.next(), parts.next(), parts.next())
+            else {
+                continue;
+            };
+
+            // Only real block devices, each counted once (bind mounts repeat them)
+            if !device.starts_with("/dev/") || device.starts_with("/dev/loop") {
+                continue;
+            }
+            if !seen.insert(device.to_string()) {
+                continue;
+            }
+
+            let mount_point = mount_point.replace("\\040", " ");
+            if let Some((total_bytes, free_bytes)) = statvfs(&mount_point) {
+                disks.push(DiskInfo {
+                    mount_point,
+                    filesystem: Some(filesystem.to_string()),
+                    total_bytes,
+                    free_bytes,
+                });
+            }
+        }
+
+        disks
+    }
+
+    #[allow(clippy::unnecessary_cast)]
+    fn statvfs(path: &str) -> Option<(u64, u64)> {
+        let c_path = std::ffi::CString::new(path).ok()?;
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+
+        if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+
+        let block_size = stat.f_frsize as u64;
+        Some((
+            stat.f_blocks as u64 * block_size,
+            stat.f_bavail as u64 * block_size,
+        ))
+    }
+
+    fn read_os() -> OsInfo {
+        let mut os = OsInfo {
+            kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .and_then(|value| clean(&value)),
+            ..Default::default()
+        };
+
+        if let Ok(content) = std::fs::read_to_string("/etc/os-release") {
+            for line in content.lines() {
+                let Some((key, value)) = line.split_once('=') else {
+                    continue;
+                };
+                let value = value.trim_matches('"');
+
+                match key {
+                    "PRETTY_NAME" => os.name = clean(value),
+                    "VERSION_ID" => os.version = clean(value),
+                    "BUILD_ID" => os.build = clean(value),
+                    _ => {}
+                }
+            }
+        }
+
+        os
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+    use tokio::process::Command;
+    use tokio::time::{timeout, Duration};
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+    const INVENTORY_TIMEOUT_SECS: u64 = 120;
+
+    // Stop on the first failed query so a broken CIM repository can't pass for an empty inventory
+    const INVENTORY_SCRIPT: &str = r#"
+$ErrorActionPreference = 'Stop'
+$cpus = @(Get-CimInstance Win32_Processor)
+$cs = Get-CimInstance Win32_ComputerSystem
+$bios = Get-CimInstance Win32_BIOS
+$board = Get-CimInstance Win32_BaseBoard
+$enclosure = Get-CimInstance Win32_SystemEnclosure | Select-Object -First 1
+$os = Get-CimInstance Win32_OperatingSystem
+$disks = @(Get-CimInstance Win32_LogicalDisk -Filter "DriveType=3")
+[pscustomobject]@{
+  cpu_model = $cpus[0].Name
+  physical_cores = ($cpus | Measure-Object -Property NumberOfCores -Sum).Sum
+  logical_cores = ($cpus | Measure-Object -Property NumberOfLogicalProcessors -Sum).Sum
+  total_memory = $cs.TotalPhysicalMemory
+  system_vendor = $cs.Manufacturer
+  system_model = $cs.Model
+  bios_vendor = $bios.Manufacturer
+  bios_version = $bios.SMBIOSBIOSVersion
+  bios_date = if ($bios.ReleaseDate) { $bios.ReleaseDate.ToString("yyyy-MM-dd") } else { $null }
+  board_vendor = $board.Manufacturer
+  board_model = $board.Product
+  chassis_type = @($enclosure.ChassisTypes)[0]
+  os_name = $os.Caption
+  os_version = $os.Version
+  os_build = $os.BuildNumber
+  disks = @($disks | ForEach-Object {
+    [pscustomobject]@{ mount = $_.DeviceID; fs = $_.FileSystem; total = $_.Size; free = $_.FreeSpace }
+  })
+} | ConvertTo-Json -Depth 3 -Compress
+"#;
+
+    #[derive(Deserialize)]
+    struct RawInventory {
+        cpu_model: Option<String>,
+        physical_cores: Option<u32>,
+        logical_cores: Option<u32>,
+        total_memory: Option<u64>,
+        system_vendor: Option<String>,
+        system_model: Option<String>,
+        bios_vendor: Option<String>,
+        bios_version: Option<String>,
+        bios_date: Option<String>,
+        board_vendor: Option<String>,
+        board_model: Option<String>,
+        chassis_type: Option<u32>,
+        os_name: Option<String>,
+        os_version: Option<String>,
+        os_build: Option<String>,
+        #[serde(default)]
+        disks: Vec<RawDisk>,
+    }
+
+    #[derive(Deserialize)]
+    struct RawDisk {
+        mount: String,
+        fs: Option<String>,
+        total: Option<u64>,
+        free: Option<u64>,
+    }
+
+    fn opt(value: Option<String>) -> Option<String> {
+        value.and_then(|v| clean(&v))
+    }
+
+    pub async fn collect() -> Result<HardwareInventory, AgentError> {
+        let mut command = Command::new("powershell.exe");
+        command
+            .args([
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                INVENTORY_SCRIPT,
+            ])
+            .creation_flags(CREATE_NO_WINDOW)
+            .kill_on_drop(true);
+
+        let output = timeout(
+            Duration::from_secs(INVENTORY_TIMEOUT_SECS),
+            command.output(),
+        )
+        .await
+        .map_err(|_| {
+            AgentError::Io(format!(
+                "Inventory query timed out after {}s",
+                INVENTORY_TIMEOUT_SECS
+            ))
+        })??;
+        if !output.status.success() {
+            return Err(AgentError::Io(format!(
+                "Inventory query failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        let raw: RawInventory = serde_json::from_slice(&output.stdout)?;
+
+        Ok(HardwareInventory {
+            cpu: CpuInfo {
+                model: opt(raw.cpu_model),
+                physical_cores: raw.physical_cores,
+                logical_cores: raw.logical_cores,
+            },
+            total_memory_bytes: raw.total_memory,
+            disks: raw
+                .disks
+                .into_iter()
+                .map(|disk| DiskInfo {
+                    mount_point: disk.mount,
+                    filesystem: opt(disk.fs),
+                    total_bytes: disk.total.unwrap_or(0),
+                    free_bytes: disk.free.unwrap_or(0),
+                })
+                .collect(),
+            bios: BiosInfo {
+                vendor: opt(raw.bios_vendor),
+                version: opt(raw.bios_version),
+                date: opt(raw.bios_date),
+            },
+            system: ProductInfo {
+                vendor: opt(raw.system_vendor),
+                model: opt(raw.system_model),
+            },
+            board: ProductInfo {
+                vendor: opt(raw.board_vendor),
+                model: opt(raw.board_model),
+            },
+            chassis_type: raw
+                .chassis_type
+                .map(|code| chassis_type_name(code).to_string()),
+            os: OsInfo {
+                name: opt(raw.os_name),
+                version: opt(raw.os_version),
+                build: opt(raw.os_build),
+                kernel: None,
+            },
+        })
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+    use std::process::Command;
+
+    fn run(program: &str, args: &[&str]) -> Option<String> {
+        let output = Command::new(program).args(args).output().ok()?;
+        clean(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn sysctl(name: &str) -> Option<String> {
+        run("sysctl", &["-n", name])
+    }
+
+    pub fn collect() -> HardwareInventory {
+        let mut system = ProductInfo {
+            vendor: Some("Apple Inc.".to_string()),
+            model: None,
+        };
+        let mut bios = BiosInfo {
+            vendor: Some("Apple Inc.".to_string()),
+            ..Default::default()
+        };
+
+        if let Some(profile) = run("system_profiler", &["SPHardwareDataType"]) {
+            for line in profile.lines() {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                match key.trim() {
+                    "Model Identifier" => system.model = clean(value),
+                    "System Firmware Version" | "Boot ROM Version" => {
+                        bios.version = clean(value)
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let chassis_type = system.model.as_ref().map(|model| {
+            if model.contains("Book") {
+                "Laptop".to_string()
+            } else {
+                "Desktop".to_string()
+            }
+        });
+
+        HardwareInventory {
+            cpu: CpuInfo {
+                model: sysctl("machdep.cpu.brand_string"),
+                physical_cores: sysctl("hw.physicalcpu").and_then(|v| v.parse().ok()),
+                logical_cores: sysctl("hw.logicalcpu").and_then(|v| v.parse().ok()),
+            },
+            total_memory_bytes: sysctl("hw.memsize").and_then(|v| v.parse().ok()),
+            disks: read_disks(),
+            bios,
+            board: system.clone(),
+            system,
+            chassis_type,
+            os: OsInfo {
+                name: run("sw_vers", &["-productName"]),
+                version: run("sw_vers", &["-productVersion"]),
+                build: run("sw_vers", &["-buildVersion"]),
+                kernel: sysctl("kern.osrelease"),
+            },
+        }
+    }
+
+    fn read_disks() -> Vec<DiskInfo> {
+        let Some(output) = run("df", &["-kP", "-T", "apfs,hfs"]) else {
+            return Vec::new();
+        };
+
+        output
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let parts: Vec<&str> = line.split_whitespace().collect();
+                if parts.len() < 6 || !parts[0].starts_with("/dev/") {
+                    return None;
+                }
+                let total_kb: u64 = parts[1].parse().ok()?;
+                let free_kb: u64 = parts[3].parse().ok()?;
+                Some(DiskInfo {
+                    mount_point: parts[5..].join(" "),
+                    filesystem: None,
+                    total_bytes: total_kb * 1024,
+                    free_bytes: free_kb * 1024,
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,386 @@
+use crate::device_manager::{get_api_endpoint, get_settings};
+use crate::error::AgentError;
+use crate::heartbeat::gather_system_info;
+use crate::log_bundle::upload_log_bundle;
+use crate::scripts::{run_script, RunScriptPayload};
+use log::{error, info, log, warn, Level};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+/// A pending job handed to the agent in the heartbeat reply.
+/// The payload is only decoded at dispatch time so one unknown job type
+/// cannot break parsing of the whole heartbeat response.
+#[derive(Deserialize, Debug, Clone)]
+pub struct Job {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub job_type: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+/// Every job type the agent knows how to run, tagged by `type` with its `payload`
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "payload", rename_all = "snake_case")]
+pub enum JobKind {
+    /// Round-trip check, echoes the message back as stdout
+    Ping { message: Option<String> },
+    /// Reports the same system info sent with heartbeats
+    CollectSystemInfo,
+    /// Downloads a signed script and runs it with the platform shell, if the `run_scripts`
+    /// feature is enabled
+    RunScript(RunScriptPayload),
+    /// Uploads the agent log bundle, stdout is the bundle ID
+    UploadLogs,
+}
+
+impl JobKind {
+    fn parse(job: &Job) -> Result<JobKind, String> {
+        let mut value = serde_json::json!({ "type": job.job_type });
+        if !job.payload.is_null() {
+            value["payload"] = job.payload.clone();
+        }
+        serde_json::from_value(value)
+            .map_err(|e| format!("Unsupported or malformed job '{}': {}", job.job_type, e))
+    }
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+    Rejected,
+    TimedOut,
+}
+
+/// Output produced by a job handler
+#[derive(Debug, Default)]
+pub struct JobOutput {
+    pub exit_code: i32,
+    pub stdout: String,
+    pub stderr: String,
+}
+
+impl JobOutput {
+    pub fn success(stdout: String) -> Self {
+        JobOutput {
+            exit_code: 0,
+            stdout,
+            stderr: String::new(),
+        }
+    }
+}
+
+/// Result posted back to `/v1.0/jobs/{id}/result`
+#[derive(Serialize, Debug, Clone)]
+pub struct JobResult {
+    pub job_type: String,
+    pub status: JobStatus,
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    pub started_at: String,
+    pub finished_at: String,
+    pub duration_ms: u64,
+}
+
+/// Error returned by a job handler, mapped to the reported status
+#[derive(Debug)]
+pub enum JobError {
+    Failed(String),
+    Rejected(String),
+    TimedOut(JobOutput),
+}
+
+/// Where a job the server listed stands on this device
+#[derive(Debug)]
+enum JobState {
+    /// Queued or running, or its result is being reported
+    Running,
+    /// Finished, but the result has not reached the server yet
+    Unreported(JobResult),
+}
+
+/// Runs jobs from heartbeat replies one at a time and reports their results.
+/// A job the server lists again is never run twice: it is skipped while it is still
+/// queued or running, and only its result is sent again if reporting it failed.
+#[derive(Clone, Default)]
+pub struct JobDispatcher {
+    jobs: Arc<Mutex<HashMap<String, JobState>>>,
+    // Held while working through a batch, so batches from later heartbeats wait their turn
+    worker: Arc<tokio::sync::Mutex<()>>,
+}
+
+impl JobDispatcher {
+    fn lock_jobs(&self) -> std::sync::MutexGuard<'_, HashMap<String, JobState>> {
+        match self.jobs.lock() {
+            Ok(jobs) => jobs,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    /// Splits a heartbeat's job list into jobs to run and cached results to report again
+    fn claim(&self, jobs: Vec<Job>) -> Vec<(Job, Option<JobResult>)> {
+        let mut states = self.lock_jobs();
+
+        // The server stopped listing these, so it no longer wants their results
+        let listed: HashSet<&str> = jobs.iter().map(|job| job.id.as_str()).collect();
+        states
+            .retain(|id, state| matches!(state, JobState::Running) || listed.contains(id.as_str()));
+
+        let mut claimed = Vec::new();
+        for job in jobs {
+            if !is_valid_job_id(&job.id) {
+                warn!("Skipping job with invalid id {:?}", job.id);
+                continue;
+            }
+
+            match states.insert(job.id.clone(), JobState::Running) {
+                None => claimed.push((job, None)),
+                Some(JobState::Unreported(result)) => claimed.push((job, Some(result))),
+                Some(JobState::Running) => {}
+            }
+        }
+        claimed
+    }
+
+    async fn report(&self, id: &str, result: JobResult) {
+        match report_job_result(id, &result).await {
+            Ok(()) => {
+                self.lock_jobs().remove(id);
+            }
+            Err(e) => {
+                error!("Failed to report result for job {}, will retry: {}", id, e);
+                self.lock_jobs()
+                    .insert(id.to_string(), JobState::Unreported(result));
+            }
+        }
+    }
+
+    /// Queues the jobs on a background task so the heartbeat loop is not blocked
+    pub fn dispatch(&self, jobs: Vec<Job>) {
+        let claimed = self.claim(jobs);
+        if claimed.is_empty() {
+            return;
+        }
+
+        let dispatcher = self.clone();
+        tauri::async_runtime::spawn(async move {
+            let _worker = dispatcher.worker.lock().await;
+            for (job, cached) in claimed {
+                let id = job.id.clone();
+                let result = match cached {
+                    Some(result) => result,
+                    None => execute_job(job).await,
+                };
+                dispatcher.report(&id, result).await;
+            }
+        });
+    }
+}
+
+/// Job IDs end up in API paths, so only plain identifiers are accepted
+fn is_valid_job_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// API endpoint for one of a job's sub-resources, e.g. `result`
+pub(crate) async fn job_endpoint(job_id: &str, resource: &str) -> Result<String, AgentError> {
+    if !is_valid_job_id(job_id) {
+        return Err(AgentError::Parse(format!("Invalid job id {:?}", job_id)));
+    }
+    get_api_endpoint(&format!("/v1.0/jobs/{}/{}", job_id, resource)).await
+}
+
+/// Runs a single job through its handler and times it
+pub async fn execute_job(job: Job) -> JobResult {
+    let job_type = job.job_type.clone();
+    info!("Running job {} ({})", job.id, job_type);
+
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let started = Instant::now();
+
+    let outcome = match JobKind::parse(&job) {
+        Ok(JobKind::Ping { message }) => run_ping(message).await,
+        Ok(JobKind::CollectSystemInfo) => run_collect_system_info().await,
+        Ok(JobKind::RunScript(payload)) => run_script(&job.id, payload).await,
+        Ok(JobKind::UploadLogs) => run_upload_logs().await,
+        Err(e) => Err(JobError::Rejected(e)),
+    };
+
+    let duration_ms = started.elapsed().as_millis() as u64;
+    let finished_at = chrono::Utc::now().to_rfc3339();
+
+    let (status, exit_code, stdout, stderr) = match outcome {
+        Ok(output) => {
+            let status = if output.exit_code == 0 {
+                JobStatus::Succeeded
+            } else {
+                JobStatus::Failed
+            };
+            (status, Some(output.exit_code), output.stdout, output.stderr)
+        }
+        Err(JobError::Failed(e)) => (JobStatus::Failed, None, String::new(), e),
+        Err(JobError::Rejected(e)) => (JobStatus::Rejected, None, String::new(), e),
+        Err(JobError::TimedOut(output)) => {
+            (JobStatus::TimedOut, None, output.stdout, output.stderr)
+        }
+    };
+
+    let level = if status == JobStatus::Succeeded {
+        Level::Info
+    } else {
+        Level::Warn
+    };
+    log!(
+        level,
+        event = "job_finished";
+        "Job {} ({}) finished with status {:?} in {}ms",
+        job.id, job_type, status, duration_ms
+    );
+
+    JobResult {
+        job_type,
+        status,
+        exit_code,
+        stdout,
+        stderr,
+        started_at,
+        finished_at,
+        duration_ms,
+    }
+}
+
+async fn run_ping(message: Option<String>) -> Result<JobOutput, JobError> {
+    Ok(JobOutput::success(message.unwrap_or_else(|| "pong".to_string())))
+}
+
+async fn run_collect_system_info() -> Result<JobOutput, JobError> {
+    let info = gather_system_info()
+        .await
+        .map_err(|e| JobError::Failed(e.to_string()))?;
+    let json = serde_json::to_string(&info).map_err(|e| JobError::Failed(e.to_string()))?;
+    Ok(JobOutput::success(json))
+}
+
+async fn run_upload_logs() -> Result<JobOutput, JobError> {
+    let bundle_id = upload_log_bundle("job")
+        .await
+        .map_err(|e| JobError::Failed(e.to_string()))?;
+    Ok(JobOutput::success(bundle_id))
+}
+
+/// Posts a job result back to the server
+pub async fn report_job_result(
+    job_id: &str,
+    result: &JobResult,
+) -> Result<(), AgentError> {
+    let settings = get_settings().await?;
+    let device_id = settings
+        .device_id
+        .as_ref()
+        .ok_or(AgentError::NotRegistered)?;
+
+    let api_url = job_endpoint(job_id, "result").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("Content-Type", "application/json")
+        .header("x-device-id", device_id)
+        .header("x-site-id", &settings.site_id)
+        .json(result)
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(id: &str) -> Job {
+        Job {
+            id: id.to_string(),
+            job_type: "ping".to_string(),
+            payload: serde_json::Value::Null,
+        }
+    }
+
+    fn result() -> JobResult {
+        JobResult {
+            job_type: "ping".to_string(),
+            status: JobStatus::Succeeded,
+            exit_code: Some(0),
+            stdout: "pong".to_string(),
+            stderr: String::new(),
+            started_at: String::new(),
+            finished_at: String::new(),
+            duration_ms: 0,
+        }
+    }
+
+    #[test]
+    fn relisted_jobs_are_not_run_twice() {
+        let dispatcher = JobDispatcher::default();
+        let claimed = dispatcher.claim(vec![job("a"), job("b")]);
+        assert_eq!(claimed.len(), 2);
+        assert!(claimed.iter().all(|(_, cached)| cached.is_none()));
+
+        // Still running
+        assert!(dispatcher.claim(vec![job("a")]).is_empty());
+
+        // Finished but unreported: only the cached result comes back
+        dispatcher
+            .lock_jobs()
+            .insert("a".to_string(), JobState::Unreported(result()));
+        let claimed = dispatcher.claim(vec![job("a")]);
+        assert_eq!(claimed.len(), 1);
+        assert_eq!(claimed[0].1.as_ref().unwrap().stdout, "pong");
+        assert!(dispatcher.claim(vec![job("a")]).is_empty());
+    }
+
+    #[test]
+    fn drops_results_the_server_no_longer_wants() {
+        let dispatcher = JobDispatcher::default();
+        dispatcher
+            .lock_jobs()
+            .insert("gone".to_string(), JobState::Unreported(result()));
+        dispatcher
+            .lock_jobs()
+            .insert("running".to_string(), JobState::Running);
+
+        dispatcher.claim(Vec::new());
+        let states = dispatcher.lock_jobs();
+        assert!(!states.contains_key("gone"));
+        assert!(states.contains_key("running"));
+    }
+
+    #[test]
+    fn skips_job_ids_that_are_not_plain_identifiers() {
+        let dispatcher = JobDispatcher::default();
+        for id in ["", "../devices", "a/b", "a?b", "a%2Fb", &"x".repeat(129)] {
+            assert!(dispatcher.claim(vec![job(id)]).is_empty(), "{:?}", id);
+        }
+        assert_eq!(
+            dispatcher
+                .claim(vec![job("3f2b9c1e-77a4-4d0e-9c1a-5b6f0e2d8a10")])
+                .len(),
+            1
+        );
+    }
+}
@@ -1,24 +1,54 @@
 mod device_manager;
 mod device_registration;
+mod error;
+mod file_access;
 mod heartbeat;
+mod identity;
+mod inventory;
+mod jobs;
+mod log_bundle;
 mod logger;
+mod network;
+mod registry;
+mod rmm;
+mod screenshot;
+mod screenshot_edit;
+mod scripts;
+mod settings_migrations;
+mod software;
+mod system_config;
+mod tickets;
 
 use base64::engine::general_purpose;
 use base64::Engine;
 use std::path::PathBuf;
-use std::sync::atomic::AtomicBool;
-use std::sync::Arc;
 use tauri::{
-    AppHandle, Emitter, EventTarget, Manager, WebviewUrl, WebviewWindowBuilder,
+    AppHandle, Emitter, EventTarget, Manager, RunEvent, WebviewUrl, WebviewWindowBuilder,
     tray::TrayIconBuilder,
     menu::{Menu, MenuItem}
 };
-use tauri_plugin_screenshots::{get_monitor_screenshot, get_screenshotable_monitors};
 
-use device_manager::{get_settings, is_device_registered, get_rmm_device_id};
-use device_registration::register_device_with_server;
-use heartbeat::{start_heartbeat_task, gather_system_info, HeartbeatRequest};
+use device_manager::{get_settings, is_device_registered};
+use device_registration::{run_registration_supervisor, RegistrationState, RegistrationStatus};
+use error::AgentError;
+use file_access::{FileAccess, PickedFile};
+use heartbeat::{
+    apply_runtime_settings, start_heartbeat_task, gather_system_info, HeartbeatHandle,
+    HeartbeatRequest, HeartbeatStatus,
+};
+use log::{error, info, warn};
 use logger::log_to_file;
+use registry::{Hive, RegistryAccess, RegistryValue};
+use rmm::{detect_rmm_agents, RmmAgent};
+use screenshot::{MonitorInfo, ScreenshotOptions, ScreenshotResult};
+use screenshot_edit::ScreenshotEdit;
+use system_config::{ConfigAccess, ConfigQuery, ConfigValue};
+use tickets::{
+    dismiss_ticket, list_queued_tickets, start_outbox_task, QueuedTicket, TicketOutbox,
+    TicketRequest,
+};
+
+pub(crate) const TRAY_ID: &str = "main";
 
 #[cfg(target_os = "windows")]
 fn acquire_single_instance_lock() -> Option<windows_sys::Win32::Foundation::HANDLE> {
@@ -52,13 +82,12 @@ fn acquire_single_instance_lock() -> Option<()> {
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logger::init();
+
     let _instance_lock = match acquire_single_instance_lock() {
         Some(lock) => lock,
         None => {
-            log_to_file(
-                "WARN".into(),
-                "Another instance is already running for this user. Exiting.".into(),
-            );
+            warn!("Another instance is already running for this user. Exiting.");
             return;
         }
     };
@@ -70,79 +99,46 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::new().build())
         .plugin(tauri_plugin_opener::init())
-        .plugin(tauri_plugin_screenshots::init())
         .setup(|app| {
-            // Create atomic flags for background task control
-            // let heartbeat_running = Arc::new(AtomicBool::new(true));
-            // let heartbeat_flag = heartbeat_running.clone();
+            // Store the heartbeat handle in app state for status queries and shutdown
+            let heartbeat = HeartbeatHandle::default();
+            app.manage(heartbeat.clone());
+
+            // Keep retrying registration in the background until it succeeds
+            app.manage(RegistrationStatus::default());
 
-            // Store the flags in app state for cleanup
-            // app.manage(heartbeat_running);
+            // Decides which files the webview may read through the agent
+            app.manage(FileAccess::default());
+            app.manage(RegistryAccess::default());
+            app.manage(ConfigAccess::default());
 
-            // Check and register device on first launch
+            // Tickets that could not be sent are kept on disk and retried in the background
+            let outbox = TicketOutbox::default();
+            app.manage(outbox.clone());
+            start_outbox_task(app.app_handle().clone(), outbox.clone());
+
+            let registration_handle = app.app_handle().clone();
             tauri::async_runtime::spawn(async move {
-                match register_device_with_server().await {
-                    Ok(response) => {
-                        log_to_file(
-                            String::from("INFO"),
-                            String::from("Device registered successfully"),
-                        );
-                        log_to_file(
-                            String::from("INFO"),
-                            format!("Device ID: {}", response.data.device_id),
-                        );
-                        log_to_file(
-                            String::from("INFO"),
-                            format!("GUID: {}", response.data.guid),
-                        );
-                    }
-                    Err(e) => {
-                        log_to_file(
-                            String::from("ERROR"),
-                            format!("Failed to regiter device: {}", e),
-                        );
-                        log_to_file(
-                            String::from("ERROR"),
-                            String::from("Will retry on next launch"),
-                        );
-                    }
-                }
+                run_registration_supervisor(registration_handle.clone()).await;
 
-                // Start background tasks after registration check
-                // start_heartbeat_task(heartbeat_flag.clone());
+                // Start background tasks once the device is registered
+                start_heartbeat_task(registration_handle, heartbeat);
+                outbox.wake();
             });
 
-            // Conditionally create system tray based on settings
+            // Apply log settings and conditionally create system tray based on settings
             let app_handle = app.app_handle().clone();
             tauri::async_runtime::spawn(async move {
                 match get_settings().await {
-                    Ok(settings) => {
-                        // Only create tray if show_tray is explicitly set to true
-                        if settings.show_tray.unwrap_or(false) {
-                            log_to_file(
-                                String::from("INFO"),
-                                String::from("show_tray is enabled, creating tray icon"),
-                            );
-                            if let Err(e) = create_tray_icon(&app_handle) {
-                                log_to_file(
-                                    String::from("ERROR"),
-                                    format!("Failed to create tray icon: {}", e),
-                                );
-                            }
-                        } else {
-                            log_to_file(
-                                String::from("INFO"),
-                                String::from("show_tray is disabled or not set, skipping tray creation"),
-                            );
-                        }
-                    }
+                    Ok(settings) => apply_runtime_settings(&app_handle, &settings),
                     Err(e) => {
-                        log_to_file(
-                            String::from("WARN"),
-                            format!("Could not load settings for tray creation: {}", e),
-                        );
+                        warn!("Could not load settings for tray creation: {}", e);
                     }
                 }
+
+                // Clean up logs left by earlier runs and versions. Not tied to registration,
+                // unlike the periodic pass in the heartbeat loop.
+                let _ = tauri::async_runtime::spawn_blocking(logger::enforce_retention).await;
             });
 
             #[cfg(target_os = "macos")]
@@ -164,23 +160,56 @@ pub fn run() {
         .invoke_handler(tauri::generate_handler![
             get_settings_info,
             check_registration_status,
+            get_registration_state,
+            get_heartbeat_status,
             hide_window,
             show_window,
             take_screenshot,
+            get_monitors,
+            edit_screenshot,
             read_file_text,
             read_file_base64,
             read_file_binary,
+            pick_image_file,
+            release_file,
             read_registry_value,
+            query_system_config,
             log_to_file,
             get_os_info,
-            get_rmm_id
+            get_rmm_id,
+            upload_logs,
+            submit_ticket,
+            get_queued_tickets,
+            dismiss_queued_ticket
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            if let RunEvent::Exit = event {
+                if let Some(heartbeat) = app.try_state::<HeartbeatHandle>() {
+                    heartbeat.stop();
+                }
+            }
+        });
+}
+
+/// Creates or removes the tray icon to match the `show_tray` setting
+pub(crate) fn sync_tray_icon(app: &AppHandle, show: bool) {
+    let exists = app.tray_by_id(TRAY_ID).is_some();
+
+    if show && !exists {
+        info!("show_tray is enabled, creating tray icon");
+        if let Err(e) = create_tray_icon(app) {
+            error!("Failed to create tray icon: {}", e);
+        }
+    } else if !show && exists {
+        info!("show_tray is disabled, removing tray icon");
+        let _ = app.remove_tray_by_id(TRAY_ID);
+    }
 }
 
-fn create_tray_icon(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
-    log_to_file(String::from("INFO"), String::from("Creating system tray icon"));
+fn create_tray_icon(app: &AppHandle) -> Result<(), AgentError> {
+    info!("Creating system tray icon");
 
     let request_support_sc_i = MenuItem::with_id(
         app,
@@ -202,9 +231,14 @@ fn create_tray_icon(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     // Create menu with items
     let menu = Menu::with_items(app, &[&request_support_sc_i, &request_support_i, &about_i])?;
 
+    let icon = app
+        .default_window_icon()
+        .ok_or_else(|| AgentError::Io("No window icon is bundled for the tray".to_string()))?
+        .clone();
+
     // Build tray icon with menu
-    let _tray = TrayIconBuilder::new()
-        .icon(app.default_window_icon().unwrap().clone())
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
+        .icon(icon)
         .menu(&menu)
         .on_menu_event(|app, event| match event.id.as_ref() {
             "request_support_sc" => {
@@ -223,7 +257,7 @@ fn create_tray_icon(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
         })
         .build(app)?;
 
-    log_to_file(String::from("INFO"), String::from("System tray icon created successfully"));
+    info!("System tray icon created successfully");
     Ok(())
 }
 
@@ -253,14 +287,18 @@ fn create_support_window(app: &AppHandle) {
 fn handle_support_window(app: &AppHandle, screenshot: bool) {
     let app_handle = app.clone();
 
-    log_to_file(String::from("INFO"), format!("Opening support window with screenshot set to {}", screenshot));
+    info!("Opening support window with screenshot set to {}", screenshot);
     tauri::async_runtime::spawn(async move {
-        let mut screenshot_path: Option<PathBuf> = None;
+        let mut screenshot_path: Option<String> = None;
 
-        // Step 1: Take screenshot first (if requested)
+        // Step 1: Take screenshot first (if requested), every monitor combined into one image
         if screenshot {
-            if let Ok(path) = take_screenshot_internal(app_handle.clone()).await {
-                screenshot_path = Some(path);
+            let options = ScreenshotOptions {
+                monitor_id: None,
+                stitch: true,
+            };
+            if let Ok(result) = take_screenshot_internal(app_handle.clone(), options).await {
+                screenshot_path = result.preferred_path().map(String::from);
             }
         }
 
@@ -283,216 +321,367 @@ fn handle_support_window(app: &AppHandle, screenshot: bool) {
     });
 }
 
-async fn take_screenshot_internal(app: AppHandle) -> Result<PathBuf, String> {
-    log_to_file(String::from("INFO"), String::from("Starting screenshot capture"));
+async fn take_screenshot_internal(
+    app: AppHandle,
+    options: ScreenshotOptions,
+) -> Result<ScreenshotResult, AgentError> {
+    info!("Starting screenshot capture");
 
     // Hide window if it exists
     if let Some(window) = app.get_webview_window("support") {
-        log_to_file(String::from("INFO"), String::from("Hiding support window before screenshot"));
+        info!("Hiding support window before screenshot");
         let _ = window.hide();
         // Give time for window to hide
         tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
     } else {
-        log_to_file(String::from("INFO"), String::from("No support window to hide"));
+        info!("No support window to hide");
     }
 
-    // Get first available monitor
-    log_to_file(String::from("INFO"), String::from("Getting screenshotable monitors"));
-    let monitors = get_screenshotable_monitors().await
+    let result = tauri::async_runtime::spawn_blocking(move || screenshot::capture(&options))
+        .await?
         .map_err(|e| {
-            let err_msg = format!("Failed to get monitors: {}", e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            error!("{}", e);
+            e
         })?;
 
-    if monitors.is_empty() {
-        let err_msg = String::from("No screenshotable monitors found");
-        log_to_file(String::from("ERROR"), err_msg.clone());
-        return Err(err_msg);
+    for capture in &result.captures {
+        info!("Screenshot of {} saved to: {}", capture.monitor.name, capture.path);
     }
-
-    log_to_file(String::from("INFO"), format!("Found {} monitor(s), capturing from first monitor", monitors.len()));
-    let path = get_monitor_screenshot(app, monitors[0].id).await
-        .map_err(|e| {
-            let err_msg = format!("Failed to capture screenshot: {}", e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
-        })?;
-
-    log_to_file(String::from("INFO"), format!("Screenshot saved to: {}", path.display()));
-    Ok(path)
+    if let Some(path) = &result.stitched_path {
+        info!("Combined screenshot saved to: {}", path);
+    }
+    Ok(result)
 }
 
 #[tauri::command]
-fn hide_window(app: tauri::AppHandle, label: String) -> Result<(), String> {
-    log_to_file(String::from("INFO"), format!("Hiding window: {}", label));
+fn hide_window(app: tauri::AppHandle, label: String) -> Result<(), AgentError> {
+    info!("Hiding window: {}", label);
     if let Some(window) = app.get_webview_window(&label) {
         window.hide().map_err(|e| {
-            let err_msg = format!("Failed to hide window {}: {}", label, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            let err = AgentError::Io(format!("Failed to hide window {}: {}", label, e));
+            error!("{}", err);
+            err
         })?;
-        log_to_file(String::from("INFO"), format!("Successfully hidden window: {}", label));
+        info!("Successfully hidden window: {}", label);
     } else {
-        log_to_file(String::from("WARN"), format!("Window not found: {}", label));
+        warn!("Window not found: {}", label);
     }
     Ok(())
 }
 
 #[tauri::command]
-fn show_window(app: tauri::AppHandle, label: String) -> Result<(), String> {
-    log_to_file(String::from("INFO"), format!("Showing window: {}", label));
+fn show_window(app: tauri::AppHandle, label: String) -> Result<(), AgentError> {
+    info!("Showing window: {}", label);
     if let Some(window) = app.get_webview_window(&label) {
         window.show().map_err(|e| {
-            let err_msg = format!("Failed to show window {}: {}", label, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            let err = AgentError::Io(format!("Failed to show window {}: {}", label, e));
+            error!("{}", err);
+            err
         })?;
         window.set_focus().map_err(|e| {
-            let err_msg = format!("Failed to focus window {}: {}", label, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            let err = AgentError::Io(format!("Failed to focus window {}: {}", label, e));
+            error!("{}", err);
+            err
         })?;
-        log_to_file(String::from("INFO"), format!("Successfully shown window: {}", label));
+        info!("Successfully shown window: {}", label);
     } else {
-        log_to_file(String::from("INFO"), format!("Window {} not found, creating new window", label));
+        info!("Window {} not found, creating new window", label);
         create_support_window(&app);
         if let Some(window) = app.get_webview_window(&label) {
             window.show().map_err(|e| {
-                let err_msg = format!("Failed to show newly created window {}: {}", label, e);
-                log_to_file(String::from("ERROR"), err_msg.clone());
-                err_msg
+                let err = AgentError::Io(format!("Failed to show newly created window {}: {}", label, e));
+                error!("{}", err);
+                err
             })?;
             window.set_focus().map_err(|e| {
-                let err_msg = format!("Failed to focus newly created window {}: {}", label, e);
-                log_to_file(String::from("ERROR"), err_msg.clone());
-                err_msg
+                let err = AgentError::Io(format!("Failed to focus newly created window {}: {}", label, e));
+                error!("{}", err);
+                err
             })?;
-            log_to_file(String::from("INFO"), format!("Successfully created and shown window: {}", label));
+            info!("Successfully created and shown window: {}", label);
         } else {
-            let err_msg = format!("Failed to get window {} after creation", label);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            return Err(err_msg);
+            let err = AgentError::Io(format!("Failed to get window {} after creation", label));
+            error!("{}", err);
+            return Err(err);
         }
     }
     Ok(())
 }
 
 #[tauri::command]
-async fn take_screenshot(app: tauri::AppHandle) -> Result<String, String> {
-    log_to_file(String::from("INFO"), String::from("take_screenshot command invoked"));
-    let path = take_screenshot_internal(app).await?;
-    let path_str = path.to_string_lossy().to_string();
-    log_to_file(String::from("INFO"), format!("Returning screenshot path: {}", path_str));
-    Ok(path_str)
+async fn take_screenshot(
+    app: tauri::AppHandle,
+    options: Option<ScreenshotOptions>,
+) -> Result<ScreenshotResult, AgentError> {
+    info!("take_screenshot command invoked");
+    take_screenshot_internal(app, options.unwrap_or_default()).await
+}
+
+/// Blurs, blacks out and annotates a screenshot. Returns the path of the edited copy,
+/// the only version of a capture `submit_ticket` will attach.
+#[tauri::command]
+async fn edit_screenshot(
+    access: tauri::State<'_, FileAccess>,
+    path: Option<String>,
+    token: Option<String>,
+    edits: Vec<ScreenshotEdit>,
+) -> Result<String, AgentError> {
+    info!("edit_screenshot command invoked with {} edit(s)", edits.len());
+    let source = check_file_access(&access, path.as_deref(), token.as_deref())?;
+    let edited = tauri::async_runtime::spawn_blocking(move || {
+        screenshot_edit::apply_edits(&source, &edits)
+    })
+    .await?
+    .map_err(|e| {
+        error!("Failed to edit screenshot: {}", e);
+        e
+    })?;
+    access.mark_redacted(&edited)?;
+    Ok(edited.to_string_lossy().to_string())
+}
+
+#[tauri::command]
+async fn get_monitors() -> Result<Vec<MonitorInfo>, AgentError> {
+    tauri::async_runtime::spawn_blocking(screenshot::list_monitors).await?
 }
 
 #[tauri::command]
-async fn get_settings_info() -> Result<device_manager::Settings, String> {
-    log_to_file(String::from("INFO"), String::from("get_settings_info command invoked"));
+async fn get_settings_info() -> Result<device_manager::Settings, AgentError> {
+    info!("get_settings_info command invoked");
     get_settings().await.map_err(|e| {
-        let err_msg = format!("Failed to get settings: {}", e);
-        log_to_file(String::from("ERROR"), err_msg.clone());
-        err_msg
+        error!("Failed to get settings: {}", e);
+        e
     })
 }
 
 #[tauri::command]
-async fn check_registration_status() -> Result<bool, String> {
-    log_to_file(String::from("INFO"), String::from("check_registration_status command invoked"));
+async fn check_registration_status() -> Result<bool, AgentError> {
+    info!("check_registration_status command invoked");
     let is_registered = is_device_registered().await;
-    log_to_file(String::from("INFO"), format!("Device registration status: {}", is_registered));
+    info!("Device registration status: {}", is_registered);
     Ok(is_registered)
 }
 
 #[tauri::command]
-fn read_file_text(path: String) -> Result<String, String> {
-    log_to_file(String::from("INFO"), format!("read_file_text command invoked for: {}", path));
-    std::fs::read_to_string(&path).map_err(|e| {
-        let err_msg = format!("Failed to read file {}: {}", path, e);
-        log_to_file(String::from("ERROR"), err_msg.clone());
-        err_msg
+fn get_registration_state(status: tauri::State<'_, RegistrationStatus>) -> RegistrationState {
+    status.current()
+}
+
+#[tauri::command]
+fn get_heartbeat_status(heartbeat: tauri::State<'_, HeartbeatHandle>) -> HeartbeatStatus {
+    heartbeat.status()
+}
+
+#[tauri::command]
+fn read_file_text(
+    access: tauri::State<'_, FileAccess>,
+    path: Option<String>,
+    token: Option<String>,
+) -> Result<String, AgentError> {
+    let allowed = check_file_access(&access, path.as_deref(), token.as_deref())?;
+    info!("read_file_text command invoked for: {}", allowed.display());
+    std::fs::read_to_string(&allowed).map_err(|e| {
+        let err = AgentError::Io(format!("Failed to read file {}: {}", allowed.display(), e));
+        error!("{}", err);
+        err
     })
 }
 
 #[tauri::command]
-fn read_file_base64(path: String) -> Result<String, String> {
-    log_to_file(String::from("INFO"), format!("read_file_base64 command invoked for: {}", path));
-    std::fs::read(&path)
+fn read_file_base64(
+    access: tauri::State<'_, FileAccess>,
+    path: Option<String>,
+    token: Option<String>,
+) -> Result<String, AgentError> {
+    let allowed = check_file_access(&access, path.as_deref(), token.as_deref())?;
+    info!(
+        "read_file_base64 command invoked for: {}",
+        allowed.display()
+    );
+    std::fs::read(&allowed)
         .map_err(|e| {
-            let err_msg = format!("Failed to read file {}: {}", path, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            let err = AgentError::Io(format!("Failed to read file {}: {}", allowed.display(), e));
+            error!("{}", err);
+            err
         })
         .map(|bytes| {
-            log_to_file(String::from("INFO"), format!("Successfully encoded {} bytes to base64", bytes.len()));
+            info!("Successfully encoded {} bytes to base64", bytes.len());
             general_purpose::STANDARD.encode(bytes)
         })
 }
 
 #[tauri::command]
-fn read_file_binary(path: String) -> Result<Vec<u8>, String> {
-    log_to_file(String::from("INFO"), format!("read_file_binary command invoked for: {}", path));
-    std::fs::read(&path)
+fn read_file_binary(
+    access: tauri::State<'_, FileAccess>,
+    path: Option<String>,
+    token: Option<String>,
+) -> Result<Vec<u8>, AgentError> {
+    let allowed = check_file_access(&access, path.as_deref(), token.as_deref())?;
+    info!(
+        "read_file_binary command invoked for: {}",
+        allowed.display()
+    );
+    std::fs::read(&allowed)
         .map_err(|e| {
-            let err_msg = format!("Failed to read file {}: {}", path, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
+            let err = AgentError::Io(format!("Failed to read file {}: {}", allowed.display(), e));
+            error!("{}", err);
+            err
         })
         .map(|bytes| {
-            log_to_file(String::from("INFO"), format!("Successfully read {} bytes as binary", bytes.len()));
+            info!("Successfully read {} bytes as binary", bytes.len());
             bytes
         })
 }
 
+/// Resolves a file the webview asked for: a picked file by its token, anything else by path
+fn check_file_access(
+    access: &FileAccess,
+    path: Option<&str>,
+    token: Option<&str>,
+) -> Result<PathBuf, AgentError> {
+    let result = match (token, path) {
+        (Some(token), _) => access.check_token(token),
+        (None, Some(path)) => access.check(path),
+        (None, None) => Err(AgentError::AccessDenied("No file was given".to_string())),
+    };
+    result.map_err(|e| {
+        warn!("Blocked file access from the webview: {}", e);
+        e
+    })
+}
+
+/// Shows an image picker and grants the webview read access to the chosen file
 #[tauri::command]
-fn read_registry_value(_path: &str, _key: &str) -> Result<String, String> {
-    log_to_file(String::from("INFO"), format!("read_registry_value command invoked: path={}, key={}", _path, _key));
-
-    #[cfg(target_os = "windows")]
-    {
-        use winreg::enums::*;
-        use winreg::RegKey;
-
-        let hklm = RegKey::predef(HKEY_LOCAL_MACHINE);
-        let subkey = hklm.open_subkey(_path).map_err(|e| {
-            let err_msg = format!("Failed to open registry path {}: {}", _path, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
-        })?;
-        let result: String = subkey.get_value(_key).map_err(|e| {
-            let err_msg = format!("Failed to read registry key {}: {}", _key, e);
-            log_to_file(String::from("ERROR"), err_msg.clone());
-            err_msg
-        })?;
-        log_to_file(String::from("INFO"), format!("Successfully read registry value for {}/{}", _path, _key));
-        Ok(result)
-    }
+async fn pick_image_file(
+    app: AppHandle,
+    access: tauri::State<'_, FileAccess>,
+) -> Result<Option<PickedFile>, AgentError> {
+    use tauri_plugin_dialog::DialogExt;
+
+    info!("pick_image_file command invoked");
+    let picked = tauri::async_runtime::spawn_blocking(move || {
+        app.dialog()
+            .file()
+            .add_filter("Image", &["png", "jpeg", "jpg"])
+            .blocking_pick_file()
+    })
+    .await?;
 
-    #[cfg(not(target_os = "windows"))]
-    {
-        let err_msg = String::from("Registry only works on Windows");
-        log_to_file(String::from("ERROR"), err_msg.clone());
-        Err(err_msg)
-    }
+    let Some(picked) = picked else {
+        return Ok(None);
+    };
+    let path = picked
+        .into_path()
+        .map_err(|e| AgentError::Io(format!("Failed to resolve picked file: {}", e)))?;
+    Ok(Some(access.grant(&path)?))
 }
 
 #[tauri::command]
-async fn get_os_info() -> Result<HeartbeatRequest, String> {
-    match gather_system_info().await {
-        Ok(info) => {
-            return Ok(info);
-        }
-        Err(e) => {
-            return Err(String::from("Failed to get system info"))
-        }
-    }
+fn release_file(access: tauri::State<'_, FileAccess>, token: String) {
+    access.release(&token);
+}
+
+/// Reads an allow-listed registry value. The hive defaults to HKLM.
+#[tauri::command]
+fn read_registry_value(
+    access: tauri::State<'_, RegistryAccess>,
+    hive: Option<Hive>,
+    path: String,
+    key: String,
+) -> Result<RegistryValue, AgentError> {
+    let hive = hive.unwrap_or(Hive::Hklm);
+    info!(
+        "read_registry_value command invoked: hive={}, path={}, key={}",
+        hive.name(),
+        path,
+        key
+    );
+
+    access.read(hive, &path, &key).map_err(|e| {
+        error!("{}", e);
+        e
+    })
+}
+
+/// Looks up a configuration setting from the registry, os-release, systemd, GSettings,
+/// dconf, sysctl or plist defaults, whichever the query names
+#[tauri::command]
+async fn query_system_config(
+    registry: tauri::State<'_, RegistryAccess>,
+    access: tauri::State<'_, ConfigAccess>,
+    query: ConfigQuery,
+) -> Result<ConfigValue, AgentError> {
+    info!("query_system_config command invoked: {:?}", query);
+    let registry = registry.inner().clone();
+    let access = access.inner().clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        system_config::query(&registry, &access, &query)
+    })
+    .await?
+    .map_err(|e| {
+        error!("System config query failed: {}", e);
+        e
+    })
 }
 
 #[tauri::command]
-async fn get_rmm_id() -> Result<String, String> {
-    match get_rmm_device_id() {
-        Some(id) => Ok(id),
-        None => Err("Failed to get key".into()),
+async fn get_os_info() -> Result<HeartbeatRequest, AgentError> {
+    gather_system_info().await.map_err(|e| {
+        error!("Failed to get system info: {}", e);
+        e
+    })
+}
+
+#[tauri::command]
+async fn get_rmm_id() -> Result<Vec<RmmAgent>, AgentError> {
+    Ok(tauri::async_runtime::spawn_blocking(detect_rmm_agents).await?)
+}
+
+#[tauri::command]
+async fn upload_logs(reason: Option<String>) -> Result<String, AgentError> {
+    let reason = reason.unwrap_or_else(|| String::from("manual"));
+    info!("upload_logs command invoked (reason: {})", reason);
+    log_bundle::upload_log_bundle(&reason).await.map_err(|e| {
+        error!("Failed to upload log bundle: {}", e);
+        e
+    })
+}
+
+/// Saves a support ticket to the outbox and tries to send it. A ticket that could not be
+/// sent comes back queued and is retried in the background.
+#[tauri::command]
+async fn submit_ticket(
+    app: AppHandle,
+    outbox: tauri::State<'_, TicketOutbox>,
+    access: tauri::State<'_, FileAccess>,
+    mut ticket: TicketRequest,
+) -> Result<QueuedTicket, AgentError> {
+    info!("submit_ticket command invoked");
+    // A capture has to go through the editor first, so nothing leaves unredacted
+    let attachment = match (ticket.screenshot_token.take(), &ticket.screenshot_path) {
+        (Some(token), _) => Some(access.check_token(&token)),
+        (None, Some(path)) => Some(access.check_attachment(path)),
+        (None, None) => None,
+    };
+    if let Some(attachment) = attachment {
+        let allowed = attachment.map_err(|e| {
+            warn!("Screenshot attachment denied: {}", e);
+            e
+        })?;
+        ticket.screenshot_path = Some(allowed.to_string_lossy().to_string());
     }
+    outbox.submit(&app, ticket).await.map_err(|e| {
+        error!("Failed to queue support ticket: {}", e);
+        e
+    })
+}
+
+#[tauri::command]
+async fn get_queued_tickets() -> Result<Vec<QueuedTicket>, AgentError> {
+    list_queued_tickets().await
+}
+
+#[tauri::command]
+async fn dismiss_queued_ticket(id: String) -> Result<(), AgentError> {
+    dismiss_ticket(&id).await
 }
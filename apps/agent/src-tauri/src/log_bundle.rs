@@ -0,0 +1,216 @@
+use crate::device_manager::{get_api_endpoint, get_settings};
+use crate::error::AgentError;
+use crate::heartbeat::gather_system_info;
+use crate::logger::{list_log_files, redact};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::path::Path;
+
+// Rotation caps each family at ~60MB, stop well before that much is uploaded
+const MAX_BUNDLE_LOG_BYTES: u64 = 50 * 1024 * 1024;
+
+// Fields that identify or authenticate the device and never leave it
+const SECRET_SETTINGS: [&str; 3] = ["site_id", "guid", "script_signing_key"];
+const SECRET_MARKERS: [&str; 4] = ["secret", "token", "password", "key"];
+
+/// Written as `manifest.json` at the root of the bundle
+#[derive(Serialize, Debug)]
+pub struct BundleManifest {
+    pub version: String,
+    pub platform: String,
+    pub created_at: String,
+    pub reason: String,
+    pub settings: Option<serde_json::Value>,
+    pub system_info: Option<serde_json::Value>,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct BundleFile {
+    pub name: String,
+    pub size: u64,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogBundleResponse {
+    pub data: LogBundleData,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogBundleData {
+    pub id: String,
+}
+
+/// `value` as JSON with identifiers and anything that looks like a credential removed
+fn redact_fields<T: Serialize>(value: &T) -> Option<serde_json::Value> {
+    let mut value = serde_json::to_value(value).ok()?;
+    if let Some(map) = value.as_object_mut() {
+        map.retain(|key, _| {
+            let key = key.to_lowercase();
+            !SECRET_SETTINGS.contains(&key.as_str())
+                && !SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+        });
+    }
+    Some(value)
+}
+
+/// Reads a log file as text, decompressing rotated `.gz` files, and redacts it again so
+/// lines written before redaction existed (or by older versions) are masked too
+fn read_log_file(path: &Path) -> std::io::Result<(String, Vec<u8>)> {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let mut raw = Vec::new();
+    if let Some(plain_name) = name.strip_suffix(".gz") {
+        GzDecoder::new(std::fs::File::open(path)?).read_to_end(&mut raw)?;
+        let text = redact(&String::from_utf8_lossy(&raw));
+        Ok((plain_name.to_string(), text.into_bytes()))
+    } else {
+        std::fs::File::open(path)?.read_to_end(&mut raw)?;
+        let text = redact(&String::from_utf8_lossy(&raw));
+        Ok((name, text.into_bytes()))
+    }
+}
+
+/// Packs the manifest and every log file into a gzipped tarball. Newest files are
+/// added first so the size cap only ever drops the oldest rotated logs.
+fn build_bundle(mut manifest: BundleManifest) -> Result<Vec<u8>, AgentError> {
+    let mut archive = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::default()));
+    let mtime = chrono::Utc::now().timestamp().max(0) as u64;
+    let mut total: u64 = 0;
+
+    for path in list_log_files() {
+        // Read up front; the current log keeps growing while the bundle is built
+        let (name, content) = match read_log_file(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Skipping {} in log bundle: {}", path.display(), e);
+                continue;
+            }
+        };
+
+        if total + content.len() as u64 > MAX_BUNDLE_LOG_BYTES {
+            warn!("Log bundle size limit reached, skipping {} and older files", name);
+            break;
+        }
+        total += content.len() as u64;
+
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(mtime);
+        archive.append_data(&mut header, format!("logs/{}", name), content.as_slice())?;
+
+        manifest.files.push(BundleFile {
+            name,
+            size: content.len() as u64,
+        });
+    }
+
+    let manifest_json = serde_json::to_vec_pretty(&manifest)?;
+    let mut header = tar::Header::new_gnu();
+    header.set_size(manifest_json.len() as u64);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    archive.append_data(&mut header, "manifest.json", manifest_json.as_slice())?;
+
+    Ok(archive.into_inner()?.finish()?)
+}
+
+/// Collects the agent logs into one bundle and uploads it. Returns the server's bundle ID,
+/// which tickets and job results reference.
+pub async fn upload_log_bundle(reason: &str) -> Result<String, AgentError> {
+    let settings = get_settings().await?;
+    let device_id = settings
+        .device_id
+        .clone()
+        .ok_or(AgentError::NotRegistered)?;
+
+    let system_info = match gather_system_info().await {
+        Ok(info) => redact_fields(&info),
+        Err(e) => {
+            warn!("Log bundle will not include system info: {}", e);
+            None
+        }
+    };
+
+    let manifest = BundleManifest {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        platform: std::env::consts::OS.to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        reason: reason.to_string(),
+        settings: redact_fields(&settings),
+        system_info,
+        files: Vec::new(),
+    };
+
+    let bundle = tokio::task::spawn_blocking(move || build_bundle(manifest)).await??;
+    let file_name = format!("logs_{}.tar.gz", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+    info!(
+        event = "log_bundle";
+        "Uploading log bundle {} ({} bytes, reason: {})",
+        file_name,
+        bundle.len(),
+        reason
+    );
+
+    let part = reqwest::multipart::Part::bytes(bundle)
+        .file_name(file_name)
+        .mime_str("application/gzip")?;
+    let form = reqwest::multipart::Form::new()
+        .text("reason", reason.to_string())
+        .text("version", env!("CARGO_PKG_VERSION"))
+        .part("bundle", part);
+
+    let api_url = get_api_endpoint("/v1.0/logs").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("x-device-id", &device_id)
+        .header("x-site-id", &settings.site_id)
+        .multipart(form)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        let result: LogBundleResponse = response.json().await?;
+        Ok(result.data.id)
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::heartbeat::HeartbeatRequest;
+    use crate::network::NetworkInfo;
+
+    #[test]
+    fn system_info_is_redacted_like_settings() {
+        let info = HeartbeatRequest {
+            hostname: "FRONTDESK-01".to_string(),
+            ip_address: None,
+            ext_address: None,
+            version: "1.0.0".to_string(),
+            mac_address: None,
+            guid: Some("8d2e41b0-7a9c-4f15-b3e6-0c5d9a7e21f4".to_string()),
+            username: None,
+            network: NetworkInfo::default(),
+            fingerprint: None,
+            dropped_log_messages: 0,
+        };
+
+        let redacted = redact_fields(&info).unwrap();
+        assert!(redacted.get("guid").is_none());
+        assert_eq!(redacted["hostname"], "FRONTDESK-01");
+        assert!(!redacted.to_string().contains("8d2e41b0"));
+    }
+}
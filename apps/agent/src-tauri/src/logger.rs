@@ -1,20 +1,75 @@
 use chrono::Local;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use log::kv::Key;
+use log::{LevelFilter, Log, Metadata, Record};
+use regex::Regex;
+use std::collections::{HashMap, VecDeque};
 use std::fs::{self, OpenOptions};
 use std::io::Write;
-use std::path::PathBuf;
-use std::sync::Mutex;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, Ordering};
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::device_manager::get_config_dir;
+use crate::error::AgentError;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 const MAX_LOG_SIZE_BYTES: u64 = 10 * 1024 * 1024; // 10MB
 const MAX_ROTATED_FILES: usize = 5;
+const DEFAULT_MAX_TOTAL_LOG_MB: u64 = 100;
+const DEFAULT_MAX_LOG_AGE_DAYS: u64 = 30;
+
+// Module paths are logged relative to the crate
+const CRATE_PREFIX: &str = "agent_lib::";
+
+// Target used for messages forwarded from the webview through `log_to_file`
+const WEBVIEW_TARGET: &str = "webview";
 
 // Global mutex to ensure thread-safe log rotation
 static LOG_MUTEX: Mutex<()> = Mutex::new(());
 
-#[derive(Debug, Clone)]
+// Set while a rotated log is being compressed in the background. Only set under LOG_MUTEX.
+static COMPRESSING: AtomicBool = AtomicBool::new(false);
+
+// Lines that could not be written to disk, replayed once the file is writable again
+static MEMORY_BUFFER: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+const MEMORY_BUFFER_CAPACITY: usize = 500;
+
+// Lines pushed out of the memory buffer before they reached the log file, so lost for
+// good. Reported with the next heartbeat.
+static DROPPED_MESSAGES: AtomicU64 = AtomicU64::new(0);
+
+// Minimum level written to disk, adjustable through server config
+static MIN_LEVEL: AtomicU8 = AtomicU8::new(2);
+
+// Per-module overrides of MIN_LEVEL, keyed by module path without the crate prefix
+static TARGET_LEVELS: RwLock<Option<HashMap<String, LogLevel>>> = RwLock::new(None);
+
+// Write JSON lines instead of plain text
+static JSON_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+// Retention policy from settings, defaults apply until settings are loaded
+static RETENTION: RwLock<Option<LogRetention>> = RwLock::new(None);
+
+// Site-specific secrets and patterns masked in every message
+static REDACTION: RwLock<Option<Redaction>> = RwLock::new(None);
+
+// Included in JSON entries so lines can be correlated server-side
+static DEVICE_ID: RwLock<Option<String>> = RwLock::new(None);
+
+static LOGGER: AgentLogger = AgentLogger;
+
+struct Redaction {
+    secrets: Vec<(String, &'static str)>,
+    patterns: Vec<Regex>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LogLevel {
+    Trace,
+    Debug,
     Info,
     Warn,
     Error,
@@ -23,16 +78,30 @@ pub enum LogLevel {
 impl LogLevel {
     fn as_str(&self) -> &str {
         match self {
+            LogLevel::Trace => "TRACE",
+            LogLevel::Debug => "DEBUG",
             LogLevel::Info => "INFO",
             LogLevel::Warn => "WARN",
             LogLevel::Error => "ERROR",
         }
     }
+
+    fn rank(&self) -> u8 {
+        match self {
+            LogLevel::Trace => 0,
+            LogLevel::Debug => 1,
+            LogLevel::Info => 2,
+            LogLevel::Warn => 3,
+            LogLevel::Error => 4,
+        }
+    }
 }
 
 impl From<String> for LogLevel {
     fn from(s: String) -> Self {
         match s.to_uppercase().as_str() {
+            "TRACE" => LogLevel::Trace,
+            "DEBUG" => LogLevel::Debug,
             "WARN" => LogLevel::Warn,
             "ERROR" => LogLevel::Error,
             _ => LogLevel::Info,
@@ -40,6 +109,139 @@ impl From<String> for LogLevel {
     }
 }
 
+impl From<log::Level> for LogLevel {
+    fn from(level: log::Level) -> Self {
+        match level {
+            log::Level::Trace => LogLevel::Trace,
+            log::Level::Debug => LogLevel::Debug,
+            log::Level::Info => LogLevel::Info,
+            log::Level::Warn => LogLevel::Warn,
+            log::Level::Error => LogLevel::Error,
+        }
+    }
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => log::Level::Trace,
+            LogLevel::Debug => log::Level::Debug,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Error => log::Level::Error,
+        }
+    }
+}
+
+/// `log` facade backend writing to the rotating file in the config dir
+struct AgentLogger;
+
+impl Log for AgentLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let level = LogLevel::from(metadata.level());
+        level.rank() >= min_level_for(module_name(metadata.target()))
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let event = record
+            .key_values()
+            .get(Key::from_str("event"))
+            .map(|value| value.to_string());
+        let message = record.args().to_string();
+
+        let entry = LogEntry {
+            level: LogLevel::from(record.level()),
+            module: module_name(record.target()),
+            event: event.as_deref(),
+            message: &message,
+        };
+
+        write_entry(&entry);
+    }
+
+    fn flush(&self) {}
+}
+
+struct LogEntry<'a> {
+    level: LogLevel,
+    module: &'a str,
+    event: Option<&'a str>,
+    message: &'a str,
+}
+
+impl LogEntry<'_> {
+    fn format(&self) -> String {
+        let message = redact(self.message);
+
+        if JSON_OUTPUT.load(Ordering::Relaxed) {
+            let device_id = match DEVICE_ID.read() {
+                Ok(device_id) => device_id.clone(),
+                Err(poisoned) => poisoned.into_inner().clone(),
+            };
+            let line = serde_json::json!({
+                "ts": chrono::Utc::now().to_rfc3339(),
+                "level": self.level.as_str(),
+                "module": self.module,
+                "device_id": device_id,
+                "event": self.event,
+                "message": message,
+                "version": VERSION,
+            });
+            format!("{}\n", line)
+        } else {
+            // [timestamp][LEVEL][module] message
+            let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
+            format!(
+                "[{}][{}][{}] {}\n",
+                timestamp,
+                self.level.as_str(),
+                self.module,
+                message
+            )
+        }
+    }
+}
+
+/// Installs the file logger as the `log` backend. Safe to call more than once.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        // Filtering happens in `enabled` so levels can change at runtime
+        log::set_max_level(LevelFilter::Trace);
+    }
+}
+
+fn module_name(target: &str) -> &str {
+    target.strip_prefix(CRATE_PREFIX).unwrap_or(target)
+}
+
+/// Most specific per-module override for `module`, falling back to the global minimum
+fn min_level_for(module: &str) -> u8 {
+    let targets = match TARGET_LEVELS.read() {
+        Ok(targets) => targets,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    targets
+        .as_ref()
+        .and_then(|targets| {
+            targets
+                .iter()
+                .filter(|(name, _)| {
+                    module == name.as_str()
+                        || module
+                            .strip_prefix(name.as_str())
+                            .is_some_and(|rest| rest.starts_with("::"))
+                })
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, level)| level.rank())
+        })
+        .unwrap_or_else(|| MIN_LEVEL.load(Ordering::Relaxed))
+}
+
 fn get_logs_dir() -> PathBuf {
     let config_dir = get_config_dir();
     config_dir.join("logs")
@@ -53,48 +255,323 @@ fn get_log_path() -> PathBuf {
     get_logs_dir().join(get_log_filename())
 }
 
-// Rotate log file if it exceeds size limit
-fn check_and_rotate_log() -> Result<(), Box<dyn std::error::Error>> {
-    let log_path = get_log_path();
+/// Every current and rotated log file in the logs directory, across versions. Newest first.
+pub fn list_log_files() -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(get_logs_dir()) else {
+        return Vec::new();
+    };
 
-    if let Ok(metadata) = fs::metadata(&log_path) {
-        if metadata.len() > MAX_LOG_SIZE_BYTES {
-            // Rotate existing logs (shift .4 -> .5, .3 -> .4, etc.)
-            for i in (1..MAX_ROTATED_FILES).rev() {
-                let old_file = get_logs_dir().join(format!("{}.{}", get_log_filename(), i));
-                let new_file = get_logs_dir().join(format!("{}.{}", get_log_filename(), i + 1));
+    let mut files: Vec<(SystemTime, PathBuf)> = entries
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("runtime_"))
+        .filter_map(|entry| {
+            let metadata = entry.metadata().ok()?;
+            metadata
+                .is_file()
+                .then(|| (metadata.modified().unwrap_or(UNIX_EPOCH), entry.path()))
+        })
+        .collect();
 
-                if old_file.exists() {
-                    let _ = fs::rename(old_file, new_file);
-                }
-            }
+    files.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    files.into_iter().map(|(_, path)| path).collect()
+}
+
+fn rotated_path(log_path: &Path, index: usize) -> PathBuf {
+    PathBuf::from(format!("{}.{}.gz", log_path.display(), index))
+}
+
+/// Where the live log is moved on rotation until its compressed copy is complete. A file
+/// left here by a failed compression is picked up by `enforce_retention`.
+fn pending_rotation_path(log_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.1", log_path.display()))
+}
 
-            // Move current log to .1
-            let rotated = get_logs_dir().join(format!("{}.1", get_log_filename()));
-            fs::rename(&log_path, rotated)?;
+/// Gzips `source` into `target` and removes the source once the copy is complete. The copy
+/// keeps the source's modification time so retention still sees how old the log is.
+fn compress_file(source: &Path, target: &Path) -> Result<(), AgentError> {
+    let mut input = fs::File::open(source)?;
+    let modified = input.metadata()?.modified()?;
+    let mut encoder = GzEncoder::new(fs::File::create(target)?, Compression::default());
+    std::io::copy(&mut input, &mut encoder)?;
+    let output = encoder.finish()?;
+    output.set_modified(modified)?;
+    output.sync_all()?;
+    drop(input);
+    fs::remove_file(source)?;
+    Ok(())
+}
+
+// Rotate log file if it exceeds size limit. The file is only renamed here, under LOG_MUTEX;
+// compressing it happens on a separate thread so logging isn't held up.
+fn check_and_rotate_log(log_path: &Path) -> Result<(), AgentError> {
+    let Ok(metadata) = fs::metadata(log_path) else {
+        return Ok(());
+    };
+    if metadata.len() <= MAX_LOG_SIZE_BYTES {
+        return Ok(());
+    }
+
+    // The previous rotation is still being compressed, or its compression failed and
+    // retention hasn't recovered it yet. Keep writing to the current file until then.
+    let pending = pending_rotation_path(log_path);
+    if COMPRESSING.load(Ordering::Acquire) || pending.exists() {
+        return Ok(());
+    }
+
+    // Rotate existing logs (shift .4.gz -> .5.gz, .3.gz -> .4.gz, etc.)
+    for i in (1..MAX_ROTATED_FILES).rev() {
+        let old_file = rotated_path(log_path, i);
+        if old_file.exists() {
+            let _ = fs::rename(old_file, rotated_path(log_path, i + 1));
         }
     }
 
+    fs::rename(log_path, &pending)?;
+
+    // Compress it to .1.gz in the background
+    COMPRESSING.store(true, Ordering::Release);
+    let target = rotated_path(log_path, 1);
+    let spawned = std::thread::Builder::new()
+        .name("log-compress".to_string())
+        .spawn(move || {
+            let result = compress_file(&pending, &target);
+            COMPRESSING.store(false, Ordering::Release);
+            if let Err(e) = result {
+                log::warn!("Failed to compress the rotated log: {}", e);
+            }
+        });
+    if let Err(e) = spawned {
+        COMPRESSING.store(false, Ordering::Release);
+        return Err(e.into());
+    }
+
     Ok(())
 }
 
-pub fn log_message(level: LogLevel, message: &str) -> Result<(), Box<dyn std::error::Error>> {
-    // Acquire mutex to ensure thread-safe rotation and writing
-    let _lock = LOG_MUTEX.lock().unwrap();
+/// Retention applied to the whole logs directory, including files from older versions
+#[derive(Debug, Clone, Copy)]
+pub struct LogRetention {
+    pub max_total_bytes: u64,
+    pub max_age: Duration,
+}
+
+impl Default for LogRetention {
+    fn default() -> Self {
+        LogRetention {
+            max_total_bytes: DEFAULT_MAX_TOTAL_LOG_MB * 1024 * 1024,
+            max_age: Duration::from_secs(DEFAULT_MAX_LOG_AGE_DAYS * 86400),
+        }
+    }
+}
+
+/// Sets the retention policy used by `enforce_retention`
+pub fn set_retention(retention: LogRetention) {
+    let mut current = match RETENTION.write() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *current = Some(retention);
+}
+
+/// Compresses leftover plain-text rotations, then deletes files past the maximum age and the
+/// oldest files beyond the total size budget. The active log file is never touched.
+pub fn enforce_retention() {
+    let retention = match RETENTION.read() {
+        Ok(current) => current.unwrap_or_default(),
+        Err(poisoned) => poisoned.into_inner().unwrap_or_default(),
+    };
+
+    // Hold the log mutex so a rotation can't run while files are being moved
+    let lock = match LOG_MUTEX.lock() {
+        Ok(lock) => lock,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    // A rotated log is being compressed right now, try again next time
+    if COMPRESSING.load(Ordering::Acquire) {
+        return;
+    }
+
+    let active = get_log_path();
+    let now = SystemTime::now();
+    let mut total: u64 = 0;
+    let mut removed = 0;
+
+    for path in list_log_files() {
+        if path == active {
+            total += fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+            continue;
+        }
+
+        // Age comes from the file as found, before compressing it could touch the timestamp
+        let Ok(original) = fs::metadata(&path) else {
+            continue;
+        };
+        let age = original
+            .modified()
+            .ok()
+            .and_then(|modified| now.duration_since(modified).ok())
+            .unwrap_or_default();
+
+        // Plain rotations from before compression was added, and older versions' logs
+        let path = if path.extension().is_some_and(|ext| ext != "gz") && age <= retention.max_age {
+            let compressed = PathBuf::from(format!("{}.gz", path.display()));
+            match compress_file(&path, &compressed) {
+                Ok(()) => compressed,
+                Err(_) => path,
+            }
+        } else {
+            path
+        };
+
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+
+        if age > retention.max_age || total + metadata.len() > retention.max_total_bytes {
+            if fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+            continue;
+        }
+        total += metadata.len();
+    }
 
-    let log_path = get_log_path();
+    // Logging takes the mutex again
+    drop(lock);
+    if removed > 0 {
+        log::info!("Log retention removed {} file(s), {} bytes kept", removed, total);
+    }
+}
+
+/// Sets the minimum level that gets written to the log file
+pub fn set_min_level(level: LogLevel) {
+    MIN_LEVEL.store(level.rank(), Ordering::Relaxed);
+}
+
+/// Replaces the per-module level overrides, e.g. `{"heartbeat": "DEBUG"}`
+pub fn set_target_levels(levels: HashMap<String, LogLevel>) {
+    let mut targets = match TARGET_LEVELS.write() {
+        Ok(targets) => targets,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *targets = if levels.is_empty() { None } else { Some(levels) };
+}
+
+/// Switches between plain text and JSON-lines output
+pub fn set_json_output(enabled: bool) {
+    JSON_OUTPUT.store(enabled, Ordering::Relaxed);
+}
+
+/// Replaces the site-specific redaction rules: literal secret values (masked with their label)
+/// and extra regexes from settings. Invalid patterns are skipped and returned.
+pub fn set_redaction(secrets: Vec<(String, &'static str)>, patterns: &[String]) -> Vec<String> {
+    let mut invalid = Vec::new();
+    let patterns = patterns
+        .iter()
+        .filter_map(|pattern| match Regex::new(pattern) {
+            Ok(regex) => Some(regex),
+            Err(_) => {
+                invalid.push(pattern.clone());
+                None
+            }
+        })
+        .collect();
+
+    let mut current = match REDACTION.write() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *current = Some(Redaction {
+        // Very short values would mask unrelated text
+        secrets: secrets
+            .into_iter()
+            .filter(|(value, _)| value.len() >= 6)
+            .collect(),
+        patterns,
+    });
+
+    invalid
+}
+
+/// Built-in patterns for personal data and credentials, compiled once
+fn builtin_patterns() -> &'static [(Regex, &'static str)] {
+    static PATTERNS: OnceLock<Vec<(Regex, &'static str)>> = OnceLock::new();
+    PATTERNS.get_or_init(|| {
+        [
+            // Bearer tokens in Authorization headers. Real tokens are long, which keeps
+            // prose like "the bearer of" intact.
+            (r"(?i)\bbearer\s+[A-Za-z0-9\-._~+/]{16,}=*", "Bearer [redacted]"),
+            // key=value / "key": "value" pairs whose key names a credential, also as the end
+            // of a longer key such as `site_secret` or `x-api-key`
+            (
+                r#"(?i)((?:^|[^A-Za-z0-9])(?:api[_-]?key|access[_-]?token|refresh[_-]?token|token|secret|password|passwd|authorization)["']?\s*[:=]\s*["']?)[^\s"',;&]+"#,
+                "${1}[redacted]",
+            ),
+            (r"[A-Za-z0-9._%+\-]+@[A-Za-z0-9.\-]+\.[A-Za-z]{2,}", "[email]"),
+            // North American numbers. A bare run of ten digits is more likely an ID or a
+            // timestamp, so a number needs a leading `+` or `(`, or separators between groups.
+            (
+                r"(?:\+1[\s.\-]?(?:\(\d{3}\)|\d{3})[\s.\-]?\d{3}[\s.\-]?\d{4}|\(\d{3}\)[\s.\-]?\d{3}[\s.\-]?\d{4}|\b(?:1[\s.\-])?\d{3}[\s.\-]\d{3}[\s.\-]\d{4})\b",
+                "[phone]",
+            ),
+        ]
+        .into_iter()
+        .map(|(pattern, replacement)| {
+            (
+                Regex::new(pattern).expect("built-in redaction pattern is valid"),
+                replacement,
+            )
+        })
+        .collect()
+    })
+}
+
+/// Masks personal data and secrets in a message before it is written or uploaded
+pub fn redact(message: &str) -> String {
+    let mut redacted = message.to_string();
+
+    {
+        let current = match REDACTION.read() {
+            Ok(current) => current,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        if let Some(redaction) = current.as_ref() {
+            for (value, label) in &redaction.secrets {
+                if redacted.contains(value.as_str()) {
+                    redacted = redacted.replace(value.as_str(), &format!("[{}]", label));
+                }
+            }
+            for pattern in &redaction.patterns {
+                redacted = pattern.replace_all(&redacted, "[redacted]").into_owned();
+            }
+        }
+    }
+
+    for (pattern, replacement) in builtin_patterns() {
+        redacted = pattern.replace_all(&redacted, *replacement).into_owned();
+    }
 
+    redacted
+}
+
+/// Sets the device ID attached to JSON log entries
+pub fn set_device_id(device_id: Option<String>) {
+    let mut current = match DEVICE_ID.write() {
+        Ok(current) => current,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    *current = device_id;
+}
+
+fn append_to_file(log_path: &Path, lines: &str) -> Result<(), AgentError> {
     // Ensure logs directory exists
     if let Some(parent) = log_path.parent() {
         fs::create_dir_all(parent)?;
     }
 
-    // Check and rotate before writing
-    check_and_rotate_log()?;
-
-    // Format log entry: [timestamp][LEVEL] message
-    let timestamp = Local::now().format("%Y-%m-%d %H:%M:%S");
-    let log_entry = format!("[{}][{}] {}\n", timestamp, level.as_str(), message);
+    // Check and rotate before writing. A failed rotation only means the file keeps growing.
+    let _ = check_and_rotate_log(log_path);
 
     // Write to file
     let mut file = OpenOptions::new()
@@ -102,13 +579,294 @@ pub fn log_message(level: LogLevel, message: &str) -> Result<(), Box<dyn std::er
         .append(true)
         .open(log_path)?;
 
-    file.write_all(log_entry.as_bytes())?;
+    file.write_all(lines.as_bytes())?;
 
     Ok(())
 }
 
+/// What became of a line handed to `write_or_buffer`
+#[derive(Debug)]
+enum LineOutcome {
+    /// Written, after everything that was buffered before it
+    Written,
+    /// The file could not be written, so the line is kept in memory. `evicted` is set when
+    /// the buffer was full and its oldest line was discarded to make room.
+    Deferred { error: AgentError, evicted: bool },
+}
+
+/// Appends `line` to the file at `log_path`, replaying buffered lines ahead of it. If the
+/// write fails the line joins the buffer instead, which holds at most
+/// `MEMORY_BUFFER_CAPACITY` lines.
+fn write_or_buffer(log_path: &Path, buffer: &mut VecDeque<String>, line: String) -> LineOutcome {
+    let pending: String = buffer.iter().map(String::as_str).chain([line.as_str()]).collect();
+
+    match append_to_file(log_path, &pending) {
+        Ok(()) => {
+            buffer.clear();
+            LineOutcome::Written
+        }
+        Err(error) => {
+            let evicted = buffer.len() >= MEMORY_BUFFER_CAPACITY;
+            if evicted {
+                buffer.pop_front();
+            }
+            buffer.push_back(line);
+            LineOutcome::Deferred { error, evicted }
+        }
+    }
+}
+
+/// Writes an entry to the log file. If the file can't be written (read-only or full disk,
+/// missing permissions) the line goes to stderr and is kept in memory until the file is
+/// writable again. Never panics.
+fn write_entry(entry: &LogEntry) {
+    let line = entry.format();
+
+    // Acquire mutex to ensure thread-safe rotation and writing
+    let _lock = match LOG_MUTEX.lock() {
+        Ok(lock) => lock,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let mut buffer = match MEMORY_BUFFER.lock() {
+        Ok(buffer) => buffer,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+
+    let outcome = write_or_buffer(&get_log_path(), &mut buffer, line.clone());
+    if let LineOutcome::Deferred { error, evicted } = outcome {
+        if evicted {
+            DROPPED_MESSAGES.fetch_add(1, Ordering::Relaxed);
+        }
+
+        let mut stderr = std::io::stderr();
+        let _ = write!(stderr, "[log file unavailable: {}] {}", error, line);
+    }
+}
+
+/// Number of messages lost since the last acknowledgement because the log file stayed
+/// unwritable for longer than the memory buffer could cover
+pub fn dropped_message_count() -> u64 {
+    DROPPED_MESSAGES.load(Ordering::Relaxed)
+}
+
+/// Subtracts messages already reported to the server from the dropped count
+pub fn acknowledge_dropped_messages(count: u64) {
+    let _ = DROPPED_MESSAGES.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+        Some(current.saturating_sub(count))
+    });
+}
+
+pub fn log_message(level: LogLevel, message: &str) {
+    if level.rank() < min_level_for(WEBVIEW_TARGET) {
+        return;
+    }
+
+    write_entry(&LogEntry {
+        level,
+        module: WEBVIEW_TARGET,
+        event: None,
+        message,
+    });
+}
+
+/// Lets the webview write to the agent log. Rust code should use the `log` macros instead.
 #[tauri::command]
 pub fn log_to_file(level: String, message: String) {
     let log_level = LogLevel::from(level);
-    log_message(log_level, &message).expect("File could not be written to")
+    log_message(log_level, &message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("mspagent-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn buffers_lines_while_the_log_dir_is_unwritable() {
+        let dir = temp_dir("logger-unwritable");
+        // A file where the logs directory should be, so it can't be created even as root
+        let blocked = dir.join("logs");
+        fs::write(&blocked, "").unwrap();
+        let unwritable = blocked.join("runtime.log");
+
+        let mut buffer = VecDeque::new();
+        for n in 0..MEMORY_BUFFER_CAPACITY {
+            let outcome = write_or_buffer(&unwritable, &mut buffer, format!("line {}\n", n));
+            assert!(matches!(
+                outcome,
+                LineOutcome::Deferred { evicted: false, .. }
+            ));
+        }
+        assert_eq!(buffer.len(), MEMORY_BUFFER_CAPACITY);
+
+        // Only once the buffer is full is a line actually lost
+        let outcome = write_or_buffer(&unwritable, &mut buffer, "overflow\n".to_string());
+        assert!(matches!(
+            outcome,
+            LineOutcome::Deferred { evicted: true, .. }
+        ));
+        assert_eq!(buffer.len(), MEMORY_BUFFER_CAPACITY);
+        assert_eq!(buffer.front().map(String::as_str), Some("line 1\n"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replays_buffered_lines_once_the_log_dir_is_writable() {
+        let dir = temp_dir("logger-replay");
+        let blocked = dir.join("logs");
+        fs::write(&blocked, "").unwrap();
+
+        let mut buffer = VecDeque::new();
+        for n in 0..3 {
+            write_or_buffer(
+                &blocked.join("runtime.log"),
+                &mut buffer,
+                format!("line {}\n", n),
+            );
+        }
+
+        fs::remove_file(&blocked).unwrap();
+        let log_path = blocked.join("runtime.log");
+        let outcome = write_or_buffer(&log_path, &mut buffer, "line 3\n".to_string());
+        assert!(matches!(outcome, LineOutcome::Written));
+        assert!(buffer.is_empty());
+        assert_eq!(
+            fs::read_to_string(&log_path).unwrap(),
+            "line 0\nline 1\nline 2\nline 3\n"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compressed_logs_keep_their_age() {
+        let dir = temp_dir("logger-compress");
+        let source = dir.join("runtime_0.9.0.log");
+        let target = dir.join("runtime_0.9.0.log.gz");
+        fs::write(&source, "old line\n").unwrap();
+        let forty_days_ago = SystemTime::now() - Duration::from_secs(40 * 86400);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(forty_days_ago)
+            .unwrap();
+
+        compress_file(&source, &target).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(
+            fs::metadata(&target).unwrap().modified().unwrap(),
+            forty_days_ago
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn rotates_in_the_background_one_compression_at_a_time() {
+        let dir = temp_dir("logger-rotate");
+        let log_path = dir.join("runtime_0.9.0.log");
+        let pending = pending_rotation_path(&log_path);
+        fs::File::create(&log_path)
+            .unwrap()
+            .set_len(MAX_LOG_SIZE_BYTES + 1)
+            .unwrap();
+        fs::write(rotated_path(&log_path, 1), "older").unwrap();
+
+        // Nothing moves while a previous rotation is unfinished
+        COMPRESSING.store(true, Ordering::Release);
+        check_and_rotate_log(&log_path).unwrap();
+        COMPRESSING.store(false, Ordering::Release);
+        fs::write(&pending, "").unwrap();
+        check_and_rotate_log(&log_path).unwrap();
+        fs::remove_file(&pending).unwrap();
+        assert!(log_path.exists());
+        assert!(!rotated_path(&log_path, 2).exists());
+
+        check_and_rotate_log(&log_path).unwrap();
+        assert!(!log_path.exists());
+        assert_eq!(
+            fs::read_to_string(rotated_path(&log_path, 2)).unwrap(),
+            "older"
+        );
+
+        let deadline = SystemTime::now() + Duration::from_secs(30);
+        while COMPRESSING.load(Ordering::Acquire) && SystemTime::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!pending.exists());
+        let mut decoder =
+            flate2::read::GzDecoder::new(fs::File::open(rotated_path(&log_path, 1)).unwrap());
+        let size = std::io::copy(&mut decoder, &mut std::io::sink()).unwrap();
+        assert_eq!(size, MAX_LOG_SIZE_BYTES + 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn masks_bearer_tokens() {
+        assert_eq!(
+            redact("Authorization header: Bearer eyJhbGciOi.J9x-y_z=="),
+            "Authorization header: Bearer [redacted]"
+        );
+        assert_eq!(redact("the bearer of bad news"), "the bearer of bad news");
+    }
+
+    #[test]
+    fn masks_credential_values() {
+        assert_eq!(redact("password=hunter2"), "password=[redacted]");
+        assert_eq!(
+            redact("site_secret=abc123&x_api_key=def456"),
+            "site_secret=[redacted]&x_api_key=[redacted]"
+        );
+        assert_eq!(
+            redact(r#"{"access_token": "abc.def"}"#),
+            r#"{"access_token": "[redacted]"}"#
+        );
+        assert_eq!(
+            redact("tokenizer=bpe secrets rotated"),
+            "tokenizer=bpe secrets rotated"
+        );
+    }
+
+    #[test]
+    fn masks_email_addresses() {
+        assert_eq!(
+            redact("ticket from jane.doe+it@example.co.uk"),
+            "ticket from [email]"
+        );
+        assert_eq!(redact("mounted at user@host"), "mounted at user@host");
+    }
+
+    #[test]
+    fn masks_phone_numbers_with_separators_or_prefix() {
+        for number in [
+            "555-123-4567",
+            "555.123.4567",
+            "555 123 4567",
+            "(555) 123-4567",
+            "+1 555 123 4567",
+            "+15551234567",
+            "1-555-123-4567",
+        ] {
+            assert_eq!(
+                redact(&format!("call {} now", number)),
+                "call [phone] now",
+                "{}",
+                number
+            );
+        }
+        for text in ["order 5551234567", "took 1712345678901 ms", "v1.255.123"] {
+            assert_eq!(redact(text), text);
+        }
+    }
 }
@@ -0,0 +1,349 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NetworkInterface {
+    pub name: String,
+    pub mac: Option<String>,
+    pub ipv4: Vec<String>,
+    pub ipv6: Vec<String>,
+    pub is_up: bool,
+    pub is_virtual: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct NetworkInfo {
+    pub interfaces: Vec<NetworkInterface>,
+    pub default_interface: Option<String>,
+    pub default_gateway: Option<String>,
+}
+
+impl NetworkInfo {
+    /// Picks the primary MAC deterministically: the default route's interface first,
+    /// then the first physical interface that is up, then any interface with a MAC.
+    pub fn primary_mac(&self) -> Option<String> {
+        let with_mac = || self.interfaces.iter().filter(|iface| iface.mac.is_some());
+
+        self.default_interface
+            .as_ref()
+            .and_then(|name| with_mac().find(|iface| &iface.name == name))
+            .or_else(|| with_mac().find(|iface| iface.is_up && !iface.is_virtual))
+            .or_else(|| with_mac().find(|iface| !iface.is_virtual))
+            .or_else(|| with_mac().next())
+            .and_then(|iface| iface.mac.clone())
+    }
+}
+
+fn normalize_mac(mac: &str) -> Option<String> {
+    let mac = mac.trim().replace('-', ":").to_lowercase();
+    if mac.is_empty() || mac == "00:00:00:00:00:00" {
+        None
+    } else {
+        Some(mac)
+    }
+}
+
+/// Lists every network interface (except loopback) sorted by name, plus the default route
+pub fn collect_network_info() -> NetworkInfo {
+    #[cfg(target_os = "linux")]
+    let mut info = linux::collect();
+    #[cfg(target_os = "windows")]
+    let mut info = windows::collect().unwrap_or_default();
+    #[cfg(target_os = "macos")]
+    let mut info = macos::collect();
+
+    info.interfaces.sort_by(|a, b| a.name.cmp(&b.name));
+    info
+}
+
+/// Fills in IP addresses from the OS interface list
+#[cfg(not(target_os = "windows"))]
+fn attach_addresses(interfaces: &mut [NetworkInterface]) {
+    use std::net::IpAddr;
+
+    let Ok(addresses) = local_ip_address::list_afinet_netifas() else {
+        return;
+    };
+
+    for (name, address) in addresses {
+        if let Some(iface) = interfaces.iter_mut().find(|iface| iface.name == name) {
+            match address {
+                IpAddr::V4(v4) => iface.ipv4.push(v4.to_string()),
+                IpAddr::V6(v6) => iface.ipv6.push(v6.to_string()),
+            }
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::net::Ipv4Addr;
+    use std::path::Path;
+
+    const NET_DIR: &str = "/sys/class/net";
+
+    fn read(path: &Path) -> Option<String> {
+        std::fs::read_to_string(path)
+            .ok()
+            .map(|value| value.trim().to_string())
+    }
+
+    pub fn collect() -> NetworkInfo {
+        let mut interfaces = Vec::new();
+
+        if let Ok(entries) = std::fs::read_dir(NET_DIR) {
+            for entry in entries.flatten() {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if name == "lo" {
+                    continue;
+                }
+
+                let dir = entry.path();
+                interfaces.push(NetworkInterface {
+                    mac: read(&dir.join("address")).and_then(|mac| normalize_mac(&mac)),
+                    is_up: read(&dir.join("operstate")).as_deref() == Some("up"),
+                    // Physical NICs have a backing device; bridges, veths and tunnels do not
+                    is_virtual: !dir.join("device").exists(),
+                    name,
+                    ..Default::default()
+                });
+            }
+        }
+
+        attach_addresses(&mut interfaces);
+        let (default_interface, default_gateway) = read_default_route().unzip();
+
+        NetworkInfo {
+            interfaces,
+            default_interface,
+            default_gateway,
+        }
+    }
+
+    /// Reads the lowest-metric default route from /proc/net/route
+    fn read_default_route() -> Option<(String, String)> {
+        let content = std::fs::read_to_string("/proc/net/route").ok()?;
+
+        content
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                if fields.len() < 7 || fields[1] != "00000000" {
+                    return None;
+                }
+                let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+                let metric: u32 = fields[6].parse().ok()?;
+                // The kernel prints the address in host (little-endian) byte order
+                let gateway = Ipv4Addr::from(gateway.swap_bytes()).to_string();
+                Some((metric, fields[0].to_string(), gateway))
+            })
+            .min_by_key(|(metric, name, _)| (*metric, name.clone()))
+            .map(|(_, name, gateway)| (name, gateway))
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x08000000;
+
+    const NETWORK_SCRIPT: &str = r#"
+$route = Get-NetRoute -DestinationPrefix "0.0.0.0/0" -ErrorAction SilentlyContinue |
+  Sort-Object -Property RouteMetric, InterfaceMetric | Select-Object -First 1
+[pscustomobject]@{
+  default_interface = if ($route) { $route.InterfaceAlias } else { $null }
+  default_gateway = if ($route) { $route.NextHop } else { $null }
+  interfaces = @(Get-NetAdapter -IncludeHidden -ErrorAction SilentlyContinue | ForEach-Object {
+    $addresses = @(Get-NetIPAddress -InterfaceIndex $_.ifIndex -ErrorAction SilentlyContinue)
+    [pscustomobject]@{
+      name = $_.Name
+      mac = $_.MacAddress
+      ipv4 = @($addresses | Where-Object AddressFamily -eq 'IPv4' | ForEach-Object { $_.IPAddress })
+      ipv6 = @($addresses | Where-Object AddressFamily -eq 'IPv6' | ForEach-Object { $_.IPAddress })
+      is_up = $_.Status -eq 'Up'
+      is_virtual = [bool]($_.Virtual -or -not $_.HardwareInterface)
+    }
+  })
+} | ConvertTo-Json -Depth 4 -Compress
+"#;
+
+    pub fn collect() -> Option<NetworkInfo> {
+        let output = Command::new("powershell.exe")
+            .args(["-NoProfile", "-NonInteractive", "-Command", NETWORK_SCRIPT])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output()
+            .ok()?;
+
+        let mut info: NetworkInfo = serde_json::from_slice(&output.stdout).ok()?;
+        for iface in info.interfaces.iter_mut() {
+            iface.mac = iface.mac.as_deref().and_then(normalize_mac);
+        }
+
+        Some(info)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+    use std::process::Command;
+
+    // Tunnels, bridges and Apple's peer-to-peer links
+    const VIRTUAL_PREFIXES: [&str; 9] = [
+        "utun", "bridge", "awdl", "llw", "vmnet", "gif", "stf", "anpi", "ap",
+    ];
+
+    pub fn collect() -> NetworkInfo {
+        let mut interfaces = Vec::new();
+
+        if let Ok(output) = Command::new("ifconfig").output() {
+            let output = String::from_utf8_lossy(&output.stdout);
+            let mut current: Option<NetworkInterface> = None;
+
+            for line in output.lines() {
+                if !line.starts_with(char::is_whitespace) {
+                    if let Some(iface) = current.take() {
+                        interfaces.push(iface);
+                    }
+
+                    let Some((name, flags)) = line.split_once(':') else {
+                        continue;
+                    };
+                    if name.starts_with("lo") {
+                        continue;
+                    }
+
+                    current = Some(NetworkInterface {
+                        name: name.to_string(),
+                        is_up: flags.contains("<UP"),
+                        is_virtual: VIRTUAL_PREFIXES
+                            .iter()
+                            .any(|prefix| name.starts_with(prefix)),
+                        ..Default::default()
+                    });
+                } else if let Some(iface) = current.as_mut() {
+                    let line = line.trim();
+                    if let Some(mac) = line.strip_prefix("ether ") {
+                        iface.mac = normalize_mac(mac.split_whitespace().next().unwrap_or(""));
+                    } else if let Some(status) = line.strip_prefix("status:") {
+                        iface.is_up = status.trim() == "active";
+                    }
+                }
+            }
+
+            if let Some(iface) = current.take() {
+                interfaces.push(iface);
+            }
+        }
+
+        attach_addresses(&mut interfaces);
+
+        let mut default_interface = None;
+        let mut default_gateway = None;
+        if let Ok(output) = Command::new("route").args(["-n", "get", "default"]).output() {
+            for line in String::from_utf8_lossy(&output.stdout).lines() {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                match key.trim() {
+                    "interface" => default_interface = Some(value.trim().to_string()),
+                    "gateway" => default_gateway = Some(value.trim().to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        NetworkInfo {
+            interfaces,
+            default_interface,
+            default_gateway,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn iface(name: &str, mac: Option<&str>, is_up: bool, is_virtual: bool) -> NetworkInterface {
+        NetworkInterface {
+            name: name.to_string(),
+            mac: mac.map(str::to_string),
+            is_up,
+            is_virtual,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn picks_the_primary_mac_in_order_of_preference() {
+        let cases = [
+            (
+                "default route wins over an up physical interface",
+                vec![
+                    iface("eth0", Some("aa:00:00:00:00:01"), true, false),
+                    iface("wlan0", Some("aa:00:00:00:00:02"), true, false),
+                ],
+                Some("wlan0"),
+                Some("aa:00:00:00:00:02"),
+            ),
+            (
+                "default route even when it is virtual",
+                vec![
+                    iface("eth0", Some("aa:00:00:00:00:01"), true, false),
+                    iface("tun0", Some("aa:00:00:00:00:03"), true, true),
+                ],
+                Some("tun0"),
+                Some("aa:00:00:00:00:03"),
+            ),
+            (
+                "up physical interface when the default route has no MAC",
+                vec![
+                    iface("eth0", Some("aa:00:00:00:00:01"), false, false),
+                    iface("ppp0", None, true, false),
+                    iface("veth1", Some("aa:00:00:00:00:04"), true, true),
+                    iface("wlan0", Some("aa:00:00:00:00:02"), true, false),
+                ],
+                Some("ppp0"),
+                Some("aa:00:00:00:00:02"),
+            ),
+            (
+                "physical interface that is down before a virtual one",
+                vec![
+                    iface("docker0", Some("aa:00:00:00:00:05"), true, true),
+                    iface("eth0", Some("aa:00:00:00:00:01"), false, false),
+                ],
+                None,
+                Some("aa:00:00:00:00:01"),
+            ),
+            (
+                "any interface with a MAC as the last resort",
+                vec![
+                    iface("eth0", None, true, false),
+                    iface("docker0", Some("aa:00:00:00:00:05"), false, true),
+                ],
+                None,
+                Some("aa:00:00:00:00:05"),
+            ),
+            (
+                "no MAC at all",
+                vec![iface("eth0", None, true, false)],
+                Some("eth0"),
+                None,
+            ),
+        ];
+
+        for (case, interfaces, default_interface, expected) in cases {
+            let info = NetworkInfo {
+                interfaces,
+                default_interface: default_interface.map(str::to_string),
+                default_gateway: None,
+            };
+            assert_eq!(info.primary_mac().as_deref(), expected, "{}", case);
+        }
+    }
+}
@@ -0,0 +1,342 @@
+use crate::error::AgentError;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Hive {
+    Hklm,
+    Hkcu,
+    Hku,
+}
+
+impl Hive {
+    /// Accepts both the short and the full hive names, case-insensitively
+    pub fn parse(name: &str) -> Option<Hive> {
+        match name.to_ascii_uppercase().as_str() {
+            "HKLM" | "HKEY_LOCAL_MACHINE" => Some(Hive::Hklm),
+            "HKCU" | "HKEY_CURRENT_USER" => Some(Hive::Hkcu),
+            "HKU" | "HKEY_USERS" => Some(Hive::Hku),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Hive::Hklm => "HKLM",
+            Hive::Hkcu => "HKCU",
+            Hive::Hku => "HKU",
+        }
+    }
+}
+
+/// A registry value with its type, sent to the frontend as `{ type, value }`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum RegistryValue {
+    String(String),
+    ExpandString(String),
+    Dword(u32),
+    Qword(u64),
+    MultiString(Vec<String>),
+    Binary(Vec<u8>),
+}
+
+/// Source of registry values. The command logic only talks to this trait so it can run
+/// against something other than the real registry.
+pub trait RegistryReader: Send + Sync {
+    fn read_value(&self, hive: Hive, path: &str, name: &str) -> Result<RegistryValue, AgentError>;
+}
+
+#[cfg(target_os = "windows")]
+pub struct WindowsRegistry;
+
+#[cfg(target_os = "windows")]
+impl RegistryReader for WindowsRegistry {
+    fn read_value(&self, hive: Hive, path: &str, name: &str) -> Result<RegistryValue, AgentError> {
+        use winreg::enums::*;
+        use winreg::types::FromRegValue;
+        use winreg::RegKey;
+
+        let root = match hive {
+            Hive::Hklm => HKEY_LOCAL_MACHINE,
+            Hive::Hkcu => HKEY_CURRENT_USER,
+            Hive::Hku => HKEY_USERS,
+        };
+        let key = RegKey::predef(root)
+            .open_subkey(path)
+            .map_err(|e| AgentError::Io(format!("Failed to open registry path {}: {}", path, e)))?;
+        let raw = key.get_raw_value(name).map_err(|e| {
+            AgentError::Io(format!("Failed to read registry value {}: {}", name, e))
+        })?;
+
+        let parse_error =
+            |e: std::io::Error| AgentError::Parse(format!("Registry value {}: {}", name, e));
+        Ok(match raw.vtype {
+            REG_SZ => RegistryValue::String(String::from_reg_value(&raw).map_err(parse_error)?),
+            REG_EXPAND_SZ => {
+                RegistryValue::ExpandString(String::from_reg_value(&raw).map_err(parse_error)?)
+            }
+            REG_DWORD => RegistryValue::Dword(u32::from_reg_value(&raw).map_err(parse_error)?),
+            REG_QWORD => RegistryValue::Qword(u64::from_reg_value(&raw).map_err(parse_error)?),
+            REG_MULTI_SZ => RegistryValue::MultiString(
+                Vec::<String>::from_reg_value(&raw).map_err(parse_error)?,
+            ),
+            _ => RegistryValue::Binary(raw.bytes.to_vec()),
+        })
+    }
+}
+
+/// Used where there is no registry, every read fails with `PlatformUnsupported`
+#[cfg(not(target_os = "windows"))]
+pub struct UnsupportedRegistry;
+
+#[cfg(not(target_os = "windows"))]
+impl RegistryReader for UnsupportedRegistry {
+    fn read_value(
+        &self,
+        _hive: Hive,
+        _path: &str,
+        _name: &str,
+    ) -> Result<RegistryValue, AgentError> {
+        Err(AgentError::PlatformUnsupported("Registry access"))
+    }
+}
+
+/// The registry of this platform
+pub fn system_registry() -> Arc<dyn RegistryReader> {
+    #[cfg(target_os = "windows")]
+    {
+        Arc::new(WindowsRegistry)
+    }
+    #[cfg(not(target_os = "windows"))]
+    {
+        Arc::new(UnsupportedRegistry)
+    }
+}
+
+/// Normalizes a key path for comparison: backslash separated, no leading or trailing
+/// separators, lowercase since registry paths are case-insensitive
+fn normalize(path: &str) -> String {
+    path.replace('/', "\\")
+        .split('\\')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("\\")
+        .to_lowercase()
+}
+
+/// Registry reads the webview may make. Only keys under an allow-listed path are readable,
+/// and nothing is allowed until settings provide a list.
+#[derive(Clone)]
+pub struct RegistryAccess {
+    reader: Arc<dyn RegistryReader>,
+    allowed: Arc<RwLock<Vec<(Hive, String)>>>,
+}
+
+impl Default for RegistryAccess {
+    fn default() -> Self {
+        Self::new(system_registry())
+    }
+}
+
+impl RegistryAccess {
+    pub fn new(reader: Arc<dyn RegistryReader>) -> Self {
+        Self {
+            reader,
+            allowed: Arc::new(RwLock::new(Vec::new())),
+        }
+    }
+
+    /// Replaces the allow-list. Entries look like `HKLM\SOFTWARE\Vendor`; ones with an
+    /// unknown hive are skipped.
+    pub fn set_allowed_paths(&self, paths: &[String]) {
+        let parsed = paths
+            .iter()
+            .filter_map(|entry| {
+                let normalized = entry.replace('/', "\\");
+                let (hive, path) = normalized.split_once('\\').unwrap_or((&normalized, ""));
+                match Hive::parse(hive) {
+                    Some(hive) => Some((hive, normalize(path))),
+                    None => {
+                        warn!(
+                            "Ignoring registry allow-list entry with unknown hive: {}",
+                            entry
+                        );
+                        None
+                    }
+                }
+            })
+            .collect();
+
+        let mut allowed = match self.allowed.write() {
+            Ok(allowed) => allowed,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *allowed = parsed;
+    }
+
+    pub fn is_allowed(&self, hive: Hive, path: &str) -> bool {
+        let path = normalize(path);
+        let allowed = match self.allowed.read() {
+            Ok(allowed) => allowed,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        allowed.iter().any(|(allowed_hive, root)| {
+            *allowed_hive == hive
+                && (root.is_empty()
+                    || path == *root
+                    || path
+                        .strip_prefix(root.as_str())
+                        .is_some_and(|rest| rest.starts_with('\\')))
+        })
+    }
+
+    /// Reads a value if its key is allow-listed
+    pub fn read(&self, hive: Hive, path: &str, name: &str) -> Result<RegistryValue, AgentError> {
+        if !self.is_allowed(hive, path) {
+            return Err(AgentError::AccessDenied(format!(
+                "{}\\{}",
+                hive.name(),
+                path
+            )));
+        }
+        // Keep the caller's case, only the separators are normalized for the reader
+        let path = path.replace('/', "\\");
+        self.reader.read_value(hive, path.trim_matches('\\'), name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    /// Registry held in memory, keyed like the real one: case-insensitive paths
+    #[derive(Default)]
+    struct MemoryRegistry {
+        values: HashMap<(&'static str, String, String), RegistryValue>,
+    }
+
+    impl MemoryRegistry {
+        fn with(mut self, hive: Hive, path: &str, name: &str, value: RegistryValue) -> Self {
+            self.values
+                .insert((hive.name(), path.to_lowercase(), name.to_string()), value);
+            self
+        }
+    }
+
+    impl RegistryReader for MemoryRegistry {
+        fn read_value(
+            &self,
+            hive: Hive,
+            path: &str,
+            name: &str,
+        ) -> Result<RegistryValue, AgentError> {
+            self.values
+                .get(&(hive.name(), path.to_lowercase(), name.to_string()))
+                .cloned()
+                .ok_or_else(|| AgentError::Io(format!("{} not found", name)))
+        }
+    }
+
+    fn access(allowed: &[&str]) -> RegistryAccess {
+        let registry = MemoryRegistry::default()
+            .with(
+                Hive::Hklm,
+                "SOFTWARE\\Vendor\\Agent",
+                "Version",
+                RegistryValue::String("4.2".to_string()),
+            )
+            .with(
+                Hive::Hklm,
+                "SOFTWARE\\VendorX",
+                "Secret",
+                RegistryValue::Dword(1),
+            );
+        let access = RegistryAccess::new(Arc::new(registry));
+        access.set_allowed_paths(
+            &allowed
+                .iter()
+                .map(|path| path.to_string())
+                .collect::<Vec<_>>(),
+        );
+        access
+    }
+
+    #[test]
+    fn empty_allow_list_denies_everything() {
+        let access = access(&[]);
+        assert!(!access.is_allowed(Hive::Hklm, "SOFTWARE\\Vendor\\Agent"));
+        assert!(!access.is_allowed(Hive::Hklm, ""));
+        assert!(matches!(
+            access.read(Hive::Hklm, "SOFTWARE\\Vendor\\Agent", "Version"),
+            Err(AgentError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn matches_paths_case_insensitively() {
+        let access = access(&["HKLM\\SOFTWARE\\Vendor"]);
+        assert!(access.is_allowed(Hive::Hklm, "software\\vendor\\agent"));
+        assert!(access.is_allowed(Hive::Hklm, "SOFTWARE\\VENDOR"));
+        assert_eq!(
+            access
+                .read(Hive::Hklm, "Software\\Vendor\\Agent", "Version")
+                .unwrap(),
+            RegistryValue::String("4.2".to_string())
+        );
+    }
+
+    #[test]
+    fn treats_forward_and_back_slashes_alike() {
+        let access = access(&["hklm/SOFTWARE/Vendor/"]);
+        assert!(access.is_allowed(Hive::Hklm, "SOFTWARE\\Vendor\\Agent"));
+        assert!(access.is_allowed(Hive::Hklm, "/SOFTWARE/Vendor/Agent"));
+        for path in [
+            "SOFTWARE/Vendor/Agent",
+            "/SOFTWARE/Vendor/Agent/",
+            "\\SOFTWARE\\Vendor\\Agent\\",
+        ] {
+            assert_eq!(
+                access.read(Hive::Hklm, path, "Version").unwrap(),
+                RegistryValue::String("4.2".to_string()),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_a_sibling_sharing_the_prefix() {
+        let access = access(&["HKLM\\SOFTWARE\\Vendor"]);
+        assert!(!access.is_allowed(Hive::Hklm, "SOFTWARE\\VendorX"));
+        assert!(matches!(
+            access.read(Hive::Hklm, "SOFTWARE\\VendorX", "Secret"),
+            Err(AgentError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_the_wrong_hive() {
+        let access = access(&["HKCU\\SOFTWARE\\Vendor"]);
+        assert!(access.is_allowed(Hive::Hkcu, "SOFTWARE\\Vendor\\Agent"));
+        assert!(!access.is_allowed(Hive::Hklm, "SOFTWARE\\Vendor\\Agent"));
+        assert!(!access.is_allowed(Hive::Hku, "SOFTWARE\\Vendor\\Agent"));
+    }
+
+    #[test]
+    fn skips_entries_with_an_unknown_hive() {
+        let access = access(&[
+            "HKXX\\SOFTWARE\\Vendor",
+            "SOFTWARE\\VendorX",
+            "HKEY_LOCAL_MACHINE\\SOFTWARE\\Vendor",
+        ]);
+        assert!(access.is_allowed(Hive::Hklm, "SOFTWARE\\Vendor\\Agent"));
+        assert!(!access.is_allowed(Hive::Hklm, "SOFTWARE\\VendorX"));
+        for hive in [Hive::Hklm, Hive::Hkcu, Hive::Hku] {
+            assert!(!access.is_allowed(hive, "HKXX\\SOFTWARE\\Vendor"));
+        }
+    }
+}
@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+
+/// An RMM agent found on this machine
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RmmAgent {
+    pub vendor: String,
+    pub device_id: String,
+}
+
+/// Detects one vendor's RMM agent and returns the device ID it was assigned
+pub trait RmmDetector: Send + Sync {
+    fn vendor(&self) -> &'static str;
+    fn detect(&self) -> Option<String>;
+}
+
+/// Every known detector, in priority order. Datto stays first since the
+/// support flow uses the first agent found as the ticket's `rmm_id`.
+pub fn detectors() -> Vec<Box<dyn RmmDetector>> {
+    vec![
+        Box::new(DattoRmmDetector),
+        Box::new(NinjaOneDetector),
+        Box::new(ConnectWiseAutomateDetector),
+        Box::new(ScreenConnectDetector),
+        Box::new(NableDetector),
+        Box::new(TacticalRmmDetector),
+    ]
+}
+
+/// Runs every detector and returns the agents that were found
+pub fn detect_rmm_agents() -> Vec<RmmAgent> {
+    detectors()
+        .iter()
+        .filter_map(|detector| {
+            detector.detect().map(|device_id| RmmAgent {
+                vendor: detector.vendor().to_string(),
+                device_id,
+            })
+        })
+        .collect()
+}
+
+fn clean(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+/// Reads a string field from a JSON file
+fn read_json_field(path: &str, field: &str) -> Option<String> {
+    let contents = std::fs::read_to_string(path).ok()?;
+    let json = serde_json::from_str::<serde_json::Value>(&contents).ok()?;
+    match json.get(field)? {
+        serde_json::Value::String(value) => clean(value),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod registry {
+    use winreg::{enums::*, RegKey};
+
+    /// Reads an HKLM value as a string, accepting both REG_SZ and REG_DWORD
+    pub fn read_hklm(path: &str, name: &str) -> Option<String> {
+        let key = RegKey::predef(HKEY_LOCAL_MACHINE).open_subkey(path).ok()?;
+        if let Ok(value) = key.get_value::<String, _>(name) {
+            return super::clean(&value);
+        }
+        key.get_value::<u32, _>(name)
+            .ok()
+            .filter(|value| *value != 0)
+            .map(|value| value.to_string())
+    }
+
+    /// Tries each path in turn, for agents that moved keys between versions or bitness
+    pub fn read_hklm_any(paths: &[&str], name: &str) -> Option<String> {
+        paths.iter().find_map(|path| read_hklm(path, name))
+    }
+
+    /// Lists subkey names of an HKLM key
+    pub fn hklm_subkeys(path: &str) -> Vec<String> {
+        RegKey::predef(HKEY_LOCAL_MACHINE)
+            .open_subkey(path)
+            .map(|key| key.enum_keys().flatten().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Datto RMM (formerly CentraStage)
+pub struct DattoRmmDetector;
+
+impl RmmDetector for DattoRmmDetector {
+    fn vendor(&self) -> &'static str {
+        "Datto RMM"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            // Registry is the most reliable source, Settings.json is the fallback
+            registry::read_hklm("SOFTWARE\\CentraStage", "DeviceID").or_else(|| {
+                read_json_field(
+                    r"C:\ProgramData\CentraStage\AEMAgent\Settings.json",
+                    "deviceUID",
+                )
+            })
+        }
+
+        #[cfg(target_os = "macos")]
+        {
+            read_json_field(
+                "/usr/local/share/CentraStage/AEMAgent/Settings.json",
+                "deviceUID",
+            )
+        }
+
+        #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+        {
+            None
+        }
+    }
+}
+
+/// NinjaOne (NinjaRMM)
+pub struct NinjaOneDetector;
+
+impl RmmDetector for NinjaOneDetector {
+    fn vendor(&self) -> &'static str {
+        "NinjaOne"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            registry::read_hklm_any(
+                &[
+                    "SOFTWARE\\WOW6432Node\\NinjaRMM LLC\\NinjaRMMAgent\\Agent",
+                    "SOFTWARE\\NinjaRMM LLC\\NinjaRMMAgent\\Agent",
+                ],
+                "NodeId",
+            )
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            None
+        }
+    }
+}
+
+/// ConnectWise Automate (formerly LabTech)
+pub struct ConnectWiseAutomateDetector;
+
+impl RmmDetector for ConnectWiseAutomateDetector {
+    fn vendor(&self) -> &'static str {
+        "ConnectWise Automate"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            registry::read_hklm("SOFTWARE\\LabTech\\Service", "ID")
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            None
+        }
+    }
+}
+
+/// ConnectWise ScreenConnect (Control). The session GUID is passed to the
+/// client service on its command line as `s=<guid>`.
+pub struct ScreenConnectDetector;
+
+#[cfg(target_os = "windows")]
+impl ScreenConnectDetector {
+    fn session_from_command_line(command_line: &str) -> Option<String> {
+        command_line
+            .split(|c| c == '&' || c == '?' || c == '"' || c == ' ')
+            .find_map(|part| part.strip_prefix("s="))
+            .and_then(clean)
+    }
+}
+
+impl RmmDetector for ScreenConnectDetector {
+    fn vendor(&self) -> &'static str {
+        "ScreenConnect"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            const SERVICES: &str = "SYSTEM\\CurrentControlSet\\Services";
+
+            registry::hklm_subkeys(SERVICES)
+                .into_iter()
+                .filter(|name| name.starts_with("ScreenConnect Client"))
+                .find_map(|name| {
+                    let image_path =
+                        registry::read_hklm(&format!("{}\\{}", SERVICES, name), "ImagePath")?;
+                    Self::session_from_command_line(&image_path)
+                })
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            None
+        }
+    }
+}
+
+/// N-able N-central
+pub struct NableDetector;
+
+#[cfg(target_os = "windows")]
+impl NableDetector {
+    fn appliance_id(path: &str) -> Option<String> {
+        let contents = std::fs::read_to_string(path).ok()?;
+        let start = contents.find("<ApplianceID>")? + "<ApplianceID>".len();
+        let end = contents[start..].find("</ApplianceID>")? + start;
+        clean(&contents[start..end])
+    }
+}
+
+impl RmmDetector for NableDetector {
+    fn vendor(&self) -> &'static str {
+        "N-able"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            [
+                r"C:\Program Files (x86)\N-able Technologies\Windows Agent\config\ApplianceConfig.xml",
+                r"C:\Program Files\N-able Technologies\Windows Agent\config\ApplianceConfig.xml",
+            ]
+            .iter()
+            .find_map(|path| Self::appliance_id(path))
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            None
+        }
+    }
+}
+
+/// Tactical RMM
+pub struct TacticalRmmDetector;
+
+impl RmmDetector for TacticalRmmDetector {
+    fn vendor(&self) -> &'static str {
+        "Tactical RMM"
+    }
+
+    fn detect(&self) -> Option<String> {
+        #[cfg(target_os = "windows")]
+        {
+            registry::read_hklm("SOFTWARE\\TacticalRMM", "AgentID")
+        }
+
+        #[cfg(not(target_os = "windows"))]
+        {
+            read_json_field("/etc/tacticalagent", "agentid")
+        }
+    }
+}
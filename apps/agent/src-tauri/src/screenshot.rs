@@ -0,0 +1,504 @@
+use crate::device_manager::Settings;
+use crate::error::AgentError;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, RgbaImage};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use xcap::Monitor;
+
+const DEFAULT_UPLOAD_QUALITY: u8 = 80;
+const DEFAULT_UPLOAD_MAX_DIMENSION: u32 = 2560;
+const DEFAULT_UPLOAD_MAX_KB: u64 = 5 * 1024;
+// Below these the screenshot stops being useful, so give up instead of shrinking further
+const MIN_UPLOAD_QUALITY: u8 = 40;
+const MIN_UPLOAD_DIMENSION: u32 = 640;
+
+/// A monitor as reported by the OS. Position and size are in desktop coordinates,
+/// which are logical points on macOS and physical pixels elsewhere.
+#[derive(Serialize, Clone, Debug)]
+pub struct MonitorInfo {
+    pub id: u32,
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f32,
+    pub is_primary: bool,
+}
+
+/// What to capture. With no monitor every screen is captured.
+#[derive(Deserialize, Debug, Default, Clone)]
+pub struct ScreenshotOptions {
+    pub monitor_id: Option<u32>,
+    /// Also combine the captures into one image laid out like the desktop
+    #[serde(default)]
+    pub stitch: bool,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct MonitorCapture {
+    pub monitor: MonitorInfo,
+    pub path: String,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct ScreenshotResult {
+    pub captures: Vec<MonitorCapture>,
+    pub stitched_path: Option<String>,
+}
+
+impl ScreenshotResult {
+    /// The image to attach by default: the composite if there is one, otherwise the
+    /// primary monitor's capture
+    pub fn preferred_path(&self) -> Option<&str> {
+        self.stitched_path.as_deref().or_else(|| {
+            self.captures
+                .iter()
+                .find(|capture| capture.monitor.is_primary)
+                .or_else(|| self.captures.first())
+                .map(|capture| capture.path.as_str())
+        })
+    }
+}
+
+/// Where captures are written. Kept separate so other code can tell agent screenshots apart,
+/// and per user so nobody else on the machine can read or swap them.
+pub fn screenshot_dir() -> PathBuf {
+    #[cfg(unix)]
+    {
+        // The temp dir is shared between users on Linux
+        let uid = unsafe { libc::getuid() };
+        std::env::temp_dir()
+            .join(format!("mspagent-{}", uid))
+            .join("screenshots")
+    }
+    #[cfg(not(unix))]
+    {
+        // %TEMP% is inside the user's profile already
+        std::env::temp_dir().join("mspagent").join("screenshots")
+    }
+}
+
+/// Creates `dir` readable only by the current user, or checks that an existing one is.
+/// Refuses a directory someone else created first, e.g. to read captures as they land.
+#[cfg(unix)]
+pub(crate) fn create_private_dir(dir: &Path) -> Result<(), AgentError> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    match std::fs::DirBuilder::new().mode(0o700).create(dir) {
+        Ok(()) => return Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    let metadata = std::fs::symlink_metadata(dir)?;
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid {
+        return Err(AgentError::AccessDenied(format!(
+            "{} is not a directory owned by this user",
+            dir.display()
+        )));
+    }
+    if metadata.mode() & 0o077 != 0 {
+        std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+    }
+    Ok(())
+}
+
+/// Creates the screenshot directory if needed and returns it
+pub fn create_screenshot_dir() -> Result<PathBuf, AgentError> {
+    let dir = screenshot_dir();
+    #[cfg(unix)]
+    if let Some(parent) = dir.parent() {
+        create_private_dir(parent)?;
+    }
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn xcap_error(context: &str, e: xcap::XCapError) -> AgentError {
+    AgentError::Io(format!("{}: {}", context, e))
+}
+
+fn monitor_info(monitor: &Monitor) -> Result<MonitorInfo, AgentError> {
+    let context = "Failed to read monitor geometry";
+    Ok(MonitorInfo {
+        id: monitor.id().map_err(|e| xcap_error(context, e))?,
+        name: monitor.name().unwrap_or_default(),
+        x: monitor.x().map_err(|e| xcap_error(context, e))?,
+        y: monitor.y().map_err(|e| xcap_error(context, e))?,
+        width: monitor.width().map_err(|e| xcap_error(context, e))?,
+        height: monitor.height().map_err(|e| xcap_error(context, e))?,
+        scale_factor: monitor.scale_factor().unwrap_or(1.0),
+        is_primary: monitor.is_primary().unwrap_or(false),
+    })
+}
+
+fn all_monitors() -> Result<Vec<(Monitor, MonitorInfo)>, AgentError> {
+    let monitors = Monitor::all().map_err(|e| xcap_error("Failed to get monitors", e))?;
+    let mut result = Vec::with_capacity(monitors.len());
+    for monitor in monitors {
+        match monitor_info(&monitor) {
+            Ok(info) => result.push((monitor, info)),
+            Err(e) => warn!("Skipping monitor: {}", e),
+        }
+    }
+    Ok(result)
+}
+
+/// Lists the monitors that can be captured, primary first
+pub fn list_monitors() -> Result<Vec<MonitorInfo>, AgentError> {
+    let mut monitors: Vec<MonitorInfo> =
+        all_monitors()?.into_iter().map(|(_, info)| info).collect();
+    monitors.sort_by_key(|monitor| (!monitor.is_primary, monitor.x, monitor.y));
+    Ok(monitors)
+}
+
+/// Lays the captures out on one canvas using the monitor positions. Captures are in
+/// physical pixels while positions may be logical, so everything is scaled to the
+/// highest pixel density among the monitors to avoid losing detail.
+fn stitch(captures: &[(MonitorInfo, RgbaImage)]) -> Option<RgbaImage> {
+    let min_x = captures.iter().map(|(info, _)| info.x).min()?;
+    let min_y = captures.iter().map(|(info, _)| info.y).min()?;
+    let max_x = captures
+        .iter()
+        .map(|(info, _)| info.x + info.width as i32)
+        .max()?;
+    let max_y = captures
+        .iter()
+        .map(|(info, _)| info.y + info.height as i32)
+        .max()?;
+
+    let density = captures
+        .iter()
+        .map(|(info, image)| image.width() as f64 / info.width.max(1) as f64)
+        .fold(1.0_f64, f64::max);
+
+    let scale = |value: i32| (value as f64 * density).round() as u32;
+    let mut canvas = RgbaImage::new(scale(max_x - min_x), scale(max_y - min_y));
+
+    for (info, image) in captures {
+        let width = scale(info.width as i32);
+        let height = scale(info.height as i32);
+        let x = scale(info.x - min_x) as i64;
+        let y = scale(info.y - min_y) as i64;
+
+        if image.width() == width && image.height() == height {
+            imageops::overlay(&mut canvas, image, x, y);
+        } else {
+            let resized = imageops::resize(image, width, height, FilterType::Triangle);
+            imageops::overlay(&mut canvas, &resized, x, y);
+        }
+    }
+
+    Some(canvas)
+}
+
+/// Captures the requested monitors and saves each one, plus the composite when asked for,
+/// as PNG files in [`screenshot_dir`]. Blocking; run it off the async runtime.
+pub fn capture(options: &ScreenshotOptions) -> Result<ScreenshotResult, AgentError> {
+    let monitors: Vec<(Monitor, MonitorInfo)> = all_monitors()?
+        .into_iter()
+        .filter(|(_, info)| match options.monitor_id {
+            Some(id) => id == info.id,
+            None => true,
+        })
+        .collect();
+
+    if monitors.is_empty() {
+        return Err(match options.monitor_id {
+            Some(id) => AgentError::Io(format!("Monitor {} was not found", id)),
+            None => AgentError::Io(String::from("No screenshotable monitors found")),
+        });
+    }
+
+    let dir = create_screenshot_dir()?;
+    let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+
+    info!("Capturing {} monitor(s)", monitors.len());
+    let mut images = Vec::with_capacity(monitors.len());
+    let mut captures = Vec::with_capacity(monitors.len());
+    for (monitor, info) in monitors {
+        let image = monitor
+            .capture_image()
+            .map_err(|e| xcap_error(&format!("Failed to capture monitor {}", info.name), e))?;
+
+        let path = dir.join(format!("screenshot_{}_{}.png", stamp, info.id));
+        image
+            .save(&path)
+            .map_err(|e| AgentError::Io(format!("Failed to save screenshot: {}", e)))?;
+
+        captures.push(MonitorCapture {
+            monitor: info.clone(),
+            path: path.to_string_lossy().to_string(),
+        });
+        images.push((info, image));
+    }
+
+    let stitched_path = if options.stitch && images.len() > 1 {
+        let canvas = stitch(&images)
+            .ok_or_else(|| AgentError::Io(String::from("Failed to stitch screenshots")))?;
+        let path = dir.join(format!("screenshot_{}_all.png", stamp));
+        canvas
+            .save(&path)
+            .map_err(|e| AgentError::Io(format!("Failed to save screenshot: {}", e)))?;
+        info!(
+            "Stitched {} monitors into {}x{}",
+            images.len(),
+            canvas.width(),
+            canvas.height()
+        );
+        Some(path.to_string_lossy().to_string())
+    } else {
+        None
+    };
+
+    Ok(ScreenshotResult {
+        captures,
+        stitched_path,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadFormat {
+    Jpeg,
+    /// Lossless, so quality does not apply and only downscaling reduces the size
+    Webp,
+}
+
+impl UploadFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            UploadFormat::Jpeg => "jpg",
+            UploadFormat::Webp => "webp",
+        }
+    }
+}
+
+/// How screenshots are re-encoded before they are attached to a ticket
+#[derive(Debug, Clone)]
+pub struct UploadImageOptions {
+    pub format: UploadFormat,
+    pub quality: u8,
+    pub max_dimension: u32,
+    pub max_bytes: u64,
+}
+
+impl Default for UploadImageOptions {
+    fn default() -> Self {
+        Self {
+            format: UploadFormat::Jpeg,
+            quality: DEFAULT_UPLOAD_QUALITY,
+            max_dimension: DEFAULT_UPLOAD_MAX_DIMENSION,
+            max_bytes: DEFAULT_UPLOAD_MAX_KB * 1024,
+        }
+    }
+}
+
+impl UploadImageOptions {
+    /// Reads the upload options from settings. A `screenshot_max_upload_kb` of 0 means no
+    /// size cap. A quality configured for WebP is ignored, WebP is always lossless.
+    pub fn from_settings(settings: &Settings) -> Self {
+        let defaults = Self::default();
+        let format = match settings.screenshot_format.as_deref() {
+            Some("webp") => UploadFormat::Webp,
+            Some("jpeg") | Some("jpg") | None => UploadFormat::Jpeg,
+            Some(other) => {
+                warn!("Unknown screenshot format {}, using jpeg", other);
+                UploadFormat::Jpeg
+            }
+        };
+
+        if format == UploadFormat::Webp && settings.screenshot_quality.is_some() {
+            warn!("Ignoring screenshot_quality, webp screenshots are always lossless");
+        }
+
+        Self {
+            format,
+            quality: settings
+                .screenshot_quality
+                .map(|quality| quality.clamp(1, 100))
+                .unwrap_or(defaults.quality),
+            max_dimension: settings
+                .screenshot_max_dimension
+                .map(|dimension| dimension.max(MIN_UPLOAD_DIMENSION))
+                .unwrap_or(defaults.max_dimension),
+            max_bytes: match settings.screenshot_max_upload_kb {
+                Some(0) => u64::MAX,
+                Some(kb) => kb.saturating_mul(1024),
+                None => defaults.max_bytes,
+            },
+        }
+    }
+}
+
+fn encode(image: &DynamicImage, format: UploadFormat, quality: u8) -> Result<Vec<u8>, AgentError> {
+    let mut buffer = Vec::new();
+    let result = match format {
+        UploadFormat::Jpeg => image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut buffer, quality)),
+        UploadFormat::Webp => image
+            .to_rgba8()
+            .write_with_encoder(WebPEncoder::new_lossless(&mut buffer)),
+    };
+    result.map_err(|e| AgentError::Io(format!("Failed to encode screenshot: {}", e)))?;
+    Ok(buffer)
+}
+
+/// Re-encodes a screenshot for upload and writes it to `target` with the format's extension.
+/// Images larger than the max dimension are downscaled first; if the result is still over the
+/// size cap the JPEG quality is lowered, then the image is shrunk further until it fits.
+/// Blocking; run it off the async runtime.
+pub fn prepare_for_upload(
+    source: &Path,
+    target: &Path,
+    options: &UploadImageOptions,
+) -> Result<PathBuf, AgentError> {
+    let original_bytes = std::fs::metadata(source)?.len();
+    let original = image::open(source)
+        .map_err(|e| AgentError::Io(format!("Failed to open {}: {}", source.display(), e)))?;
+    let (original_width, original_height) = (original.width(), original.height());
+
+    let mut image = if original_width.max(original_height) > options.max_dimension {
+        original.resize(
+            options.max_dimension,
+            options.max_dimension,
+            FilterType::Lanczos3,
+        )
+    } else {
+        original
+    };
+    let mut quality = options.quality;
+
+    let encoded = loop {
+        let encoded = encode(&image, options.format, quality)?;
+        if encoded.len() as u64 <= options.max_bytes {
+            break encoded;
+        }
+
+        if options.format == UploadFormat::Jpeg && quality > MIN_UPLOAD_QUALITY {
+            quality = quality.saturating_sub(15).max(MIN_UPLOAD_QUALITY);
+            continue;
+        }
+
+        let longest = image.width().max(image.height());
+        if longest <= MIN_UPLOAD_DIMENSION {
+            return Err(AgentError::Io(format!(
+                "Screenshot is still {} bytes at {}x{}, over the {} byte upload limit",
+                encoded.len(),
+                image.width(),
+                image.height(),
+                options.max_bytes
+            )));
+        }
+        let next = (longest * 3 / 4).max(MIN_UPLOAD_DIMENSION);
+        image = image.resize(next, next, FilterType::Lanczos3);
+    };
+
+    let target = target.with_extension(options.format.extension());
+    std::fs::write(&target, &encoded)?;
+
+    info!(
+        event = "screenshot_processed";
+        "Screenshot re-encoded from {} bytes ({}x{}) to {} bytes ({}x{} {}, quality {})",
+        original_bytes,
+        original_width,
+        original_height,
+        encoded.len(),
+        image.width(),
+        image.height(),
+        options.format.extension(),
+        quality
+    );
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings(screenshot: serde_json::Value) -> Settings {
+        let mut value = json!({
+            "site_id": "site",
+            "api_host": "https://agent.example.com",
+            "installed_at": "2025-03-14T09:26:53Z",
+        });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(screenshot.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_dir_is_created_for_this_user_only() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let base = std::env::temp_dir().join(format!("mspagent-private-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&base);
+        std::fs::create_dir_all(&base).unwrap();
+
+        let dir = base.join("fresh");
+        create_private_dir(&dir).unwrap();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // An existing directory is tightened
+        let loose = base.join("loose");
+        std::fs::create_dir(&loose).unwrap();
+        std::fs::set_permissions(&loose, std::fs::Permissions::from_mode(0o777)).unwrap();
+        create_private_dir(&loose).unwrap();
+        let mode = std::fs::metadata(&loose).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+
+        // A symlink planted in its place is refused
+        let planted = base.join("planted");
+        std::os::unix::fs::symlink(&dir, &planted).unwrap();
+        assert!(matches!(
+            create_private_dir(&planted),
+            Err(AgentError::AccessDenied(_))
+        ));
+
+        std::fs::remove_dir_all(&base).unwrap();
+    }
+
+    #[test]
+    fn zero_upload_cap_means_no_cap() {
+        let options =
+            UploadImageOptions::from_settings(&settings(json!({ "screenshot_max_upload_kb": 0 })));
+        assert_eq!(options.max_bytes, u64::MAX);
+
+        let options = UploadImageOptions::from_settings(&settings(
+            json!({ "screenshot_max_upload_kb": 256 }),
+        ));
+        assert_eq!(options.max_bytes, 256 * 1024);
+    }
+
+    #[test]
+    fn ignores_a_quality_for_lossless_webp() {
+        let options = UploadImageOptions::from_settings(&settings(
+            json!({ "screenshot_format": "webp", "screenshot_quality": 60 }),
+        ));
+        assert_eq!(options.format, UploadFormat::Webp);
+
+        let target = std::env::temp_dir().join(format!("mspagent-webp-{}", std::process::id()));
+        let source = target.with_extension("png");
+        DynamicImage::new_rgba8(64, 64).save(&source).unwrap();
+        let processed = prepare_for_upload(&source, &target, &options).unwrap();
+        assert_eq!(processed.extension().unwrap(), "webp");
+        std::fs::remove_file(&source).unwrap();
+        std::fs::remove_file(&processed).unwrap();
+
+        let options = UploadImageOptions::from_settings(&settings(
+            json!({ "screenshot_format": "jpeg", "screenshot_quality": 60 }),
+        ));
+        assert_eq!(options.quality, 60);
+    }
+}
@@ -0,0 +1,628 @@
+use crate::error::AgentError;
+use crate::screenshot::create_screenshot_dir;
+use embedded_graphics::mono_font::{ascii::FONT_8X13, MonoTextStyle};
+use embedded_graphics::pixelcolor::BinaryColor;
+use embedded_graphics::prelude::{
+    DrawTarget, Drawable, OriginDimensions, Point as GlyphPoint, Size,
+};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::Pixel;
+use image::imageops::{self, FilterType};
+use image::{Rgba, RgbaImage};
+use log::{info, warn};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+
+// Blurred regions are shrunk to blocks this size before being scaled back up, which
+// destroys the detail instead of just softening it like a plain blur would
+const BLUR_BLOCK_SIZE: u32 = 12;
+const DEFAULT_LINE_WIDTH: f32 = 4.0;
+const DEFAULT_TEXT_SIZE: u32 = 24;
+const DEFAULT_COLOR: [u8; 4] = [220, 38, 38, 255];
+
+/// A rectangle in image pixels
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Region {
+    pub x: i64,
+    pub y: i64,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy)]
+pub struct Point {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// One change drawn in the screenshot editor. Coordinates are in image pixels.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ScreenshotEdit {
+    /// Pixelates the region beyond recognition
+    Blur { region: Region },
+    /// Fills the region with solid black
+    BlackOut { region: Region },
+    /// Outlines the region to draw attention to it
+    Highlight {
+        region: Region,
+        color: Option<[u8; 4]>,
+        line_width: Option<f32>,
+    },
+    Arrow {
+        from: Point,
+        to: Point,
+        color: Option<[u8; 4]>,
+        line_width: Option<f32>,
+    },
+    Text {
+        position: Point,
+        text: String,
+        color: Option<[u8; 4]>,
+        /// Height of a line of text in pixels
+        size: Option<u32>,
+    },
+}
+
+/// Clamps the region to the image, returning `(x, y, width, height)` or `None` if nothing is left
+fn clip(region: &Region, image: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let left = region.x.clamp(0, image.width() as i64);
+    let top = region.y.clamp(0, image.height() as i64);
+    let right = region
+        .x
+        .saturating_add(region.width as i64)
+        .clamp(0, image.width() as i64);
+    let bottom = region
+        .y
+        .saturating_add(region.height as i64)
+        .clamp(0, image.height() as i64);
+    if right <= left || bottom <= top {
+        return None;
+    }
+    Some((
+        left as u32,
+        top as u32,
+        (right - left) as u32,
+        (bottom - top) as u32,
+    ))
+}
+
+fn blur(image: &mut RgbaImage, region: &Region) {
+    let Some((x, y, width, height)) = clip(region, image) else {
+        return;
+    };
+
+    let small = imageops::resize(
+        &imageops::crop_imm(&*image, x, y, width, height).to_image(),
+        width.div_ceil(BLUR_BLOCK_SIZE),
+        height.div_ceil(BLUR_BLOCK_SIZE),
+        FilterType::Triangle,
+    );
+    let blurred = imageops::resize(&small, width, height, FilterType::Triangle);
+    imageops::replace(image, &blurred, x as i64, y as i64);
+}
+
+fn fill_region(image: &mut RgbaImage, region: &Region, color: Rgba<u8>) {
+    let Some((x, y, width, height)) = clip(region, image) else {
+        return;
+    };
+
+    for py in y..y + height {
+        for px in x..x + width {
+            image.put_pixel(px, py, color);
+        }
+    }
+}
+
+/// Darkens the region, used as a backing behind text
+fn darken(image: &mut RgbaImage, region: &Region) {
+    let Some((x, y, width, height)) = clip(region, image) else {
+        return;
+    };
+
+    for py in y..y + height {
+        for px in x..x + width {
+            let pixel = image.get_pixel_mut(px, py);
+            for channel in pixel.0.iter_mut().take(3) {
+                *channel /= 3;
+            }
+        }
+    }
+}
+
+/// Paints every pixel inside the bounding box for which `inside` holds
+fn fill_where(
+    image: &mut RgbaImage,
+    (min_x, min_y, max_x, max_y): (f32, f32, f32, f32),
+    color: Rgba<u8>,
+    inside: impl Fn(f32, f32) -> bool,
+) {
+    let left = min_x.floor().max(0.0) as u32;
+    let top = min_y.floor().max(0.0) as u32;
+    let right = (max_x.ceil().max(0.0) as u32).min(image.width());
+    let bottom = (max_y.ceil().max(0.0) as u32).min(image.height());
+
+    for py in top..bottom {
+        for px in left..right {
+            if inside(px as f32 + 0.5, py as f32 + 0.5) {
+                image.put_pixel(px, py, color);
+            }
+        }
+    }
+}
+
+fn draw_line(image: &mut RgbaImage, from: Point, to: Point, width: f32, color: Rgba<u8>) {
+    let half = width / 2.0;
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length_sq = (dx * dx + dy * dy).max(f32::EPSILON);
+
+    let bounds = (
+        from.x.min(to.x) - half,
+        from.y.min(to.y) - half,
+        from.x.max(to.x) + half,
+        from.y.max(to.y) + half,
+    );
+    fill_where(image, bounds, color, |px, py| {
+        // Distance from the pixel to the closest point on the segment
+        let t = (((px - from.x) * dx + (py - from.y) * dy) / length_sq).clamp(0.0, 1.0);
+        let (cx, cy) = (from.x + t * dx, from.y + t * dy);
+        (px - cx).powi(2) + (py - cy).powi(2) <= half * half
+    });
+}
+
+fn draw_triangle(image: &mut RgbaImage, points: [Point; 3], color: Rgba<u8>) {
+    let [a, b, c] = points;
+    let edge =
+        |p: Point, q: Point, x: f32, y: f32| (q.x - p.x) * (y - p.y) - (q.y - p.y) * (x - p.x);
+
+    let bounds = (
+        a.x.min(b.x).min(c.x),
+        a.y.min(b.y).min(c.y),
+        a.x.max(b.x).max(c.x),
+        a.y.max(b.y).max(c.y),
+    );
+    fill_where(image, bounds, color, |x, y| {
+        let (d1, d2, d3) = (edge(a, b, x, y), edge(b, c, x, y), edge(c, a, x, y));
+        let has_negative = d1 < 0.0 || d2 < 0.0 || d3 < 0.0;
+        let has_positive = d1 > 0.0 || d2 > 0.0 || d3 > 0.0;
+        !(has_negative && has_positive)
+    });
+}
+
+fn draw_arrow(image: &mut RgbaImage, from: Point, to: Point, width: f32, color: Rgba<u8>) {
+    let (dx, dy) = (to.x - from.x, to.y - from.y);
+    let length = (dx * dx + dy * dy).sqrt();
+    if length < 1.0 {
+        return;
+    }
+
+    let (ux, uy) = (dx / length, dy / length);
+    let head_length = (width * 4.0).min(length);
+    let head_width = width * 2.5;
+    let base = Point {
+        x: to.x - ux * head_length,
+        y: to.y - uy * head_length,
+    };
+
+    draw_line(image, from, base, width, color);
+    draw_triangle(
+        image,
+        [
+            to,
+            Point {
+                x: base.x - uy * head_width,
+                y: base.y + ux * head_width,
+            },
+            Point {
+                x: base.x + uy * head_width,
+                y: base.y - ux * head_width,
+            },
+        ],
+        color,
+    );
+}
+
+fn draw_highlight(image: &mut RgbaImage, region: &Region, width: f32, color: Rgba<u8>) {
+    let (left, top) = (region.x as f32, region.y as f32);
+    let (right, bottom) = (left + region.width as f32, top + region.height as f32);
+    let corners = [
+        Point { x: left, y: top },
+        Point { x: right, y: top },
+        Point {
+            x: right,
+            y: bottom,
+        },
+        Point { x: left, y: bottom },
+    ];
+    for (start, end) in corners.iter().zip(corners.iter().cycle().skip(1)) {
+        draw_line(image, *start, *end, width, color);
+    }
+}
+
+/// Receives the pixels of rendered text and paints each one into the image as a block of
+/// `scale` pixels, starting at `origin`
+struct TextCanvas<'a> {
+    image: &'a mut RgbaImage,
+    origin: (i64, i64),
+    scale: u32,
+    color: Rgba<u8>,
+}
+
+impl OriginDimensions for TextCanvas<'_> {
+    fn size(&self) -> Size {
+        Size::new(
+            self.image.width() / self.scale,
+            self.image.height() / self.scale,
+        )
+    }
+}
+
+impl DrawTarget for TextCanvas<'_> {
+    type Color = BinaryColor;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(point, color) in pixels {
+            if color.is_off() {
+                continue;
+            }
+            let block = Region {
+                x: self
+                    .origin
+                    .0
+                    .saturating_add(point.x as i64 * self.scale as i64),
+                y: self
+                    .origin
+                    .1
+                    .saturating_add(point.y as i64 * self.scale as i64),
+                width: self.scale,
+                height: self.scale,
+            };
+            fill_region(self.image, &block, self.color);
+        }
+        Ok(())
+    }
+}
+
+/// Draws text with a built-in bitmap font scaled to `size`, on a dark backing so it stays
+/// readable on any background. Text taller than the image is drawn at the image's height.
+fn draw_text(image: &mut RgbaImage, position: Point, text: &str, size: u32, color: Rgba<u8>) {
+    let font = &FONT_8X13;
+    let scale = (size.min(image.height()) / font.character_size.height).max(1);
+    let advance = (font.character_size.width + font.character_spacing) * scale;
+    let line_height = font.character_size.height * scale;
+    let padding = scale * 2;
+    let style = MonoTextStyle::new(font, BinaryColor::On);
+
+    let origin_x = position.x.round() as i64;
+    let mut origin_y = position.y.round() as i64;
+    for line in text.lines() {
+        // Later lines can only be further down
+        if origin_y > image.height() as i64 {
+            break;
+        }
+        let columns = u32::try_from(line.chars().count()).unwrap_or(u32::MAX);
+
+        darken(
+            image,
+            &Region {
+                x: origin_x.saturating_sub(padding as i64),
+                y: origin_y.saturating_sub(padding as i64),
+                width: columns.saturating_mul(advance).saturating_add(padding * 2),
+                height: line_height + padding * 2,
+            },
+        );
+
+        let mut canvas = TextCanvas {
+            image,
+            origin: (origin_x, origin_y),
+            scale,
+            color,
+        };
+        // Characters the font lacks are drawn as `?`
+        let _ =
+            Text::with_baseline(line, GlyphPoint::zero(), style, Baseline::Top).draw(&mut canvas);
+
+        origin_y = origin_y.saturating_add((line_height + padding) as i64);
+    }
+}
+
+/// Applies the edits and writes the result as a new PNG next to the other captures.
+/// Redactions are applied before any annotation so nothing drawn can reveal what was hidden.
+/// A capture taken by the agent is deleted once processed so the unredacted image can not be
+/// attached by mistake; images the user picked from disk are left alone.
+pub fn apply_edits(source: &Path, edits: &[ScreenshotEdit]) -> Result<PathBuf, AgentError> {
+    let mut image = image::open(source)
+        .map_err(|e| AgentError::Io(format!("Failed to open {}: {}", source.display(), e)))?
+        .to_rgba8();
+
+    let (redactions, annotations): (Vec<&ScreenshotEdit>, Vec<&ScreenshotEdit>) =
+        edits.iter().partition(|edit| {
+            matches!(
+                edit,
+                ScreenshotEdit::Blur { .. } | ScreenshotEdit::BlackOut { .. }
+            )
+        });
+
+    for edit in redactions.into_iter().chain(annotations) {
+        match edit {
+            ScreenshotEdit::Blur { region } => blur(&mut image, region),
+            ScreenshotEdit::BlackOut { region } => {
+                fill_region(&mut image, region, Rgba([0, 0, 0, 255]))
+            }
+            ScreenshotEdit::Highlight {
+                region,
+                color,
+                line_width,
+            } => draw_highlight(
+                &mut image,
+                region,
+                line_width.unwrap_or(DEFAULT_LINE_WIDTH),
+                Rgba(color.unwrap_or(DEFAULT_COLOR)),
+            ),
+            ScreenshotEdit::Arrow {
+                from,
+                to,
+                color,
+                line_width,
+            } => draw_arrow(
+                &mut image,
+                *from,
+                *to,
+                line_width.unwrap_or(DEFAULT_LINE_WIDTH),
+                Rgba(color.unwrap_or(DEFAULT_COLOR)),
+            ),
+            ScreenshotEdit::Text {
+                position,
+                text,
+                color,
+                size,
+            } => draw_text(
+                &mut image,
+                *position,
+                text,
+                size.unwrap_or(DEFAULT_TEXT_SIZE),
+                Rgba(color.unwrap_or(DEFAULT_COLOR)),
+            ),
+        }
+    }
+
+    let dir = create_screenshot_dir()?;
+    let stem = source
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| String::from("screenshot"));
+    let target = dir.join(format!(
+        "{}_edited_{}.png",
+        stem.split("_edited_").next().unwrap_or(&stem),
+        chrono::Local::now().format("%H%M%S%3f")
+    ));
+    image
+        .save(&target)
+        .map_err(|e| AgentError::Io(format!("Failed to save edited screenshot: {}", e)))?;
+    info!(
+        "Applied {} edit(s) to {}, saved as {}",
+        edits.len(),
+        source.display(),
+        target.display()
+    );
+
+    if source.parent() == Some(dir.as_path()) {
+        if let Err(e) = std::fs::remove_file(source) {
+            warn!(
+                "Failed to remove unedited screenshot {}: {}",
+                source.display(),
+                e
+            );
+        }
+    }
+
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GREY: Rgba<u8> = Rgba([128, 128, 128, 255]);
+
+    /// One-pixel black and white checkerboard, the worst case for hiding detail
+    fn checkerboard() -> RgbaImage {
+        RgbaImage::from_fn(64, 64, |x, y| {
+            if (x + y) % 2 == 0 {
+                Rgba([255, 255, 255, 255])
+            } else {
+                Rgba([0, 0, 0, 255])
+            }
+        })
+    }
+
+    fn grey() -> RgbaImage {
+        RgbaImage::from_pixel(64, 64, GREY)
+    }
+
+    fn region(x: i64, y: i64, width: u32, height: u32) -> Region {
+        Region {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn contains(region: &Region, x: u32, y: u32) -> bool {
+        let (x, y) = (x as i64, y as i64);
+        x >= region.x
+            && y >= region.y
+            && x < region.x + region.width as i64
+            && y < region.y + region.height as i64
+    }
+
+    /// Coordinates of every pixel that differs between the two images
+    fn changed(before: &RgbaImage, after: &RgbaImage) -> Vec<(u32, u32)> {
+        before
+            .enumerate_pixels()
+            .filter(|(x, y, pixel)| after.get_pixel(*x, *y) != *pixel)
+            .map(|(x, y, _)| (x, y))
+            .collect()
+    }
+
+    #[test]
+    fn blur_changes_every_pixel_inside_the_region_only() {
+        let before = checkerboard();
+        let mut after = before.clone();
+        let area = region(8, 16, 24, 36);
+        blur(&mut after, &area);
+
+        for (x, y, pixel) in before.enumerate_pixels() {
+            let inside = contains(&area, x, y);
+            assert_eq!(after.get_pixel(x, y) != pixel, inside, "({}, {})", x, y);
+        }
+    }
+
+    #[test]
+    fn black_out_fills_the_region_only() {
+        let before = checkerboard();
+        let mut after = before.clone();
+        let area = region(-4, 40, 20, 100);
+        fill_region(&mut after, &area, Rgba([0, 0, 0, 255]));
+
+        for (x, y, pixel) in after.enumerate_pixels() {
+            if contains(&area, x, y) {
+                assert_eq!(*pixel, Rgba([0, 0, 0, 255]), "({}, {})", x, y);
+            } else {
+                assert_eq!(pixel, before.get_pixel(x, y), "({}, {})", x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn highlight_outlines_the_region_and_leaves_its_inside_alone() {
+        let before = grey();
+        let mut after = before.clone();
+        let color = Rgba([0, 200, 0, 255]);
+        draw_highlight(&mut after, &region(16, 16, 32, 24), 4.0, color);
+
+        // Each edge, centered on the region's border
+        for (x, y) in [(30, 16), (30, 39), (16, 28), (47, 28), (15, 15), (17, 17)] {
+            assert_eq!(*after.get_pixel(x, y), color, "({}, {})", x, y);
+        }
+        // The line is 4 pixels wide, so 2 on either side of the border
+        for (x, y) in changed(&before, &after) {
+            let near_edge = |edge: u32, value: u32| value + 2 >= edge && value < edge + 2;
+            let on_border = (x + 2 >= 16 && x < 50 && (near_edge(16, y) || near_edge(40, y)))
+                || (y + 2 >= 16 && y < 42 && (near_edge(16, x) || near_edge(48, x)));
+            assert!(on_border, "({}, {}) is off the outline", x, y);
+        }
+        assert_eq!(*after.get_pixel(32, 28), GREY);
+    }
+
+    #[test]
+    fn arrow_draws_a_shaft_and_a_head_at_the_target() {
+        let before = grey();
+        let mut after = before.clone();
+        let color = Rgba([0, 0, 200, 255]);
+        draw_arrow(
+            &mut after,
+            Point { x: 8.0, y: 32.0 },
+            Point { x: 56.0, y: 32.0 },
+            4.0,
+            color,
+        );
+
+        // Shaft, then the head which is wider than the shaft, up to the tip
+        for (x, y) in [(10, 32), (30, 31), (42, 38), (42, 26), (54, 31)] {
+            assert_eq!(*after.get_pixel(x, y), color, "({}, {})", x, y);
+        }
+        // Only the shaft's width before the head
+        assert_eq!(*after.get_pixel(30, 36), GREY);
+        // Nothing behind the start or past the tip
+        for (x, y) in changed(&before, &after) {
+            assert!(
+                (6..=56).contains(&x) && (21..=43).contains(&y),
+                "({}, {})",
+                x,
+                y
+            );
+        }
+    }
+
+    #[test]
+    fn text_is_drawn_on_a_darkened_backing_at_its_position() {
+        let before = grey();
+        let mut after = before.clone();
+        let color = Rgba([255, 255, 0, 255]);
+        // 26 pixels tall is the 13 pixel font at twice the size, with 4 pixels of padding
+        draw_text(&mut after, Point { x: 10.0, y: 10.0 }, "Hi", 26, color);
+        let backing = region(6, 6, 2 * 16 + 8, 26 + 8);
+
+        let darkened = Rgba([128 / 3, 128 / 3, 128 / 3, 255]);
+        let mut glyph_pixels = 0;
+        for (x, y, pixel) in after.enumerate_pixels() {
+            if !contains(&backing, x, y) {
+                assert_eq!(*pixel, GREY, "({}, {})", x, y);
+            } else if *pixel == color {
+                glyph_pixels += 1;
+            } else {
+                assert_eq!(*pixel, darkened, "({}, {})", x, y);
+            }
+        }
+        assert!(glyph_pixels > 0);
+    }
+
+    #[test]
+    fn extreme_regions_are_clipped_without_overflow() {
+        let image = RgbaImage::new(100, 50);
+        let region = Region {
+            x: i64::MAX - 10,
+            y: 0,
+            width: u32::MAX,
+            height: u32::MAX,
+        };
+        assert_eq!(clip(&region, &image), None);
+
+        let region = Region {
+            x: -10,
+            y: -10,
+            width: u32::MAX,
+            height: 20,
+        };
+        assert_eq!(clip(&region, &image), Some((0, 0, 100, 10)));
+    }
+
+    #[test]
+    fn oversized_text_is_drawn_without_overflow() {
+        let mut image = RgbaImage::new(200, 100);
+        let long_line = "W".repeat(100_000);
+        let many_lines = "line\n".repeat(100_000);
+        for (text, size) in [
+            ("hello", u32::MAX),
+            (long_line.as_str(), 1_000_000),
+            (many_lines.as_str(), 24),
+        ] {
+            draw_text(
+                &mut image,
+                Point { x: 5.0, y: 5.0 },
+                text,
+                size,
+                Rgba(DEFAULT_COLOR),
+            );
+        }
+        draw_text(
+            &mut image,
+            Point {
+                x: f32::MAX,
+                y: f32::MIN,
+            },
+            "off the canvas",
+            24,
+            Rgba(DEFAULT_COLOR),
+        );
+
+        assert!(image.pixels().any(|pixel| *pixel == Rgba(DEFAULT_COLOR)));
+    }
+}
@@ -0,0 +1,606 @@
+use crate::device_manager::{get_config_dir, get_settings, Settings};
+use crate::error::AgentError;
+use crate::jobs::{job_endpoint, JobError, JobOutput};
+use base64::engine::general_purpose;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::time::{interval, timeout, Duration};
+
+const DEFAULT_TIMEOUT_SECS: u64 = 60 * 5;
+const MAX_TIMEOUT_SECS: u64 = 60 * 60;
+const MAX_SCRIPT_BYTES: usize = 5 * 1024 * 1024; // 5MB
+const MAX_OUTPUT_BYTES: usize = 1024 * 1024; // 1MB per stream
+const OUTPUT_FLUSH_INTERVAL_SECS: u64 = 2;
+const OUTPUT_DRAIN_TIMEOUT_SECS: u64 = 5;
+
+// Feature toggle the server sets to allow script jobs on a device, off unless enabled
+const RUN_SCRIPTS_FEATURE: &str = "run_scripts";
+
+/// Payload of a `run_script` job
+#[derive(Deserialize, Debug, Clone)]
+pub struct RunScriptPayload {
+    /// Absolute URL, or a path relative to `settings.api_host`
+    pub script_url: String,
+    /// Base64 detached ed25519 signature; fetched from `{script_url}.sig` when omitted
+    pub signature: Option<String>,
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Serialize, Debug)]
+struct OutputChunk {
+    stream: &'static str,
+    data: String,
+}
+
+/// Downloads, verifies and runs a signed script with the platform shell
+pub async fn run_script(job_id: &str, payload: RunScriptPayload) -> Result<JobOutput, JobError> {
+    let settings = get_settings()
+        .await
+        .map_err(|e| JobError::Failed(format!("Failed to load settings: {}", e)))?;
+    if !settings.is_feature_enabled(RUN_SCRIPTS_FEATURE) {
+        return Err(reject(
+            job_id,
+            format!("The {} feature is not enabled", RUN_SCRIPTS_FEATURE),
+        ));
+    }
+
+    let script_url = resolve_url(&settings, &payload.script_url);
+    let script = download(&settings, &script_url, MAX_SCRIPT_BYTES)
+        .await
+        .map_err(|e| JobError::Failed(format!("Failed to download script: {}", e)))?;
+
+    let signature = match payload.signature {
+        Some(signature) => signature,
+        None => {
+            let signature_url = format!("{}.sig", script_url);
+            let bytes = download(&settings, &signature_url, 1024)
+                .await
+                .map_err(|e| reject(job_id, format!("Failed to download signature: {}", e)))?;
+            String::from_utf8_lossy(&bytes).to_string()
+        }
+    };
+
+    verify_signature(&settings, &script, &signature).map_err(|e| reject(job_id, e.to_string()))?;
+
+    let script_path = write_script(job_id, &script)
+        .await
+        .map_err(|e| JobError::Failed(format!("Failed to write script: {}", e)))?;
+
+    let timeout_secs = payload
+        .timeout_secs
+        .unwrap_or(DEFAULT_TIMEOUT_SECS)
+        .clamp(1, MAX_TIMEOUT_SECS);
+    let result = execute(job_id, &script_path, Duration::from_secs(timeout_secs)).await;
+
+    let _ = tokio::fs::remove_file(&script_path).await;
+    result
+}
+
+fn reject(job_id: &str, reason: String) -> JobError {
+    error!("Rejected script for job {}: {}", job_id, reason);
+    JobError::Rejected(reason)
+}
+
+fn resolve_url(settings: &Settings, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!("{}{}", settings.api_host, url)
+    }
+}
+
+/// Whether `url` is on the configured API server, compared by scheme, host and port
+fn is_api_origin(settings: &Settings, url: &str) -> bool {
+    let api = reqwest::Url::parse(&settings.api_host);
+    match (reqwest::Url::parse(url), api) {
+        (Ok(url), Ok(api)) => url.origin() == api.origin(),
+        _ => false,
+    }
+}
+
+async fn download(
+    settings: &Settings,
+    url: &str,
+    max_bytes: usize,
+) -> Result<Vec<u8>, AgentError> {
+    let client = reqwest::Client::new();
+    let mut request = client.get(url);
+    // Scripts may be hosted elsewhere; only our own server gets to see who is asking
+    if is_api_origin(settings, url) {
+        request = request.header("x-site-id", &settings.site_id);
+        if let Some(device_id) = &settings.device_id {
+            request = request.header("x-device-id", device_id);
+        }
+    }
+
+    let mut response = request.send().await?;
+    if !response.status().is_success() {
+        return Err(AgentError::from_response(response).await);
+    }
+
+    let too_large = || AgentError::Network(format!("Download exceeds {} bytes", max_bytes));
+    if response
+        .content_length()
+        .is_some_and(|length| length > max_bytes as u64)
+    {
+        return Err(too_large());
+    }
+
+    // The length header is optional and can lie, so the cap is enforced while reading too
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        if bytes.len() + chunk.len() > max_bytes {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+
+    Ok(bytes)
+}
+
+/// Checks the detached signature against the public key pinned in settings
+fn verify_signature(settings: &Settings, script: &[u8], signature: &str) -> Result<(), AgentError> {
+    let key = settings.script_signing_key.as_ref().ok_or_else(|| {
+        AgentError::AccessDenied("No script signing key is pinned in settings".to_string())
+    })?;
+
+    let key_bytes: [u8; 32] = general_purpose::STANDARD
+        .decode(key.trim())
+        .map_err(|e| {
+            AgentError::SettingsCorrupt(format!("Pinned signing key is not valid base64: {}", e))
+        })?
+        .try_into()
+        .map_err(|_| {
+            AgentError::SettingsCorrupt("Pinned signing key must be 32 bytes".to_string())
+        })?;
+    let key = VerifyingKey::from_bytes(&key_bytes).map_err(|e| {
+        AgentError::SettingsCorrupt(format!("Pinned signing key is invalid: {}", e))
+    })?;
+
+    let signature_bytes = general_purpose::STANDARD
+        .decode(signature.trim())
+        .map_err(|e| AgentError::Parse(format!("Signature is not valid base64: {}", e)))?;
+    let signature = Signature::from_slice(&signature_bytes)
+        .map_err(|e| AgentError::Parse(format!("Signature is malformed: {}", e)))?;
+
+    key.verify_strict(script, &signature).map_err(|_| {
+        AgentError::AccessDenied("Signature does not match script contents".to_string())
+    })
+}
+
+async fn write_script(job_id: &str, script: &[u8]) -> Result<PathBuf, std::io::Error> {
+    let dir = get_config_dir().join("scripts");
+    tokio::fs::create_dir_all(&dir).await?;
+
+    // Job IDs come from the server, keep only filename-safe characters
+    let safe_id: String = job_id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '-' || *c == '_')
+        .collect();
+
+    #[cfg(target_os = "windows")]
+    let extension = "ps1";
+    #[cfg(not(target_os = "windows"))]
+    let extension = "sh";
+
+    let path = dir.join(format!("job_{}.{}", safe_id, extension));
+    tokio::fs::write(&path, script).await?;
+    Ok(path)
+}
+
+fn shell_command(script_path: &Path) -> Command {
+    #[cfg(target_os = "windows")]
+    {
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let mut command = Command::new("powershell.exe");
+        command
+            .args(["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-File"])
+            .arg(script_path)
+            .creation_flags(CREATE_NO_WINDOW);
+        command
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let shell = if Path::new("/bin/bash").exists() {
+            "/bin/bash"
+        } else {
+            "/bin/sh"
+        };
+        let mut command = Command::new(shell);
+        command.arg(script_path);
+        // Own process group so the whole tree can be killed on timeout
+        command.process_group(0);
+        command
+    }
+}
+
+/// Kills the process and everything it spawned
+fn kill_process_tree(pid: u32) {
+    #[cfg(target_os = "windows")]
+    {
+        use std::os::windows::process::CommandExt;
+        const CREATE_NO_WINDOW: u32 = 0x08000000;
+        let _ = std::process::Command::new("taskkill")
+            .args(["/T", "/F", "/PID", &pid.to_string()])
+            .creation_flags(CREATE_NO_WINDOW)
+            .output();
+    }
+
+    #[cfg(not(target_os = "windows"))]
+    {
+        let _ = std::process::Command::new("kill")
+            .args(["-KILL", "--", &format!("-{}", pid)])
+            .output();
+    }
+}
+
+async fn execute(
+    job_id: &str,
+    script_path: &Path,
+    limit: Duration,
+) -> Result<JobOutput, JobError> {
+    let mut child = shell_command(script_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| JobError::Failed(format!("Failed to start shell: {}", e)))?;
+
+    let pid = child.id();
+    let (tx, rx) = mpsc::unbounded_channel();
+    let uploader = tauri::async_runtime::spawn(stream_output(job_id.to_string(), rx));
+
+    let stdout_output = Arc::new(Mutex::new(CapturedOutput::default()));
+    let stderr_output = Arc::new(Mutex::new(CapturedOutput::default()));
+    let stdout = child.stdout.take().map(|out| {
+        tauri::async_runtime::spawn(capture(out, "stdout", tx.clone(), stdout_output.clone()))
+    });
+    let stderr = child.stderr.take().map(|err| {
+        tauri::async_runtime::spawn(capture(err, "stderr", tx.clone(), stderr_output.clone()))
+    });
+    drop(tx);
+
+    let status = match timeout(limit, child.wait()).await {
+        Ok(status) => {
+            Some(status.map_err(|e| JobError::Failed(format!("Failed to wait for shell: {}", e)))?)
+        }
+        Err(_) => {
+            warn!("Job {} exceeded {}s timeout, killing process tree", job_id, limit.as_secs());
+            if let Some(pid) = pid {
+                kill_process_tree(pid);
+            }
+            let _ = child.kill().await;
+            None
+        }
+    };
+
+    let stdout = collect(stdout, &stdout_output, "stdout").await;
+    let stderr = collect(stderr, &stderr_output, "stderr").await;
+    let _ = timeout(Duration::from_secs(OUTPUT_DRAIN_TIMEOUT_SECS), uploader).await;
+
+    match status {
+        Some(status) => Ok(JobOutput {
+            exit_code: status.code().unwrap_or(-1),
+            stdout,
+            stderr,
+        }),
+        None => Err(JobError::TimedOut(JobOutput {
+            exit_code: -1,
+            stdout,
+            stderr,
+        })),
+    }
+}
+
+/// Output kept from one stream. Shared with the task reading it, so whatever arrived is
+/// still there if the pipe never closes.
+#[derive(Default)]
+struct CapturedOutput {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+impl CapturedOutput {
+    fn to_output(&self) -> String {
+        let mut output = String::from_utf8_lossy(&self.bytes).to_string();
+        if self.truncated {
+            output.push_str(&format!("\n[output truncated at {} bytes]", MAX_OUTPUT_BYTES));
+        }
+        output
+    }
+}
+
+/// Waits for a stream to close and returns what was captured from it. A background
+/// process left running by the script can hold the pipe open indefinitely, so after
+/// `OUTPUT_DRAIN_TIMEOUT_SECS` the reader is stopped and the output so far is returned.
+async fn collect(
+    handle: Option<tauri::async_runtime::JoinHandle<()>>,
+    captured: &Mutex<CapturedOutput>,
+    stream: &str,
+) -> String {
+    if let Some(mut handle) = handle {
+        let drain = Duration::from_secs(OUTPUT_DRAIN_TIMEOUT_SECS);
+        if timeout(drain, &mut handle).await.is_err() {
+            warn!(
+                "{} was still open {}s after the script ended, keeping the output read so far",
+                stream, OUTPUT_DRAIN_TIMEOUT_SECS
+            );
+            handle.abort();
+        }
+    }
+
+    let captured = match captured.lock() {
+        Ok(captured) => captured,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    captured.to_output()
+}
+
+/// Reads a stream to the end, keeping at most `MAX_OUTPUT_BYTES` in `captured` and
+/// forwarding the kept bytes to the uploader as they arrive
+async fn capture<R: AsyncRead + Unpin>(
+    mut reader: R,
+    stream: &'static str,
+    tx: mpsc::UnboundedSender<OutputChunk>,
+    captured: Arc<Mutex<CapturedOutput>>,
+) {
+    let mut buf = [0u8; 8192];
+
+    loop {
+        let read = match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(read) => read,
+        };
+
+        let mut captured = match captured.lock() {
+            Ok(captured) => captured,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        let remaining = MAX_OUTPUT_BYTES.saturating_sub(captured.bytes.len());
+        if remaining == 0 {
+            captured.truncated = true;
+            continue;
+        }
+
+        let kept = &buf[..read.min(remaining)];
+        captured.truncated |= kept.len() < read;
+        captured.bytes.extend_from_slice(kept);
+        let _ = tx.send(OutputChunk {
+            stream,
+            data: String::from_utf8_lossy(kept).to_string(),
+        });
+    }
+}
+
+/// Uploads output chunks to the server while the script runs
+async fn stream_output(job_id: String, mut rx: mpsc::UnboundedReceiver<OutputChunk>) {
+    let mut pending: Vec<OutputChunk> = Vec::new();
+    let mut flush_interval = interval(Duration::from_secs(OUTPUT_FLUSH_INTERVAL_SECS));
+    let mut upload_failed = false;
+
+    loop {
+        tokio::select! {
+            chunk = rx.recv() => match chunk {
+                Some(chunk) => pending.push(chunk),
+                None => break,
+            },
+            _ = flush_interval.tick() => {
+                flush_output(&job_id, &mut pending, &mut upload_failed).await;
+            }
+        }
+    }
+
+    flush_output(&job_id, &mut pending, &mut upload_failed).await;
+}
+
+async fn flush_output(job_id: &str, pending: &mut Vec<OutputChunk>, upload_failed: &mut bool) {
+    if pending.is_empty() {
+        return;
+    }
+
+    let chunks = std::mem::take(pending);
+    if let Err(e) = post_output(job_id, &chunks).await {
+        // The final result still carries the captured output, so only log the first failure
+        if !*upload_failed {
+            warn!("Failed to stream output for job {}: {}", job_id, e);
+            *upload_failed = true;
+        }
+    }
+}
+
+async fn post_output(
+    job_id: &str,
+    chunks: &[OutputChunk],
+) -> Result<(), AgentError> {
+    let settings = get_settings().await?;
+    let device_id = settings
+        .device_id
+        .as_ref()
+        .ok_or(AgentError::NotRegistered)?;
+
+    let api_url = job_endpoint(job_id, "output").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("Content-Type", "application/json")
+        .header("x-device-id", device_id)
+        .header("x-site-id", &settings.site_id)
+        .json(&serde_json::json!({ "chunks": chunks }))
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        Ok(())
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+    use tokio::io::AsyncWriteExt;
+
+    fn settings(api_host: &str) -> Settings {
+        serde_json::from_value(serde_json::json!({
+            "site_id": "site-1",
+            "device_id": "device-1",
+            "api_host": api_host,
+            "installed_at": "2024-01-01T00:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    /// Answers every request on a local port with `response`, returning the base URL
+    fn serve(response: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut request = [0u8; 4096];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(&response);
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn identifies_only_the_api_origin() {
+        let settings = settings("https://api.example.com");
+        assert!(is_api_origin(
+            &settings,
+            &resolve_url(&settings, "/v1.0/scripts/1")
+        ));
+        assert!(is_api_origin(&settings, "https://api.example.com:443/a.sh"));
+        assert!(!is_api_origin(&settings, "https://cdn.example.com/a.sh"));
+        assert!(!is_api_origin(&settings, "http://api.example.com/a.sh"));
+        assert!(!is_api_origin(
+            &settings,
+            "https://api.example.com:8443/a.sh"
+        ));
+        assert!(!is_api_origin(
+            &settings,
+            "https://api.example.com.evil.io/a.sh"
+        ));
+    }
+
+    fn signed_settings(key: &SigningKey) -> Settings {
+        let mut settings = settings("https://api.example.com");
+        settings.script_signing_key =
+            Some(general_purpose::STANDARD.encode(key.verifying_key().to_bytes()));
+        settings
+    }
+
+    fn sign(key: &SigningKey, script: &[u8]) -> String {
+        general_purpose::STANDARD.encode(key.sign(script).to_bytes())
+    }
+
+    #[test]
+    fn accepts_a_script_signed_with_the_pinned_key() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let script = b"echo hello";
+        verify_signature(&signed_settings(&key), script, &sign(&key, script)).unwrap();
+    }
+
+    #[test]
+    fn rejects_unsigned_scripts() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let result = verify_signature(&signed_settings(&key), b"echo hello", "");
+        assert!(matches!(result, Err(AgentError::Parse(_))));
+
+        // Nothing to check against without a pinned key
+        let signature = sign(&key, b"echo hello");
+        let result = verify_signature(
+            &settings("https://api.example.com"),
+            b"echo hello",
+            &signature,
+        );
+        assert!(matches!(result, Err(AgentError::AccessDenied(_))));
+    }
+
+    #[test]
+    fn rejects_tampered_scripts() {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let signature = sign(&key, b"echo hello");
+        let result = verify_signature(&signed_settings(&key), b"echo hello; rm -rf ~", &signature);
+        assert!(matches!(result, Err(AgentError::AccessDenied(_))));
+    }
+
+    #[test]
+    fn rejects_scripts_signed_with_another_key() {
+        let pinned = SigningKey::from_bytes(&[7; 32]);
+        let other = SigningKey::from_bytes(&[9; 32]);
+        let script = b"echo hello";
+        let result = verify_signature(&signed_settings(&pinned), script, &sign(&other, script));
+        assert!(matches!(result, Err(AgentError::AccessDenied(_))));
+    }
+
+    #[tokio::test]
+    async fn download_rejects_declared_length_over_the_cap() {
+        let mut response =
+            b"HTTP/1.1 200 OK\r\nContent-Length: 4096\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(&[b'x'; 4096]);
+        let url = serve(response);
+
+        let result = download(&settings(&url), &format!("{}/a.sh", url), 1024).await;
+        assert!(matches!(result, Err(AgentError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn download_stops_reading_past_the_cap_without_a_length() {
+        let mut response = b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n".to_vec();
+        response.extend_from_slice(&[b'x'; 64 * 1024]);
+        let url = serve(response);
+
+        let result = download(&settings(&url), &format!("{}/a.sh", url), 1024).await;
+        assert!(matches!(result, Err(AgentError::Network(_))));
+    }
+
+    #[tokio::test]
+    async fn download_returns_bodies_within_the_cap() {
+        let url = serve(
+            b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\r\necho!".to_vec(),
+        );
+
+        let bytes = download(&settings(&url), &format!("{}/a.sh", url), 1024)
+            .await
+            .unwrap();
+        assert_eq!(bytes, b"echo!");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn collect_keeps_output_when_the_pipe_stays_open() {
+        let (reader, mut writer) = tokio::io::duplex(1024);
+        let (tx, _rx) = mpsc::unbounded_channel();
+        let captured = Arc::new(Mutex::new(CapturedOutput::default()));
+        let handle = tauri::async_runtime::spawn(capture(reader, "stdout", tx, captured.clone()));
+
+        writer.write_all(b"partial output").await.unwrap();
+        while captured.lock().unwrap().bytes.is_empty() {
+            tokio::task::yield_now().await;
+        }
+
+        // `writer` stays alive, like a background child still holding stdout
+        let output = collect(Some(handle), &captured, "stdout").await;
+        assert_eq!(output, "partial output");
+        drop(writer);
+    }
+}
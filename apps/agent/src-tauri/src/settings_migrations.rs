@@ -0,0 +1,108 @@
+use serde_json::{Map, Value};
+
+/// Version written by this build. Bump it and append to `MIGRATIONS` whenever
+/// the meaning or type of a stored field changes.
+pub const CURRENT_SCHEMA_VERSION: u64 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades a file from version `n` to `n + 1`.
+///
+/// - v0: unversioned file. Either the one the Windows or macOS installer writes
+///   (site_id, api_host, show_tray, installed_at), or the agent's own copy, which adds
+///   device_id, guid, hostname and registered_at and stores unset ones as `null`.
+/// - v1: show_tray is always a boolean.
+const MIGRATIONS: [Migration; 1] = [migrate_v0_to_v1];
+
+fn as_bool(value: &Value) -> Option<bool> {
+    match value {
+        Value::Bool(b) => Some(*b),
+        Value::Number(n) => Some(n.as_u64() == Some(1)),
+        Value::String(s) => match s.trim().to_lowercase().as_str() {
+            "true" | "1" | "yes" => Some(true),
+            "false" | "0" | "no" | "" => Some(false),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn migrate_v0_to_v1(settings: &mut Map<String, Value>) {
+    // The installers paste the `/visible=` argument in unquoted, so `/visible=1` ends up
+    // as a number; anything unreadable falls back to the default of hidden
+    if let Some(show_tray) = settings.get("show_tray").filter(|value| !value.is_null()) {
+        let show_tray = as_bool(show_tray).unwrap_or(false);
+        settings.insert("show_tray".to_string(), Value::Bool(show_tray));
+    }
+}
+
+/// Upgrades a raw settings document to the current schema in place.
+/// Returns true if anything was migrated. Files from a newer build are left untouched.
+pub fn migrate_settings(value: &mut Value) -> Result<bool, String> {
+    let settings = value
+        .as_object_mut()
+        .ok_or("Settings file is not a JSON object")?;
+
+    let version = settings
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+
+    if version >= CURRENT_SCHEMA_VERSION {
+        return Ok(false);
+    }
+
+    for migration in &MIGRATIONS[version as usize..] {
+        migration(settings);
+    }
+
+    settings.insert(
+        "schema_version".to_string(),
+        Value::from(CURRENT_SCHEMA_VERSION),
+    );
+
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn normalizes_show_tray_from_installer_arguments() {
+        for (raw, expected) in [
+            (json!(true), true),
+            (json!(1), true),
+            (json!("yes"), true),
+            (json!(0), false),
+            (json!("bogus"), false),
+        ] {
+            let mut settings = json!({ "site_id": "site", "show_tray": raw });
+            assert!(migrate_settings(&mut settings).unwrap());
+            assert_eq!(settings["show_tray"], json!(expected));
+            assert_eq!(settings["schema_version"], json!(CURRENT_SCHEMA_VERSION));
+        }
+    }
+
+    #[test]
+    fn leaves_unset_show_tray_alone() {
+        let mut missing = json!({ "site_id": "site" });
+        migrate_settings(&mut missing).unwrap();
+        assert!(missing.get("show_tray").is_none());
+
+        let mut null = json!({ "site_id": "site", "show_tray": null });
+        migrate_settings(&mut null).unwrap();
+        assert!(null["show_tray"].is_null());
+    }
+
+    #[test]
+    fn leaves_current_and_newer_files_untouched() {
+        let mut newer = json!({ "schema_version": CURRENT_SCHEMA_VERSION + 1, "show_tray": 1 });
+        let before = newer.clone();
+        assert!(!migrate_settings(&mut newer).unwrap());
+        assert_eq!(newer, before);
+
+        assert!(migrate_settings(&mut json!([])).is_err());
+    }
+}
@@ -0,0 +1,530 @@
+use crate::device_manager::{get_api_endpoint, get_config_dir, get_settings};
+use crate::error::AgentError;
+use log::info;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct InstalledSoftware {
+    pub name: String,
+    pub version: Option<String>,
+    pub publisher: Option<String>,
+    pub install_date: Option<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SoftwareUpgrade {
+    pub name: String,
+    pub publisher: Option<String>,
+    pub from_version: Option<String>,
+    pub to_version: Option<String>,
+}
+
+/// Changes since the last successful upload.
+/// `full` is set when there was no previous state and `added` holds the whole list.
+#[derive(Serialize, Debug, Default)]
+pub struct SoftwareDiff {
+    pub full: bool,
+    pub added: Vec<InstalledSoftware>,
+    pub removed: Vec<InstalledSoftware>,
+    pub upgraded: Vec<SoftwareUpgrade>,
+}
+
+impl SoftwareDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.upgraded.is_empty()
+    }
+}
+
+/// Last list reported to the server, kept in the config dir
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct SoftwareState {
+    hash: String,
+    software: Vec<InstalledSoftware>,
+}
+
+#[derive(Serialize, Debug)]
+struct SoftwareRequest {
+    guid: Option<String>,
+    hash: String,
+    #[serde(flatten)]
+    diff: SoftwareDiff,
+}
+
+fn get_state_path() -> PathBuf {
+    get_config_dir().join("software_state.json")
+}
+
+/// Stable hash of a sorted software list
+pub fn hash_software(software: &[InstalledSoftware]) -> String {
+    let mut hasher = Sha256::new();
+    for item in software {
+        hasher.update(item.name.as_bytes());
+        hasher.update([0]);
+        hasher.update(item.version.as_deref().unwrap_or("").as_bytes());
+        hasher.update([0]);
+        hasher.update(item.publisher.as_deref().unwrap_or("").as_bytes());
+        hasher.update([0]);
+        hasher.update(item.install_date.as_deref().unwrap_or("").as_bytes());
+        hasher.update([0xff]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+/// Groups entries by name and publisher, the identity of a package across versions
+fn by_identity(
+    software: &[InstalledSoftware],
+) -> BTreeMap<(&str, Option<&str>), Vec<&InstalledSoftware>> {
+    let mut grouped: BTreeMap<(&str, Option<&str>), Vec<&InstalledSoftware>> = BTreeMap::new();
+    for item in software {
+        grouped
+            .entry((item.name.as_str(), item.publisher.as_deref()))
+            .or_default()
+            .push(item);
+    }
+    grouped
+}
+
+/// Computes added/removed/upgraded entries, matching them by name and publisher. An entry
+/// whose version is unchanged is not reported even if its install date moved, and one that
+/// comes back with a different version counts as an upgrade.
+pub fn diff_software(old: &[InstalledSoftware], new: &[InstalledSoftware]) -> SoftwareDiff {
+    let mut old_by_identity = by_identity(old);
+    let mut diff = SoftwareDiff::default();
+
+    for (identity, mut current) in by_identity(new) {
+        let mut previous = old_by_identity.remove(&identity).unwrap_or_default();
+
+        // Several versions can be installed side by side; drop the ones still present
+        current.retain(|item| {
+            let unchanged = previous.iter().position(|old| old.version == item.version);
+            if let Some(index) = unchanged {
+                previous.remove(index);
+            }
+            unchanged.is_none()
+        });
+
+        let mut previous = previous.into_iter();
+        for item in current {
+            match previous.next() {
+                Some(old) => diff.upgraded.push(SoftwareUpgrade {
+                    name: item.name.clone(),
+                    publisher: item.publisher.clone(),
+                    from_version: old.version.clone(),
+                    to_version: item.version.clone(),
+                }),
+                None => diff.added.push(item.clone()),
+            }
+        }
+        diff.removed.extend(previous.cloned());
+    }
+
+    for (_, remaining) in old_by_identity {
+        diff.removed.extend(remaining.into_iter().cloned());
+    }
+
+    diff
+}
+
+/// Collects installed software for the current platform, sorted and deduplicated
+pub fn collect_installed_software() -> Vec<InstalledSoftware> {
+    #[cfg(target_os = "windows")]
+    let mut software = windows::collect();
+    #[cfg(target_os = "linux")]
+    let mut software = linux::collect();
+    #[cfg(target_os = "macos")]
+    let mut software = macos::collect();
+
+    software.sort();
+    software.dedup();
+    software
+}
+
+async fn load_state() -> Option<SoftwareState> {
+    let content = tokio::fs::read_to_string(get_state_path()).await.ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+async fn save_state(state: &SoftwareState) -> Result<(), AgentError> {
+    let content = serde_json::to_string(state)?;
+    tokio::fs::write(get_state_path(), content).await?;
+    Ok(())
+}
+
+/// Uploads software changes since the last report. Does nothing when the hash is unchanged.
+pub async fn send_software_inventory() -> Result<(), AgentError> {
+    let settings = get_settings().await?;
+
+    let device_id = settings
+        .device_id
+        .as_ref()
+        .ok_or(AgentError::NotRegistered)?;
+
+    let software = tokio::task::spawn_blocking(collect_installed_software).await?;
+    let hash = hash_software(&software);
+
+    let previous = load_state().await;
+    let diff = match &previous {
+        Some(state) if state.hash == hash => return Ok(()),
+        Some(state) => diff_software(&state.software, &software),
+        None => SoftwareDiff {
+            full: true,
+            added: software.clone(),
+            ..Default::default()
+        },
+    };
+
+    if !diff.full && diff.is_empty() {
+        // Only the ordering or hash inputs changed, just refresh the stored state
+        return save_state(&SoftwareState { hash, software }).await;
+    }
+
+    info!(
+        "Reporting software changes: {} added, {} removed, {} upgraded",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.upgraded.len()
+    );
+
+    let request = SoftwareRequest {
+        guid: settings.guid.clone(),
+        hash: hash.clone(),
+        diff,
+    };
+
+    let api_url = get_api_endpoint("/v1.0/software").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("Content-Type", "application/json")
+        .header("x-device-id", device_id)
+        .header("x-site-id", &settings.site_id)
+        .json(&request)
+        .send()
+        .await?;
+
+    let status = response.status();
+
+    if status.is_success() {
+        save_state(&SoftwareState { hash, software }).await
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+fn clean(value: &str) -> Option<String> {
+    let trimmed = value.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed.to_string())
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use super::*;
+    use winreg::enums::*;
+    use winreg::RegKey;
+
+    const UNINSTALL_PATH: &str = "SOFTWARE\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
+    const UNINSTALL_PATH_WOW64: &str =
+        "SOFTWARE\\WOW6432Node\\Microsoft\\Windows\\CurrentVersion\\Uninstall";
+
+    /// Converts the registry's `yyyymmdd` InstallDate to `yyyy-mm-dd`
+    fn format_install_date(value: &str) -> Option<String> {
+        let value = value.trim();
+        if value.len() == 8 && value.chars().all(|c| c.is_ascii_digit()) {
+            Some(format!("{}-{}-{}", &value[0..4], &value[4..6], &value[6..8]))
+        } else {
+            clean(value)
+        }
+    }
+
+    pub fn collect() -> Vec<InstalledSoftware> {
+        let mut software = Vec::new();
+
+        let sources = [
+            (RegKey::predef(HKEY_LOCAL_MACHINE), UNINSTALL_PATH),
+            (RegKey::predef(HKEY_LOCAL_MACHINE), UNINSTALL_PATH_WOW64),
+            (RegKey::predef(HKEY_CURRENT_USER), UNINSTALL_PATH),
+        ];
+
+        for (root, path) in sources {
+            let Ok(uninstall) = root.open_subkey(path) else {
+                continue;
+            };
+
+            for name in uninstall.enum_keys().flatten() {
+                let Ok(entry) = uninstall.open_subkey(&name) else {
+                    continue;
+                };
+
+                let Some(display_name) = entry
+                    .get_value::<String, _>("DisplayName")
+                    .ok()
+                    .and_then(|v| clean(&v))
+                else {
+                    continue;
+                };
+
+                // Skip hidden components and updates that belong to a parent product
+                if entry.get_value::<u32, _>("SystemComponent").unwrap_or(0) == 1 {
+                    continue;
+                }
+                if entry.get_value::<String, _>("ParentKeyName").is_ok() {
+                    continue;
+                }
+
+                software.push(InstalledSoftware {
+                    name: display_name,
+                    version: entry
+                        .get_value::<String, _>("DisplayVersion")
+                        .ok()
+                        .and_then(|v| clean(&v)),
+                    publisher: entry
+                        .get_value::<String, _>("Publisher")
+                        .ok()
+                        .and_then(|v| clean(&v)),
+                    install_date: entry
+                        .get_value::<String, _>("InstallDate")
+                        .ok()
+                        .and_then(|v| format_install_date(&v)),
+                });
+            }
+        }
+
+        software
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+    use std::process::Command;
+
+    const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+    const DPKG_INFO_DIR: &str = "/var/lib/dpkg/info";
+
+    pub fn collect() -> Vec<InstalledSoftware> {
+        if std::path::Path::new(DPKG_STATUS).exists() {
+            return collect_dpkg();
+        }
+        collect_rpm()
+    }
+
+    fn dpkg_install_date(package: &str, arch: Option<&str>) -> Option<String> {
+        let mut candidates = vec![format!("{}/{}.list", DPKG_INFO_DIR, package)];
+        if let Some(arch) = arch {
+            candidates.push(format!("{}/{}:{}.list", DPKG_INFO_DIR, package, arch));
+        }
+
+        candidates.iter().find_map(|path| {
+            let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+            let modified: chrono::DateTime<chrono::Utc> = modified.into();
+            Some(modified.format("%Y-%m-%d").to_string())
+        })
+    }
+
+    fn collect_dpkg() -> Vec<InstalledSoftware> {
+        let Ok(content) = std::fs::read_to_string(DPKG_STATUS) else {
+            return Vec::new();
+        };
+
+        let mut software = Vec::new();
+
+        for paragraph in content.split("\n\n") {
+            let mut package = None;
+            let mut version = None;
+            let mut maintainer = None;
+            let mut arch = None;
+            let mut installed = false;
+
+            for line in paragraph.lines() {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                match key {
+                    "Package" => package = clean(value),
+                    "Version" => version = clean(value),
+                    "Maintainer" => maintainer = clean(value),
+                    "Architecture" => arch = clean(value),
+                    "Status" => installed = value.trim().ends_with(" installed"),
+                    _ => {}
+                }
+            }
+
+            if let (Some(name), true) = (package, installed) {
+                software.push(InstalledSoftware {
+                    install_date: dpkg_install_date(&name, arch.as_deref()),
+                    name,
+                    version,
+                    publisher: maintainer,
+                });
+            }
+        }
+
+        software
+    }
+
+    fn collect_rpm() -> Vec<InstalledSoftware> {
+        let Ok(output) = Command::new("rpm")
+            .args([
+                "-qa",
+                "--queryformat",
+                "%{NAME}\t%{VERSION}-%{RELEASE}\t%{VENDOR}\t%{INSTALLTIME}\n",
+            ])
+            .output()
+        else {
+            return Vec::new();
+        };
+
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(|line| {
+                let mut parts = line.split('\t');
+                let name = clean(parts.next()?)?;
+                let version = parts.next().and_then(clean);
+                let publisher = parts.next().and_then(clean).filter(|v| v != "(none)");
+                let install_date = parts
+                    .next()
+                    .and_then(|v| v.trim().parse::<i64>().ok())
+                    .and_then(|secs| chrono::DateTime::from_timestamp(secs, 0))
+                    .map(|date| date.format("%Y-%m-%d").to_string());
+
+                Some(InstalledSoftware {
+                    name,
+                    version,
+                    publisher,
+                    install_date,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+
+    const APPLICATIONS_DIR: &str = "/Applications";
+
+    fn plist_string(dict: &plist::Dictionary, key: &str) -> Option<String> {
+        dict.get(key).and_then(|v| v.as_string()).and_then(clean)
+    }
+
+    pub fn collect() -> Vec<InstalledSoftware> {
+        let Ok(entries) = std::fs::read_dir(APPLICATIONS_DIR) else {
+            return Vec::new();
+        };
+
+        entries
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "app"))
+            .filter_map(|entry| {
+                let path = entry.path();
+                let info = plist::Value::from_file(path.join("Contents/Info.plist")).ok()?;
+                let dict = info.as_dictionary()?;
+
+                let name = plist_string(dict, "CFBundleDisplayName")
+                    .or_else(|| plist_string(dict, "CFBundleName"))
+                    .or_else(|| {
+                        path.file_stem()
+                            .map(|stem| stem.to_string_lossy().to_string())
+                    })?;
+
+                let install_date = entry.metadata().ok()?.modified().ok().map(|modified| {
+                    let modified: chrono::DateTime<chrono::Utc> = modified.into();
+                    modified.format("%Y-%m-%d").to_string()
+                });
+
+                Some(InstalledSoftware {
+                    name,
+                    version: plist_string(dict, "CFBundleShortVersionString")
+                        .or_else(|| plist_string(dict, "CFBundleVersion")),
+                    publisher: plist_string(dict, "CFBundleIdentifier"),
+                    install_date,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package(name: &str, version: &str, publisher: &str, date: &str) -> InstalledSoftware {
+        InstalledSoftware {
+            name: name.to_string(),
+            version: Some(version.to_string()),
+            publisher: Some(publisher.to_string()),
+            install_date: Some(date.to_string()),
+        }
+    }
+
+    #[test]
+    fn ignores_changes_to_install_date_alone() {
+        let old = vec![package("curl", "8.5.0", "Debian", "2024-01-01")];
+        let new = vec![package("curl", "8.5.0", "Debian", "2024-06-01")];
+
+        assert!(diff_software(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn reports_a_version_change_as_an_upgrade() {
+        let old = vec![package("curl", "8.5.0", "Debian", "2024-01-01")];
+        let new = vec![package("curl", "8.6.0", "Debian", "2024-06-01")];
+
+        let diff = diff_software(&old, &new);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.upgraded.len(), 1);
+        assert_eq!(diff.upgraded[0].from_version.as_deref(), Some("8.5.0"));
+        assert_eq!(diff.upgraded[0].to_version.as_deref(), Some("8.6.0"));
+    }
+
+    #[test]
+    fn treats_another_publisher_as_another_package() {
+        let old = vec![package("Agent", "1.0", "Vendor A", "2024-01-01")];
+        let new = vec![package("Agent", "2.0", "Vendor B", "2024-01-01")];
+
+        let diff = diff_software(&old, &new);
+        assert_eq!(diff.added, new);
+        assert_eq!(diff.removed, old);
+        assert!(diff.upgraded.is_empty());
+    }
+
+    #[test]
+    fn matches_side_by_side_versions_individually() {
+        let old = vec![
+            package("Python", "3.11.4", "PSF", "2024-01-01"),
+            package("Python", "3.12.0", "PSF", "2024-01-01"),
+        ];
+        let new = vec![
+            package("Python", "3.11.4", "PSF", "2024-03-01"),
+            package("Python", "3.12.1", "PSF", "2024-03-01"),
+            package("Python", "3.13.0", "PSF", "2024-03-01"),
+        ];
+
+        let diff = diff_software(&old, &new);
+        assert_eq!(diff.upgraded.len(), 1);
+        assert_eq!(diff.upgraded[0].from_version.as_deref(), Some("3.12.0"));
+        assert_eq!(diff.upgraded[0].to_version.as_deref(), Some("3.12.1"));
+        assert_eq!(diff.added, vec![new[2].clone()]);
+        assert!(diff.removed.is_empty());
+    }
+
+    #[test]
+    fn reports_plain_additions_and_removals() {
+        let old = vec![package("git", "2.43", "Debian", "2024-01-01")];
+        let new = vec![package("vim", "9.1", "Debian", "2024-01-01")];
+
+        let diff = diff_software(&old, &new);
+        assert_eq!(diff.added, new);
+        assert_eq!(diff.removed, old);
+        assert!(diff.upgraded.is_empty());
+    }
+}
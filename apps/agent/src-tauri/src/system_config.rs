@@ -0,0 +1,610 @@
+use crate::error::AgentError;
+use crate::registry::{Hive, RegistryAccess, RegistryValue};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+// Sources gated by `ConfigAccess`. The registry has its own allow-list and os-release
+// is readable by every user anyway.
+const ALLOW_LIST_SOURCES: [&str; 5] = ["systemd", "gsettings", "dconf", "sysctl", "defaults"];
+
+/// One configuration setting to look up. Each source only exists on some platforms;
+/// asking for one elsewhere fails with `PlatformUnsupported`.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(tag = "source", rename_all = "snake_case")]
+pub enum ConfigQuery {
+    /// Windows registry, subject to the registry allow-list
+    Registry {
+        hive: Option<Hive>,
+        path: String,
+        key: String,
+    },
+    /// A field of `/etc/os-release` on Linux, e.g. `VERSION_ID`
+    OsRelease { key: String },
+    /// A property of a systemd unit on Linux, e.g. `ActiveState` of `sshd.service`
+    SystemdUnit { unit: String, property: String },
+    /// A GSettings key on Linux, e.g. `org.gnome.desktop.screensaver` / `lock-enabled`
+    Gsettings { schema: String, key: String },
+    /// A dconf key path on Linux, e.g. `/org/gnome/desktop/session/idle-delay`
+    Dconf { key: String },
+    /// A kernel parameter on Linux or macOS, e.g. `net.ipv4.ip_forward`
+    Sysctl { name: String },
+    /// A preference from a macOS plist domain, e.g. `com.apple.screensaver` / `askForPassword`
+    Defaults { domain: String, key: String },
+}
+
+impl ConfigQuery {
+    fn source(&self) -> &'static str {
+        match self {
+            ConfigQuery::Registry { .. } => "Registry",
+            ConfigQuery::OsRelease { .. } => "os-release",
+            ConfigQuery::SystemdUnit { .. } => "systemd",
+            ConfigQuery::Gsettings { .. } => "GSettings",
+            ConfigQuery::Dconf { .. } => "dconf",
+            ConfigQuery::Sysctl { .. } => "sysctl",
+            ConfigQuery::Defaults { .. } => "Defaults",
+        }
+    }
+
+    /// The source and key this query is checked against in the allow-list. Keys are the
+    /// query's names joined with `/`, e.g. `sshd.service/ActiveState`.
+    fn allow_list_key(&self) -> Option<(&'static str, String)> {
+        match self {
+            ConfigQuery::Registry { .. } | ConfigQuery::OsRelease { .. } => None,
+            ConfigQuery::SystemdUnit { unit, property } => {
+                Some(("systemd", format!("{}/{}", unit, property)))
+            }
+            ConfigQuery::Gsettings { schema, key } => {
+                Some(("gsettings", format!("{}/{}", schema, key)))
+            }
+            ConfigQuery::Dconf { key } => Some(("dconf", key.clone())),
+            ConfigQuery::Sysctl { name } => Some(("sysctl", name.clone())),
+            ConfigQuery::Defaults { domain, key } => {
+                Some(("defaults", format!("{}/{}", domain, key)))
+            }
+        }
+    }
+}
+
+/// Configuration the webview may query outside the registry. Entries look like
+/// `sysctl:kernel.*` or `defaults:com.apple.screensaver/askForPassword`: a source, then a
+/// key that matches exactly or, ending in `*`, by prefix. Nothing is allowed until settings
+/// provide a list.
+#[derive(Clone, Default)]
+pub struct ConfigAccess {
+    allowed: Arc<RwLock<Vec<(&'static str, String)>>>,
+}
+
+impl ConfigAccess {
+    /// Replaces the allow-list. Entries with an unknown source are skipped.
+    pub fn set_allowed_keys(&self, entries: &[String]) {
+        let parsed = entries
+            .iter()
+            .filter_map(|entry| {
+                let source = entry.split_once(':').and_then(|(source, pattern)| {
+                    let source = ALLOW_LIST_SOURCES.iter().find(|known| **known == source)?;
+                    Some((*source, pattern.to_string()))
+                });
+                if source.is_none() {
+                    warn!(
+                        "Ignoring config allow-list entry with unknown source: {}",
+                        entry
+                    );
+                }
+                source
+            })
+            .collect();
+
+        let mut allowed = match self.allowed.write() {
+            Ok(allowed) => allowed,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        *allowed = parsed;
+    }
+
+    pub fn is_allowed(&self, source: &str, key: &str) -> bool {
+        let allowed = match self.allowed.read() {
+            Ok(allowed) => allowed,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        allowed.iter().any(|(allowed_source, pattern)| {
+            *allowed_source == source
+                && match pattern.strip_suffix('*') {
+                    Some(prefix) => key.starts_with(prefix),
+                    None => key == pattern,
+                }
+        })
+    }
+}
+
+/// A configuration value, sent to the frontend as `{ type, value }`
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ConfigValue {
+    String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    List(Vec<ConfigValue>),
+    Map(BTreeMap<String, ConfigValue>),
+    Binary(Vec<u8>),
+}
+
+impl From<RegistryValue> for ConfigValue {
+    fn from(value: RegistryValue) -> Self {
+        match value {
+            RegistryValue::String(value) | RegistryValue::ExpandString(value) => {
+                ConfigValue::String(value)
+            }
+            RegistryValue::Dword(value) => ConfigValue::Integer(value as i64),
+            RegistryValue::Qword(value) => i64::try_from(value)
+                .map(ConfigValue::Integer)
+                .unwrap_or_else(|_| ConfigValue::String(value.to_string())),
+            RegistryValue::MultiString(values) => {
+                ConfigValue::List(values.into_iter().map(ConfigValue::String).collect())
+            }
+            RegistryValue::Binary(bytes) => ConfigValue::Binary(bytes),
+        }
+    }
+}
+
+/// Guesses a type for plain text output, so `1` and `true` can be compared as such
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn typed(text: &str) -> ConfigValue {
+    let text = text.trim();
+    if let Ok(value) = text.parse::<i64>() {
+        return ConfigValue::Integer(value);
+    }
+    match text {
+        "true" | "yes" => ConfigValue::Bool(true),
+        "false" | "no" => ConfigValue::Bool(false),
+        _ => ConfigValue::String(text.to_string()),
+    }
+}
+
+/// Names passed to other programs or joined onto paths may only use these characters,
+/// which covers unit names, schemas, sysctl names and plist domains
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn validate_name(kind: &str, name: &str, extra: &[char]) -> Result<(), AgentError> {
+    let valid = !name.is_empty()
+        && !name.starts_with('-')
+        && !name.contains("..")
+        && name.chars().all(|c| {
+            c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-') || extra.contains(&c)
+        });
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::AccessDenied(format!(
+            "Invalid {} name: {}",
+            kind, name
+        )))
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn run(program: &str, args: &[&str]) -> Result<String, AgentError> {
+    let output = std::process::Command::new(program)
+        .args(args)
+        .output()
+        .map_err(|e| AgentError::Io(format!("Failed to run {}: {}", program, e)))?;
+    if !output.status.success() {
+        return Err(AgentError::Io(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use super::*;
+
+    /// Parses a `KEY=value` line of os-release, removing shell quoting
+    fn os_release_value(content: &str, key: &str) -> Option<String> {
+        content.lines().find_map(|line| {
+            let (name, value) = line.split_once('=')?;
+            (name.trim() == key).then(|| {
+                value
+                    .trim()
+                    .trim_matches('"')
+                    .trim_matches('\'')
+                    .to_string()
+            })
+        })
+    }
+
+    pub fn os_release(key: &str) -> Result<ConfigValue, AgentError> {
+        let content = std::fs::read_to_string("/etc/os-release")
+            .or_else(|_| std::fs::read_to_string("/usr/lib/os-release"))?;
+        os_release_value(&content, key)
+            .map(ConfigValue::String)
+            .ok_or_else(|| AgentError::Io(format!("{} is not set in os-release", key)))
+    }
+
+    pub fn systemd_unit(unit: &str, property: &str) -> Result<ConfigValue, AgentError> {
+        validate_name("unit", unit, &['@', ':'])?;
+        validate_name("property", property, &[])?;
+        let property = format!("--property={}", property);
+        run("systemctl", &["show", unit, &property, "--value"]).map(|value| typed(&value))
+    }
+
+    /// Converts the GVariant text format printed by gsettings and dconf. Scalars and
+    /// string arrays are understood; anything else comes back as the raw text.
+    fn parse_gvariant(text: &str) -> ConfigValue {
+        let text = text.trim();
+        // Typed scalars are printed with their type, e.g. `uint32 300`
+        let text = [
+            "uint32 ", "int32 ", "uint64 ", "int64 ", "byte ", "int16 ", "uint16 ",
+        ]
+        .iter()
+        .find_map(|prefix| text.strip_prefix(prefix))
+        .unwrap_or(text);
+
+        if let Some(inner) = text
+            .strip_prefix('[')
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            return ConfigValue::List(
+                inner
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|item| !item.is_empty())
+                    .map(parse_gvariant)
+                    .collect(),
+            );
+        }
+        if text.len() >= 2
+            && ((text.starts_with('\'') && text.ends_with('\''))
+                || (text.starts_with('"') && text.ends_with('"')))
+        {
+            return ConfigValue::String(text[1..text.len() - 1].to_string());
+        }
+        if let Ok(value) = text.parse::<f64>() {
+            if text.contains('.') {
+                return ConfigValue::Float(value);
+            }
+        }
+        typed(text)
+    }
+
+    pub fn gsettings(schema: &str, key: &str) -> Result<ConfigValue, AgentError> {
+        validate_name("schema", schema, &[])?;
+        validate_name("key", key, &[])?;
+        run("gsettings", &["get", schema, key]).map(|value| parse_gvariant(&value))
+    }
+
+    pub fn dconf(key: &str) -> Result<ConfigValue, AgentError> {
+        let path = key.strip_prefix('/').unwrap_or(key);
+        validate_name("dconf key", path, &['/'])?;
+        let value = run("dconf", &["read", key])?;
+        if value.is_empty() {
+            return Err(AgentError::Io(format!("{} is not set", key)));
+        }
+        Ok(parse_gvariant(&value))
+    }
+
+    /// Reads from /proc/sys directly, which needs no extra tools
+    pub fn sysctl(name: &str) -> Result<ConfigValue, AgentError> {
+        validate_name("sysctl", name, &[])?;
+        let path = std::path::Path::new("/proc/sys").join(name.replace('.', "/"));
+        let value = std::fs::read_to_string(&path)
+            .map_err(|e| AgentError::Io(format!("Failed to read sysctl {}: {}", name, e)))?;
+        Ok(typed(&value))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn reads_os_release_values() {
+            let content = "NAME=\"Ubuntu\"\nVERSION_ID='24.04'\nID=ubuntu\nID_LIKE=debian\n";
+            assert_eq!(os_release_value(content, "NAME").as_deref(), Some("Ubuntu"));
+            assert_eq!(
+                os_release_value(content, "VERSION_ID").as_deref(),
+                Some("24.04")
+            );
+            assert_eq!(os_release_value(content, "ID").as_deref(), Some("ubuntu"));
+            assert_eq!(os_release_value(content, "VERSION"), None);
+            assert_eq!(os_release_value("garbage\n", "ID"), None);
+        }
+
+        #[test]
+        fn parses_gvariant_text() {
+            assert_eq!(parse_gvariant("true"), ConfigValue::Bool(true));
+            assert_eq!(parse_gvariant("uint32 300"), ConfigValue::Integer(300));
+            assert_eq!(parse_gvariant("-5"), ConfigValue::Integer(-5));
+            assert_eq!(parse_gvariant("1.5"), ConfigValue::Float(1.5));
+            assert_eq!(
+                parse_gvariant("'Adwaita'"),
+                ConfigValue::String("Adwaita".to_string())
+            );
+            assert_eq!(
+                parse_gvariant("['ibus', 'xkb']"),
+                ConfigValue::List(vec![
+                    ConfigValue::String("ibus".to_string()),
+                    ConfigValue::String("xkb".to_string()),
+                ])
+            );
+            assert_eq!(
+                parse_gvariant("@as []"),
+                ConfigValue::String("@as []".to_string())
+            );
+            assert_eq!(parse_gvariant("[]"), ConfigValue::List(Vec::new()));
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::*;
+
+    fn from_plist(value: plist::Value) -> ConfigValue {
+        match value {
+            plist::Value::String(value) => ConfigValue::String(value),
+            plist::Value::Boolean(value) => ConfigValue::Bool(value),
+            plist::Value::Integer(value) => value
+                .as_signed()
+                .map(ConfigValue::Integer)
+                .unwrap_or_else(|| ConfigValue::String(value.to_string())),
+            plist::Value::Real(value) => ConfigValue::Float(value),
+            plist::Value::Data(bytes) => ConfigValue::Binary(bytes),
+            plist::Value::Date(date) => ConfigValue::String(date.to_xml_format()),
+            plist::Value::Array(values) => {
+                ConfigValue::List(values.into_iter().map(from_plist).collect())
+            }
+            plist::Value::Dictionary(dict) => ConfigValue::Map(
+                dict.into_iter()
+                    .map(|(key, value)| (key, from_plist(value)))
+                    .collect(),
+            ),
+            other => ConfigValue::String(format!("{:?}", other)),
+        }
+    }
+
+    /// Where `defaults` looks for a domain's preferences, most specific first: profiles
+    /// managed for `user`, then for the whole device, then the user's and system-wide ones
+    fn preference_paths(
+        file_name: &str,
+        user: Option<&str>,
+        home: Option<&std::path::Path>,
+    ) -> Vec<std::path::PathBuf> {
+        let managed = std::path::Path::new("/Library/Managed Preferences");
+        let mut candidates = Vec::new();
+        if let Some(user) =
+            user.filter(|user| !matches!(*user, "" | "." | "..") && !user.contains('/'))
+        {
+            candidates.push(managed.join(user).join(file_name));
+        }
+        candidates.push(managed.join(file_name));
+        if let Some(home) = home {
+            candidates.push(home.join("Library/Preferences").join(file_name));
+        }
+        candidates.push(std::path::Path::new("/Library/Preferences").join(file_name));
+        candidates
+    }
+
+    /// Looks the key up in the same places `defaults` does, managed profiles first
+    pub fn defaults(domain: &str, key: &str) -> Result<ConfigValue, AgentError> {
+        validate_name("domain", domain, &[])?;
+
+        let user = std::env::var("USER").ok();
+        let home = std::env::var_os("HOME").map(std::path::PathBuf::from);
+        let candidates = preference_paths(
+            &format!("{}.plist", domain),
+            user.as_deref(),
+            home.as_deref(),
+        );
+
+        for path in candidates {
+            let Ok(plist::Value::Dictionary(dict)) = plist::Value::from_file(&path) else {
+                continue;
+            };
+            if let Some(value) = dict.get(key) {
+                return Ok(from_plist(value.clone()));
+            }
+        }
+        Err(AgentError::Io(format!("{} is not set in {}", key, domain)))
+    }
+
+    pub fn sysctl(name: &str) -> Result<ConfigValue, AgentError> {
+        validate_name("sysctl", name, &[])?;
+        run("sysctl", &["-n", name]).map(|value| typed(&value))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn converts_plist_values() {
+            assert_eq!(
+                from_plist(plist::Value::Boolean(true)),
+                ConfigValue::Bool(true)
+            );
+            assert_eq!(
+                from_plist(plist::Value::Integer(5i64.into())),
+                ConfigValue::Integer(5)
+            );
+            assert_eq!(
+                from_plist(plist::Value::Integer(u64::MAX.into())),
+                ConfigValue::String(u64::MAX.to_string())
+            );
+            assert_eq!(
+                from_plist(plist::Value::Data(vec![1, 2])),
+                ConfigValue::Binary(vec![1, 2])
+            );
+
+            let mut dict = plist::Dictionary::new();
+            dict.insert(
+                "askForPassword".to_string(),
+                plist::Value::Array(vec![plist::Value::Real(0.5)]),
+            );
+            assert_eq!(
+                from_plist(plist::Value::Dictionary(dict)),
+                ConfigValue::Map(BTreeMap::from([(
+                    "askForPassword".to_string(),
+                    ConfigValue::List(vec![ConfigValue::Float(0.5)]),
+                )]))
+            );
+        }
+
+        #[test]
+        fn checks_managed_preferences_first() {
+            let paths = preference_paths(
+                "com.apple.screensaver.plist",
+                Some("alex"),
+                Some(std::path::Path::new("/Users/alex")),
+            );
+            assert_eq!(
+                paths,
+                [
+                    "/Library/Managed Preferences/alex/com.apple.screensaver.plist",
+                    "/Library/Managed Preferences/com.apple.screensaver.plist",
+                    "/Users/alex/Library/Preferences/com.apple.screensaver.plist",
+                    "/Library/Preferences/com.apple.screensaver.plist",
+                ]
+                .map(std::path::PathBuf::from)
+            );
+
+            let paths = preference_paths("com.apple.screensaver.plist", Some(".."), None);
+            assert_eq!(
+                paths,
+                [
+                    "/Library/Managed Preferences/com.apple.screensaver.plist",
+                    "/Library/Preferences/com.apple.screensaver.plist",
+                ]
+                .map(std::path::PathBuf::from)
+            );
+        }
+    }
+}
+
+/// Answers a configuration query from whatever backs that source on this platform, if the
+/// allow-list for its source permits it. Blocking; run it off the async runtime.
+pub fn query(
+    registry: &RegistryAccess,
+    access: &ConfigAccess,
+    query: &ConfigQuery,
+) -> Result<ConfigValue, AgentError> {
+    if let Some((source, key)) = query.allow_list_key() {
+        if !access.is_allowed(source, &key) {
+            return Err(AgentError::AccessDenied(format!("{}:{}", source, key)));
+        }
+    }
+
+    match query {
+        ConfigQuery::Registry { hive, path, key } => registry
+            .read(hive.unwrap_or(Hive::Hklm), path, key)
+            .map(ConfigValue::from),
+
+        #[cfg(target_os = "linux")]
+        ConfigQuery::OsRelease { key } => linux::os_release(key),
+        #[cfg(target_os = "linux")]
+        ConfigQuery::SystemdUnit { unit, property } => linux::systemd_unit(unit, property),
+        #[cfg(target_os = "linux")]
+        ConfigQuery::Gsettings { schema, key } => linux::gsettings(schema, key),
+        #[cfg(target_os = "linux")]
+        ConfigQuery::Dconf { key } => linux::dconf(key),
+        #[cfg(target_os = "linux")]
+        ConfigQuery::Sysctl { name } => linux::sysctl(name),
+
+        #[cfg(target_os = "macos")]
+        ConfigQuery::Defaults { domain, key } => macos::defaults(domain, key),
+        #[cfg(target_os = "macos")]
+        ConfigQuery::Sysctl { name } => macos::sysctl(name),
+
+        #[allow(unreachable_patterns)]
+        other => Err(AgentError::PlatformUnsupported(other.source())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn access(entries: &[&str]) -> ConfigAccess {
+        let access = ConfigAccess::default();
+        access.set_allowed_keys(
+            &entries
+                .iter()
+                .map(|entry| entry.to_string())
+                .collect::<Vec<_>>(),
+        );
+        access
+    }
+
+    #[test]
+    fn empty_allow_list_denies_everything() {
+        let access = access(&[]);
+        assert!(!access.is_allowed("sysctl", "kernel.hostname"));
+        let query = ConfigQuery::Sysctl {
+            name: "kernel.hostname".to_string(),
+        };
+        assert!(matches!(
+            super::query(&RegistryAccess::default(), &access, &query),
+            Err(AgentError::AccessDenied(_))
+        ));
+    }
+
+    #[test]
+    fn matches_exact_keys_and_prefixes() {
+        let access = access(&[
+            "sysctl:kernel.*",
+            "defaults:com.apple.*",
+            "systemd:sshd.service/ActiveState",
+            "dconf:/org/gnome/desktop/*",
+        ]);
+        assert!(access.is_allowed("sysctl", "kernel.hostname"));
+        assert!(!access.is_allowed("sysctl", "net.ipv4.ip_forward"));
+        assert!(access.is_allowed("defaults", "com.apple.screensaver/askForPassword"));
+        assert!(!access.is_allowed("defaults", "com.vendor.agent/token"));
+        assert!(access.is_allowed("systemd", "sshd.service/ActiveState"));
+        assert!(!access.is_allowed("systemd", "sshd.service/Environment"));
+        assert!(access.is_allowed("dconf", "/org/gnome/desktop/session/idle-delay"));
+        // The entry's source has to match too
+        assert!(!access.is_allowed("gsettings", "kernel.hostname"));
+    }
+
+    #[test]
+    fn keeps_qwords_beyond_i64_as_text() {
+        assert_eq!(
+            ConfigValue::from(RegistryValue::Qword(i64::MAX as u64)),
+            ConfigValue::Integer(i64::MAX)
+        );
+        assert_eq!(
+            ConfigValue::from(RegistryValue::Qword(u64::MAX)),
+            ConfigValue::String(u64::MAX.to_string())
+        );
+    }
+
+    #[test]
+    fn skips_entries_with_an_unknown_source() {
+        let access = access(&["registry:HKLM\\SOFTWARE", "kernel.*", "sysctl:vm.*"]);
+        assert!(access.is_allowed("sysctl", "vm.swappiness"));
+        assert!(!access.is_allowed("sysctl", "kernel.hostname"));
+    }
+
+    #[test]
+    fn builds_allow_list_keys_from_queries() {
+        let query = ConfigQuery::Gsettings {
+            schema: "org.gnome.desktop.screensaver".to_string(),
+            key: "lock-enabled".to_string(),
+        };
+        assert_eq!(
+            query.allow_list_key(),
+            Some((
+                "gsettings",
+                "org.gnome.desktop.screensaver/lock-enabled".to_string()
+            ))
+        );
+        let query = ConfigQuery::OsRelease {
+            key: "VERSION_ID".to_string(),
+        };
+        assert_eq!(query.allow_list_key(), None);
+    }
+}
@@ -0,0 +1,449 @@
+use crate::device_manager::{get_api_endpoint, get_settings, get_user_data_dir, write_file_atomic};
+use crate::device_registration::backoff_delay;
+use crate::error::AgentError;
+use crate::log_bundle::upload_log_bundle;
+use crate::rmm::{detect_rmm_agents, RmmAgent};
+#[cfg(unix)]
+use crate::screenshot::create_private_dir;
+use crate::screenshot::{prepare_for_upload, UploadImageOptions};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{Mutex, Notify};
+use tokio::time::Duration;
+
+const OUTBOX_POLL_SECS: u64 = 60;
+const RETRY_BASE_DELAY_SECS: u64 = 30;
+const RETRY_MAX_DELAY_SECS: u64 = 60 * 60; // 1 hour
+
+/// Ticket fields collected by the support form
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct TicketRequest {
+    pub summary: String,
+    pub description: Option<String>,
+    pub impact: String,
+    pub urgency: String,
+    pub name: String,
+    pub email: String,
+    pub phone: String,
+    pub screenshot_path: Option<String>,
+    /// Token of an image picked in a dialog, attached instead of `screenshot_path`
+    #[serde(default, skip_serializing)]
+    pub screenshot_token: Option<String>,
+    #[serde(default = "default_include_logs")]
+    pub include_logs: bool,
+}
+
+fn default_include_logs() -> bool {
+    true
+}
+
+/// Where a ticket is in the outbox
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum TicketStatus {
+    /// Waiting for the next attempt, e.g. because the network is down
+    Queued,
+    Sending,
+    Submitted {
+        ticket_id: String,
+    },
+    /// The server refused the ticket, retrying would not help
+    Rejected {
+        error: String,
+    },
+}
+
+/// A ticket persisted in the outbox until the server accepts it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct QueuedTicket {
+    pub id: String,
+    pub request: TicketRequest,
+    pub status: TicketStatus,
+    pub rmm_agents: Vec<RmmAgent>,
+    pub log_bundle_id: Option<String>,
+    pub attempts: u32,
+    pub last_error: Option<String>,
+    pub created_at: String,
+    pub next_attempt_at: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TicketResponse {
+    data: String,
+}
+
+/// Handle to the outbox, stored in app state. Sending is serialized so the
+/// background retry and a fresh submission never post the same ticket twice; the outbox
+/// is per user, so no other process drains it.
+#[derive(Clone, Default)]
+pub struct TicketOutbox {
+    sending: Arc<Mutex<()>>,
+    wake: Arc<Notify>,
+    running: Arc<AtomicBool>,
+}
+
+/// Private to the user: queued tickets hold their contact details and screenshots
+fn get_outbox_dir() -> PathBuf {
+    get_user_data_dir().join("outbox")
+}
+
+fn create_outbox_dir() -> Result<PathBuf, AgentError> {
+    let data_dir = get_user_data_dir();
+    if let Some(parent) = data_dir.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    #[cfg(unix)]
+    create_private_dir(&data_dir)?;
+    let dir = get_outbox_dir();
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+fn ticket_path(id: &str) -> PathBuf {
+    get_outbox_dir().join(format!("{}.json", id))
+}
+
+fn screenshot_mime(path: &Path) -> &'static str {
+    match path.extension().and_then(|ext| ext.to_str()) {
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("webp") => "image/webp",
+        _ => "image/png",
+    }
+}
+
+async fn save_ticket(ticket: &QueuedTicket) -> Result<(), AgentError> {
+    create_outbox_dir()?;
+    let content = serde_json::to_string_pretty(ticket)?;
+    write_file_atomic(&ticket_path(&ticket.id), content.as_bytes()).await?;
+    Ok(())
+}
+
+/// The outbox's current copy of a ticket, `None` once it has been removed
+async fn load_ticket(id: &str) -> Option<QueuedTicket> {
+    let content = tokio::fs::read_to_string(ticket_path(id)).await.ok()?;
+    match serde_json::from_str(&content) {
+        Ok(ticket) => Some(ticket),
+        Err(e) => {
+            warn!("Skipping unreadable outbox entry {}: {}", id, e);
+            None
+        }
+    }
+}
+
+async fn remove_ticket(ticket: &QueuedTicket) {
+    let _ = tokio::fs::remove_file(ticket_path(&ticket.id)).await;
+    if let Some(path) = &ticket.request.screenshot_path {
+        let _ = tokio::fs::remove_file(path).await;
+    }
+}
+
+/// Every ticket still in the outbox, oldest first
+pub async fn list_queued_tickets() -> Result<Vec<QueuedTicket>, AgentError> {
+    let mut tickets = Vec::new();
+    let mut entries = match tokio::fs::read_dir(get_outbox_dir()).await {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(tickets),
+        Err(e) => return Err(e.into()),
+    };
+
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let parsed = tokio::fs::read_to_string(&path)
+            .await
+            .map_err(AgentError::from)
+            .and_then(|content| Ok(serde_json::from_str::<QueuedTicket>(&content)?));
+        match parsed {
+            Ok(ticket) => tickets.push(ticket),
+            Err(e) => warn!("Skipping unreadable outbox entry {}: {}", path.display(), e),
+        }
+    }
+
+    tickets.sort_by(|a, b| a.created_at.cmp(&b.created_at));
+    Ok(tickets)
+}
+
+/// Removes a rejected ticket from the outbox once the user has seen why it failed
+pub async fn dismiss_ticket(id: &str) -> Result<(), AgentError> {
+    // Look the ticket up rather than joining `id` onto the outbox path
+    let ticket = list_queued_tickets()
+        .await?
+        .into_iter()
+        .find(|ticket| ticket.id == id)
+        .ok_or_else(|| AgentError::Io(format!("Ticket {} is not in the outbox", id)))?;
+    remove_ticket(&ticket).await;
+    info!("Ticket {} dismissed from the outbox", id);
+    Ok(())
+}
+
+fn publish_status(app: &AppHandle, ticket: &QueuedTicket) {
+    let _ = app.emit("ticket_status", ticket);
+}
+
+/// Builds and posts the multipart ticket request. Returns the server's ticket ID.
+async fn post_ticket(ticket: &QueuedTicket) -> Result<String, AgentError> {
+    let settings = get_settings().await?;
+    let device_id = settings
+        .device_id
+        .as_ref()
+        .ok_or(AgentError::NotRegistered)?;
+    let request = &ticket.request;
+
+    let mut form = reqwest::multipart::Form::new()
+        .text("summary", request.summary.clone())
+        .text(
+            "description",
+            request.description.clone().unwrap_or_default(),
+        )
+        .text("impact", request.impact.clone())
+        .text("urgency", request.urgency.clone())
+        .text("name", request.name.clone())
+        .text("email", request.email.clone())
+        .text(
+            "phone",
+            request
+                .phone
+                .chars()
+                .filter(|c| c.is_ascii_digit())
+                .collect::<String>(),
+        );
+
+    if let Some(rmm) = ticket.rmm_agents.first() {
+        form = form.text("rmm_id", rmm.device_id.clone());
+        form = form.text("rmm_agents", serde_json::to_string(&ticket.rmm_agents)?);
+    }
+    if let Some(log_bundle_id) = &ticket.log_bundle_id {
+        form = form.text("log_bundle_id", log_bundle_id.clone());
+    }
+
+    if let Some(path) = &request.screenshot_path {
+        match tokio::fs::read(path).await {
+            Ok(bytes) => {
+                let path = Path::new(path);
+                let file_name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "screenshot.png".to_string());
+                let part = reqwest::multipart::Part::bytes(bytes)
+                    .file_name(file_name)
+                    .mime_str(screenshot_mime(path))?;
+                form = form.part("screenshot", part);
+            }
+            Err(e) => warn!("Sending ticket {} without its screenshot: {}", ticket.id, e),
+        }
+    }
+
+    let api_url = get_api_endpoint("/v1.0/ticket/create").await?;
+
+    let client = reqwest::Client::new();
+    let response = client
+        .post(&api_url)
+        .header("x-device-id", device_id)
+        .header("x-site-id", &settings.site_id)
+        .multipart(form)
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        let result: TicketResponse = response.json().await?;
+        Ok(result.data)
+    } else {
+        Err(AgentError::from_response(response).await)
+    }
+}
+
+/// Client errors mean the ticket itself is bad; everything else is worth retrying
+fn is_permanent(error: &AgentError) -> bool {
+    match error {
+        AgentError::HttpStatus { status, .. } => {
+            (400..500).contains(status) && *status != 408 && *status != 429
+        }
+        _ => false,
+    }
+}
+
+/// Writes the upload copy of a screenshot into the outbox, returning its path
+async fn attach_screenshot(id: &str, source: String) -> Result<String, AgentError> {
+    let options = UploadImageOptions::from_settings(&get_settings().await?);
+    let target = create_outbox_dir()?.join(id);
+    let processed = tauri::async_runtime::spawn_blocking(move || {
+        prepare_for_upload(Path::new(&source), &target, &options)
+    })
+    .await??;
+    Ok(processed.to_string_lossy().to_string())
+}
+
+impl TicketOutbox {
+    /// Makes one delivery attempt and persists the outcome
+    async fn attempt(&self, app: &AppHandle, ticket: QueuedTicket) -> QueuedTicket {
+        let _sending = self.sending.lock().await;
+
+        // `ticket` may be stale: whoever held the lock before could have sent it already
+        let mut ticket = match load_ticket(&ticket.id).await {
+            Some(current) if current.status == TicketStatus::Queued => current,
+            Some(current) => return current,
+            None => return ticket,
+        };
+
+        ticket.status = TicketStatus::Sending;
+        ticket.attempts += 1;
+        publish_status(app, &ticket);
+
+        if ticket.request.include_logs && ticket.log_bundle_id.is_none() {
+            match upload_log_bundle("support_ticket").await {
+                Ok(id) => ticket.log_bundle_id = Some(id),
+                Err(e) => warn!("Ticket {} will be sent without logs: {}", ticket.id, e),
+            }
+        }
+
+        match post_ticket(&ticket).await {
+            Ok(ticket_id) => {
+                info!(event = "ticket_submitted"; "Ticket {} submitted as {}", ticket.id, ticket_id);
+                ticket.status = TicketStatus::Submitted { ticket_id };
+                ticket.last_error = None;
+                ticket.next_attempt_at = None;
+                remove_ticket(&ticket).await;
+            }
+            Err(e) if is_permanent(&e) => {
+                error!("Ticket {} was rejected: {}", ticket.id, e);
+                ticket.status = TicketStatus::Rejected {
+                    error: e.to_string(),
+                };
+                ticket.last_error = Some(e.to_string());
+                ticket.next_attempt_at = None;
+                if let Err(e) = save_ticket(&ticket).await {
+                    error!("Failed to update outbox entry {}: {}", ticket.id, e);
+                }
+            }
+            Err(e) => {
+                let delay =
+                    backoff_delay(ticket.attempts, RETRY_BASE_DELAY_SECS, RETRY_MAX_DELAY_SECS);
+                warn!(
+                    "Ticket {} queued, retrying in {}s: {}",
+                    ticket.id,
+                    delay.as_secs(),
+                    e
+                );
+                ticket.status = TicketStatus::Queued;
+                ticket.last_error = Some(e.to_string());
+                ticket.next_attempt_at = chrono::Duration::from_std(delay)
+                    .ok()
+                    .map(|delay| (chrono::Utc::now() + delay).to_rfc3339());
+                if let Err(e) = save_ticket(&ticket).await {
+                    error!("Failed to update outbox entry {}: {}", ticket.id, e);
+                }
+            }
+        }
+
+        publish_status(app, &ticket);
+        ticket
+    }
+
+    /// Persists the ticket to the outbox, then tries to send it right away.
+    /// The ticket is never lost: if sending fails it stays queued for the background task.
+    pub async fn submit(
+        &self,
+        app: &AppHandle,
+        mut request: TicketRequest,
+    ) -> Result<QueuedTicket, AgentError> {
+        let id = format!(
+            "{}-{:08x}",
+            chrono::Utc::now().format("%Y%m%d%H%M%S"),
+            rand::random::<u32>()
+        );
+
+        let screenshot = request.screenshot_path.take();
+        let rmm_agents = tauri::async_runtime::spawn_blocking(detect_rmm_agents).await?;
+
+        let mut ticket = QueuedTicket {
+            id,
+            request,
+            status: TicketStatus::Queued,
+            rmm_agents,
+            log_bundle_id: None,
+            attempts: 0,
+            last_error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+            next_attempt_at: None,
+        };
+
+        {
+            // Hold off the background task until the screenshot is attached
+            let _sending = self.sending.lock().await;
+            save_ticket(&ticket).await?;
+            info!("Ticket {} saved to the outbox", ticket.id);
+
+            // Keep a compressed private copy of the screenshot, the original may be a temp file
+            if let Some(source) = screenshot {
+                match attach_screenshot(&ticket.id, source).await {
+                    Ok(path) => {
+                        ticket.request.screenshot_path = Some(path);
+                        if let Err(e) = save_ticket(&ticket).await {
+                            error!("Failed to update outbox entry {}: {}", ticket.id, e);
+                        }
+                    }
+                    Err(e) => warn!(
+                        "Ticket {} will be sent without its screenshot: {}",
+                        ticket.id, e
+                    ),
+                }
+            }
+        }
+
+        Ok(self.attempt(app, ticket).await)
+    }
+
+    /// Wakes the background task so queued tickets are retried now, e.g. once registration completes
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// Sends every queued ticket whose backoff has elapsed
+    async fn flush(&self, app: &AppHandle) {
+        let tickets = match list_queued_tickets().await {
+            Ok(tickets) => tickets,
+            Err(e) => {
+                warn!("Failed to read the ticket outbox: {}", e);
+                return;
+            }
+        };
+
+        let now = chrono::Utc::now();
+        for ticket in tickets {
+            let next_attempt = ticket
+                .next_attempt_at
+                .as_deref()
+                .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok());
+            let due = !matches!(next_attempt, Some(at) if at > now);
+
+            if ticket.status == TicketStatus::Queued && due {
+                self.attempt(app, ticket).await;
+            }
+        }
+    }
+}
+
+/// Starts the background task that drains the outbox. Does nothing if it is already running.
+pub fn start_outbox_task(app: AppHandle, outbox: TicketOutbox) {
+    if outbox.running.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    tauri::async_runtime::spawn(async move {
+        loop {
+            outbox.flush(&app).await;
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(OUTBOX_POLL_SECS)) => {}
+                _ = outbox.wake.notified() => {}
+            }
+        }
+    });
+}
@@ -0,0 +1,246 @@
+import { PointerEvent, useRef, useState } from 'react';
+import { toast } from 'sonner';
+import { editScreenshot, FileRef, Point, Region, ScreenshotEdit } from '@/lib/file.ts';
+import { Button } from '@/ui/components/button';
+
+type Tool = 'blur' | 'black_out' | 'highlight' | 'arrow' | 'text';
+
+const TOOLS: { tool: Tool; label: string }[] = [
+  { tool: 'blur', label: 'Blur' },
+  { tool: 'black_out', label: 'Black Out' },
+  { tool: 'highlight', label: 'Box' },
+  { tool: 'arrow', label: 'Arrow' },
+  { tool: 'text', label: 'Text' },
+];
+
+// Matches the default color in screenshot_edit.rs so the preview looks like the result
+const ANNOTATION_COLOR = 'rgb(220, 38, 38)';
+const LINE_WIDTH = 4;
+const TEXT_SIZE = 24;
+
+function toRegion(from: Point, to: Point): Region {
+  return {
+    x: Math.round(Math.min(from.x, to.x)),
+    y: Math.round(Math.min(from.y, to.y)),
+    width: Math.round(Math.abs(to.x - from.x)),
+    height: Math.round(Math.abs(to.y - from.y)),
+  };
+}
+
+function EditShape({ edit }: { edit: ScreenshotEdit }) {
+  switch (edit.kind) {
+    case 'blur':
+      return <rect {...edit.region} fill="rgba(128, 128, 128, 0.8)" />;
+    case 'black_out':
+      return <rect {...edit.region} fill="black" />;
+    case 'highlight':
+      return (
+        <rect
+          {...edit.region}
+          fill="none"
+          stroke={ANNOTATION_COLOR}
+          strokeWidth={edit.line_width}
+        />
+      );
+    case 'arrow':
+      return (
+        <line
+          x1={edit.from.x}
+          y1={edit.from.y}
+          x2={edit.to.x}
+          y2={edit.to.y}
+          stroke={ANNOTATION_COLOR}
+          strokeWidth={edit.line_width}
+          markerEnd="url(#arrowhead)"
+        />
+      );
+    case 'text':
+      return (
+        <text
+          x={edit.position.x}
+          y={edit.position.y + (edit.size ?? TEXT_SIZE)}
+          fill={ANNOTATION_COLOR}
+          fontSize={edit.size}
+          fontFamily="monospace"
+        >
+          {edit.text}
+        </text>
+      );
+  }
+}
+
+/**
+ * Lets the user hide sensitive parts of a screenshot and mark it up before it is attached.
+ * Shapes are previewed in the browser; the agent renders the final image.
+ */
+export default function ScreenshotEditor({
+  file,
+  url,
+  onApply,
+  onCancel,
+}: {
+  file: FileRef;
+  url: string;
+  onApply: (path: string) => void;
+  onCancel: () => void;
+}) {
+  const [tool, setTool] = useState<Tool>('blur');
+  const [edits, setEdits] = useState<ScreenshotEdit[]>([]);
+  const [dragStart, setDragStart] = useState<Point>();
+  const [dragEnd, setDragEnd] = useState<Point>();
+  const [size, setSize] = useState<{ width: number; height: number }>();
+  const [isApplying, setIsApplying] = useState(false);
+  const svgRef = useRef<SVGSVGElement>(null);
+
+  // Keeps strokes a consistent on-screen thickness regardless of the capture resolution
+  const scale = size ? Math.max(size.width / 1600, 1) : 1;
+  const lineWidth = Math.round(LINE_WIDTH * scale);
+
+  // Converts a pointer position to image pixels
+  const toImagePoint = (e: PointerEvent<SVGSVGElement>): Point => {
+    const rect = svgRef.current!.getBoundingClientRect();
+    return {
+      x: ((e.clientX - rect.left) / rect.width) * (size?.width ?? rect.width),
+      y: ((e.clientY - rect.top) / rect.height) * (size?.height ?? rect.height),
+    };
+  };
+
+  const buildEdit = (from: Point, to: Point): ScreenshotEdit | undefined => {
+    if (tool === 'arrow') {
+      return { kind: 'arrow', from, to, line_width: lineWidth };
+    }
+    if (tool === 'text') {
+      return undefined;
+    }
+
+    const region = toRegion(from, to);
+    if (region.width < 2 || region.height < 2) return undefined;
+    return tool === 'highlight'
+      ? { kind: tool, region, line_width: lineWidth }
+      : { kind: tool, region };
+  };
+
+  const handlePointerDown = (e: PointerEvent<SVGSVGElement>) => {
+    const point = toImagePoint(e);
+
+    if (tool === 'text') {
+      const text = prompt('Text to add');
+      if (text) {
+        const textSize = Math.round(TEXT_SIZE * scale);
+        setEdits((current) => [...current, { kind: 'text', position: point, text, size: textSize }]);
+      }
+      return;
+    }
+
+    e.currentTarget.setPointerCapture(e.pointerId);
+    setDragStart(point);
+    setDragEnd(point);
+  };
+
+  const handlePointerMove = (e: PointerEvent<SVGSVGElement>) => {
+    if (dragStart) setDragEnd(toImagePoint(e));
+  };
+
+  const handlePointerUp = (e: PointerEvent<SVGSVGElement>) => {
+    if (!dragStart) return;
+
+    const edit = buildEdit(dragStart, toImagePoint(e));
+    if (edit) setEdits((current) => [...current, edit]);
+    setDragStart(undefined);
+    setDragEnd(undefined);
+  };
+
+  const handleApply = async () => {
+    setIsApplying(true);
+    const { data: editedPath } = await editScreenshot(file, edits);
+    setIsApplying(false);
+
+    if (!editedPath) {
+      toast.error('Failed to apply changes to the screenshot');
+      return;
+    }
+    onApply(editedPath);
+  };
+
+  const pending = dragStart && dragEnd ? buildEdit(dragStart, dragEnd) : undefined;
+
+  return (
+    <div className="flex flex-col gap-2 border rounded p-2">
+      <div className="flex flex-wrap gap-2">
+        {TOOLS.map(({ tool: value, label }) => (
+          <Button
+            key={value}
+            type="button"
+            size="sm"
+            variant={tool === value ? 'default' : 'outline'}
+            onClick={() => setTool(value)}
+            disabled={isApplying}
+          >
+            {label}
+          </Button>
+        ))}
+        <Button
+          type="button"
+          size="sm"
+          variant="ghost"
+          onClick={() => setEdits((current) => current.slice(0, -1))}
+          disabled={isApplying || edits.length === 0}
+        >
+          Undo
+        </Button>
+      </div>
+
+      <div className="relative w-fit max-h-80 overflow-auto">
+        <img
+          src={url}
+          alt="Screenshot to edit"
+          className="block max-w-full select-none"
+          draggable={false}
+          onLoad={(e) =>
+            setSize({
+              width: e.currentTarget.naturalWidth,
+              height: e.currentTarget.naturalHeight,
+            })
+          }
+        />
+        {size && (
+          <svg
+            ref={svgRef}
+            className="absolute inset-0 size-full cursor-crosshair touch-none"
+            viewBox={`0 0 ${size.width} ${size.height}`}
+            preserveAspectRatio="none"
+            onPointerDown={handlePointerDown}
+            onPointerMove={handlePointerMove}
+            onPointerUp={handlePointerUp}
+          >
+            <defs>
+              <marker
+                id="arrowhead"
+                markerWidth="4"
+                markerHeight="4"
+                refX="2"
+                refY="2"
+                orient="auto"
+              >
+                <path d="M0,0 L4,2 L0,4 Z" fill={ANNOTATION_COLOR} />
+              </marker>
+            </defs>
+            {edits.map((edit, index) => (
+              <EditShape key={index} edit={edit} />
+            ))}
+            {pending && <EditShape edit={pending} />}
+          </svg>
+        )}
+      </div>
+
+      <div className="flex gap-2">
+        <Button type="button" onClick={handleApply} disabled={isApplying || edits.length === 0}>
+          Apply
+        </Button>
+        <Button type="button" variant="outline" onClick={onCancel} disabled={isApplying}>
+          Cancel
+        </Button>
+      </div>
+    </div>
+  );
+}
@@ -1,19 +1,21 @@
-import { useEffect, useMemo, useState } from 'react';
+import { useEffect, useMemo, useRef, useState } from 'react';
 import z from 'zod';
 import { useForm } from 'react-hook-form';
 import { zodResolver } from '@hookform/resolvers/zod';
 import { toast } from 'sonner';
 import {
   chooseImageDialog,
+  getMonitors,
   readFileBase64,
+  releaseFile,
   takeScreenshot,
   logToFile,
-  readFileBinary,
+  FileRef,
+  MonitorInfo,
 } from '@/lib/file.ts';
 import { listen } from '@tauri-apps/api/event';
-import { fetch } from '@tauri-apps/plugin-http';
-import { getSettings, getRmmId } from '@/lib/agent.ts';
-import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import ScreenshotEditor from './ScreenshotEditor';
+import { dismissTicket, getQueuedTickets, submitTicket, QueuedTicket } from '@/lib/tickets.ts';
 import { hideWindow, showWindow } from '@/lib/window.ts';
 import {
   Form,
@@ -67,6 +69,18 @@ type FormSchema = z.infer<typeof formSchema>;
 
 export default function Support() {
   const [isSubmitting, setIsSubmitting] = useState(false);
+  const [queuedTickets, setQueuedTickets] = useState<QueuedTicket[]>([]);
+  const [monitors, setMonitors] = useState<MonitorInfo[]>([]);
+  const [screenshotMonitor, setScreenshotMonitor] = useState('all');
+  const [isEditingScreenshot, setIsEditingScreenshot] = useState(false);
+  // Grants read access to an image picked from disk until the form is cleared
+  const pickedFileToken = useRef<string>();
+  // The last copy the editor produced; the agent only attaches captures that went through it
+  const editedScreenshot = useRef<string>();
+
+  // A picked image is only readable through its token, captures are read by path
+  const screenshotFile = (path: string): FileRef =>
+    pickedFileToken.current ? { token: pickedFileToken.current } : { path };
 
   const form = useForm<FormSchema>({
     resolver: zodResolver(formSchema),
@@ -90,6 +104,7 @@ export default function Support() {
   useEffect(() => {
     const usePromise = listen('use_screenshot', async (path) => {
       try {
+        releasePickedFile();
         form.setValue('screenshot', path.payload as string);
       } catch (err) {
         toast.error('Failed to get screenshot');
@@ -101,9 +116,33 @@ export default function Support() {
     };
   }, []);
 
+  useEffect(() => {
+    getMonitors().then(({ data }) => setMonitors(data ?? []));
+  }, []);
+
+  useEffect(() => {
+    getQueuedTickets().then(({ data }) => setQueuedTickets(data ?? []));
+
+    const unlistenPromise = listen<QueuedTicket>('ticket_status', ({ payload: ticket }) => {
+      setQueuedTickets((tickets) => {
+        const others = tickets.filter((t) => t.id !== ticket.id);
+        return ticket.status.state === 'submitted' ? others : [...others, ticket];
+      });
+
+      if (ticket.status.state === 'submitted' && ticket.attempts > 1) {
+        toast.success(`Queued ticket "${ticket.request.summary}" was sent`);
+      }
+    });
+
+    return () => {
+      unlistenPromise.then((unlisten) => unlisten());
+    };
+  }, []);
+
   useEffect(() => {
     const unlistenPromise = listen('on_hide', async () => {
       form.reset();
+      releasePickedFile();
     });
 
     return () => {
@@ -115,7 +154,7 @@ export default function Support() {
   useEffect(() => {
     if (formValues.screenshot) {
       (async () => {
-        const { data: base64 } = await readFileBase64(formValues.screenshot!);
+        const { data: base64 } = await readFileBase64(screenshotFile(formValues.screenshot!));
 
         if (base64) {
           form.setValue('screenshot_url', `data:image/png;base64,${base64}`);
@@ -149,92 +188,67 @@ export default function Support() {
   }, [formValues.screenshot, formValues.screenshot_url, formValues.screenshot_blob]);
 
   const onSubmit = async (formData: FormSchema) => {
+    if (
+      formData.screenshot &&
+      !pickedFileToken.current &&
+      formData.screenshot !== editedScreenshot.current
+    ) {
+      toast.info('Please check the screenshot for anything private and apply it before sending');
+      setIsEditingScreenshot(true);
+      return;
+    }
+
     setIsSubmitting(true);
     await logToFile('INFO', 'Starting ticket submission');
 
     try {
-      await logToFile('INFO', 'Fetching agent settings');
-      const { data: settings } = await getSettings();
-      if (!settings || !settings.device_id || !settings.site_id || !settings.api_host) {
-        const errMsg = 'Invalid settings. Please restart agent.';
-        await logToFile('ERROR', errMsg);
-        throw errMsg;
-      }
-
-      await logToFile(
-        'INFO',
-        `Settings loaded: device_id=${settings.device_id}, site_id=${settings.site_id}`
-      );
-
-      await logToFile('INFO', 'Fetching RMM ID from registry');
-      const { data: rmmId, error: rmmError } = await getRmmId();
-      if (!rmmId) {
-        await logToFile('WARN', `Failed to get RMM ID: ${rmmError?.message}`);
-      } else {
-        await logToFile('INFO', `RMM ID: ${rmmId}`);
-      }
-
-      const apiUrl = `${settings.api_host}/v1.0/ticket/create`;
-      await logToFile('INFO', `Submitting ticket to: ${apiUrl}`);
       await logToFile(
         'INFO',
         `Ticket data: summary="${formData.summary}", urgency=${formData.urgency}, impact=${formData.impact}, has_screenshot=${!!screenshot}`
       );
 
-      // Use FormData for multipart/form-data to handle large screenshots
-      const formDataToSend = new FormData();
-      formDataToSend.append('summary', formData.summary);
-      formDataToSend.append('description', formData.description || '');
-      formDataToSend.append('impact', formData.impact);
-      formDataToSend.append('urgency', formData.urgency);
-      formDataToSend.append('name', formData.name);
-      formDataToSend.append('email', formData.email);
-      formDataToSend.append('phone', formData.phone.replace(/\D/g, ''));
-      if (rmmId) {
-        formDataToSend.append('rmm_id', rmmId);
-      }
-
-      // Add screenshot as a file if present
-      if (screenshot && formData.screenshot) {
-        await logToFile('INFO', `Adding screenshot file: ${screenshot.name}`);
-        // Read the file as a blob
-        const { data: fileContent } = await readFileBinary(formData.screenshot);
-
-        if (fileContent) {
-          const blob = new Blob([new Uint8Array(fileContent)], {
-            type: 'image/png',
-          });
-          formDataToSend.append('screenshot', blob, screenshot.name || 'screenshot.png');
-          await logToFile('INFO', `Screenshot file size: ${blob.size} bytes`);
-        } else {
-          await logToFile('WARN', `Screenshot failed to be read as binary: ${formData.screenshot}`);
-        }
-      }
-
-      const res = await fetch(apiUrl, {
-        method: 'POST',
-        headers: {
-          'X-Site-ID': settings.site_id,
-          'X-Device-ID': settings.device_id,
-          // Don't set Content-Type - browser will set it with boundary
-        },
-        body: formDataToSend,
+      // The agent saves the ticket before sending, so it survives being offline
+      const { data: ticket, error, meta } = await submitTicket({
+        summary: formData.summary,
+        description: formData.description,
+        impact: formData.impact,
+        urgency: formData.urgency,
+        name: formData.name,
+        email: formData.email,
+        phone: formData.phone,
+        screenshot_path: pickedFileToken.current ? undefined : formData.screenshot,
+        screenshot_token: pickedFileToken.current,
       });
 
-      await logToFile('INFO', `API response status: ${res.status}`);
-
-      if (!res.ok) {
-        const errorText = await res.text();
-        await logToFile('ERROR', `API request failed with status ${res.status}: ${errorText}`);
-        throw 'API Fetch Error';
+      if (!ticket) {
+        const code = meta?.code;
+        throw code === 'settings_missing' || code === 'settings_corrupt'
+          ? 'Agent settings are missing or damaged. Please reinstall the agent.'
+          : error?.message;
       }
 
-      const ret: APIResponse<string> = await res.json();
-      await logToFile('INFO', `Ticket created successfully! Ticket ID: ${ret.data}`);
-
-      alert(`Support ticket created successfully! Ticket ID: ${ret.data}`);
-      form.reset();
-      await hideWindow('support');
+      switch (ticket.status.state) {
+        case 'submitted':
+          await logToFile(
+            'INFO',
+            `Ticket created successfully! Ticket ID: ${ticket.status.ticket_id}`
+          );
+          alert(`Support ticket created successfully! Ticket ID: ${ticket.status.ticket_id}`);
+          form.reset();
+          releasePickedFile();
+          await hideWindow('support');
+          break;
+        case 'rejected':
+          throw ticket.status.error;
+        default:
+          await logToFile('WARN', `Ticket ${ticket.id} queued: ${ticket.last_error}`);
+          toast.info(
+            'Your ticket was saved and will be sent automatically once the connection is back.'
+          );
+          form.reset();
+          releasePickedFile();
+          break;
+      }
     } catch (err) {
       const errMsg = `Failed to submit ticket: ${err}`;
       await logToFile('ERROR', errMsg);
@@ -245,20 +259,43 @@ export default function Support() {
     }
   };
 
+  const handleDismiss = async (id: string) => {
+    const { data } = await dismissTicket(id);
+    if (!data) {
+      toast.error('Failed to dismiss ticket');
+      return;
+    }
+
+    setQueuedTickets((tickets) => tickets.filter((t) => t.id !== id));
+  };
+
+  const releasePickedFile = () => {
+    if (pickedFileToken.current) releaseFile(pickedFileToken.current);
+    pickedFileToken.current = undefined;
+  };
+
   const handleFileSelect = async () => {
-    const { data: path } = await chooseImageDialog();
-    if (!path) {
+    const { data: file } = await chooseImageDialog();
+    if (!file) {
       toast.info('No file was chosen or found');
       return;
     }
 
-    form.setValue('screenshot', path);
+    releasePickedFile();
+    pickedFileToken.current = file.token;
+    form.setValue('screenshot', file.path);
   };
 
   const handleScreenshot = async () => {
-    const { data: path } = await takeScreenshot();
+    // "all" captures every monitor and combines them into one image
+    const { data: result } = await takeScreenshot(
+      screenshotMonitor === 'all'
+        ? { stitch: true }
+        : { monitor_id: Number(screenshotMonitor) }
+    );
     await showWindow('support');
-    form.setValue('screenshot', path);
+    releasePickedFile();
+    form.setValue('screenshot', result?.stitched_path ?? result?.captures[0]?.path);
   };
 
   return (
@@ -421,29 +458,108 @@ export default function Support() {
               {!!screenshot ? (
                 <Button
                   variant="destructive"
-                  onClick={() => form.setValue('screenshot', undefined)}
+                  onClick={() => {
+                    form.setValue('screenshot', undefined);
+                    setIsEditingScreenshot(false);
+                    releasePickedFile();
+                  }}
                   disabled={isSubmitting}
                 >
                   Clear Screenshot
                 </Button>
               ) : (
-                <Button type="button" onClick={handleScreenshot} disabled={isSubmitting}>
-                  Take Screenshot
-                </Button>
+                <>
+                  {monitors.length > 1 && (
+                    <Select
+                      onValueChange={setScreenshotMonitor}
+                      value={screenshotMonitor}
+                      disabled={isSubmitting}
+                    >
+                      <SelectTrigger>
+                        <SelectValue placeholder="Select screen" />
+                      </SelectTrigger>
+                      <SelectContent>
+                        <SelectItem value="all">All Screens</SelectItem>
+                        {monitors.map((monitor, index) => (
+                          <SelectItem key={monitor.id} value={String(monitor.id)}>
+                            {monitor.name || `Screen ${index + 1}`}
+                            {monitor.is_primary ? ' (Primary)' : ''}
+                          </SelectItem>
+                        ))}
+                      </SelectContent>
+                    </Select>
+                  )}
+                  <Button type="button" onClick={handleScreenshot} disabled={isSubmitting}>
+                    Take Screenshot
+                  </Button>
+                </>
               )}
             </div>
 
-            {screenshot && (
-              <div className="border rounded p-2 w-fit overflow-clip">
-                <img
-                  src={screenshot.url}
-                  alt="Screenshot preview"
-                  className="w-full h-52 object-contain"
-                />
-              </div>
+            {screenshot && isEditingScreenshot && formValues.screenshot ? (
+              <ScreenshotEditor
+                file={screenshotFile(formValues.screenshot)}
+                url={screenshot.url}
+                onApply={(path) => {
+                  // Only the edited copy may be attached from here on, the picked file is done
+                  releasePickedFile();
+                  editedScreenshot.current = path;
+                  form.setValue('screenshot', path);
+                  setIsEditingScreenshot(false);
+                }}
+                onCancel={() => setIsEditingScreenshot(false)}
+              />
+            ) : (
+              screenshot && (
+                <div className="flex flex-col gap-2 w-fit">
+                  <div className="border rounded p-2 w-fit overflow-clip">
+                    <img
+                      src={screenshot.url}
+                      alt="Screenshot preview"
+                      className="w-full h-52 object-contain"
+                    />
+                  </div>
+                  <Button
+                    type="button"
+                    variant="outline"
+                    onClick={() => setIsEditingScreenshot(true)}
+                    disabled={isSubmitting || !screenshot.url}
+                  >
+                    Redact / Annotate
+                  </Button>
+                </div>
+              )
             )}
           </div>
 
+          {queuedTickets.length > 0 && (
+            <div className="flex flex-col w-full gap-1 text-sm">
+              <FormLabel>Pending Tickets</FormLabel>
+              {queuedTickets.map((ticket) => (
+                <div key={ticket.id} className="flex justify-between gap-2 border rounded px-2 py-1">
+                  <span className="truncate">{ticket.request.summary}</span>
+                  <span className="text-muted-foreground shrink-0">
+                    {ticket.status.state === 'rejected'
+                      ? `Rejected: ${ticket.status.error}`
+                      : ticket.status.state === 'sending'
+                        ? 'Sending...'
+                        : 'Waiting to send'}
+                  </span>
+                  {ticket.status.state === 'rejected' && (
+                    <Button
+                      type="button"
+                      variant="ghost"
+                      size="sm"
+                      onClick={() => handleDismiss(ticket.id)}
+                    >
+                      Dismiss
+                    </Button>
+                  )}
+                </div>
+              ))}
+            </div>
+          )}
+
           <div className="flex w-full mt-auto">
             <SubmitButton pending={isSubmitting}>Submit Ticket</SubmitButton>
           </div>
@@ -1,7 +1,9 @@
 import { invoke } from '@tauri-apps/api/core';
-import { Logger, APIResponse } from '@workspace/shared/lib/utils/logger';
+import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import { commandError } from '@/lib/error.ts';
 
 export type AgentSettings = {
+  schema_version?: number;
   site_id: string;
   api_host: string;
   installed_at: string;
@@ -10,6 +12,41 @@ export type AgentSettings = {
   device_id?: string;
   hostname?: string;
   registered_at?: string;
+  show_tray?: boolean;
+  heartbeat_interval_secs?: number;
+  log_level?: string;
+  log_targets?: Record<string, string>;
+  log_format?: 'text' | 'json';
+  log_max_total_mb?: number;
+  log_max_age_days?: number;
+  log_redact_patterns?: string[];
+  screenshot_format?: 'jpeg' | 'webp';
+  screenshot_quality?: number;
+  screenshot_max_dimension?: number;
+  screenshot_max_upload_kb?: number;
+  file_read_paths?: string[];
+  registry_read_paths?: string[];
+  features?: Record<string, boolean>;
+};
+
+export type NetworkInterface = {
+  name: string;
+  mac?: string;
+  ipv4: string[];
+  ipv6: string[];
+  is_up: boolean;
+  is_virtual: boolean;
+};
+
+export type NetworkInfo = {
+  interfaces: NetworkInterface[];
+  default_interface?: string;
+  default_gateway?: string;
+};
+
+export type RmmAgent = {
+  vendor: string;
+  device_id: string;
 };
 
 export type SystemInfo = {
@@ -20,6 +57,19 @@ export type SystemInfo = {
   guid?: string;
   version?: string;
   username?: string;
+  network?: NetworkInfo;
+};
+
+export type RegistrationState =
+  | { state: 'pending' }
+  | { state: 'registering'; attempt: number }
+  | { state: 'retrying'; attempt: number; retry_in_secs: number; error: string }
+  | { state: 'registered'; device_id?: string };
+
+export type HeartbeatStatus = {
+  running: boolean;
+  last_sent_at?: string;
+  last_error?: string;
 };
 
 export async function getSettings(): Promise<APIResponse<AgentSettings>> {
@@ -31,11 +81,14 @@ export async function getSettings(): Promise<APIResponse<AgentSettings>> {
       data: content,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'Agent',
-      context: 'getSettings',
-      message: `Failed to get agent settings: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'getSettings',
+        message: 'Failed to get agent settings',
+      },
+      err
+    );
   }
 }
 
@@ -48,25 +101,82 @@ export async function getSystemInfo(): Promise<APIResponse<SystemInfo>> {
       data: content,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'Agent',
-      context: 'getSystemInfo',
-      message: `Failed to get system info`,
-    });
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'getSystemInfo',
+        message: 'Failed to get system info',
+      },
+      err
+    );
+  }
+}
+
+export async function getRmmAgents(): Promise<APIResponse<RmmAgent[]>> {
+  try {
+    const content = await invoke<RmmAgent[]>('get_rmm_id');
+    if (!content || content.length === 0) throw 'No RMM agents found';
+
+    return { data: content };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'getRmmAgents',
+        message: 'Failed to get RMM agents',
+      },
+      err
+    );
   }
 }
 
-export async function getRmmId(): Promise<APIResponse<string>> {
+export async function getRegistrationState(): Promise<APIResponse<RegistrationState>> {
   try {
-    const content = await invoke<string>('get_rmm_id');
-    if (!content) throw 'Failed to get Windows key';
+    const content = await invoke<RegistrationState>('get_registration_state');
 
     return { data: content };
-  } catch {
-    return Logger.error({
-      module: 'Agent',
-      context: 'getRmmId',
-      message: 'Failed to get RMM ID',
-    });
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'getRegistrationState',
+        message: 'Failed to get registration state',
+      },
+      err
+    );
+  }
+}
+
+export async function getHeartbeatStatus(): Promise<APIResponse<HeartbeatStatus>> {
+  try {
+    const content = await invoke<HeartbeatStatus>('get_heartbeat_status');
+
+    return { data: content };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'getHeartbeatStatus',
+        message: 'Failed to get heartbeat status',
+      },
+      err
+    );
+  }
+}
+
+export async function uploadLogs(reason?: string): Promise<APIResponse<string>> {
+  try {
+    const content = await invoke<string>('upload_logs', { reason });
+
+    return { data: content };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Agent',
+        context: 'uploadLogs',
+        message: 'Failed to upload agent logs',
+      },
+      err
+    );
   }
 }
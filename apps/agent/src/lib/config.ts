@@ -0,0 +1,48 @@
+import { invoke } from '@tauri-apps/api/core';
+import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import { commandError } from '@/lib/error.ts';
+import { RegistryHive } from '@/lib/registry.ts';
+
+// Mirrors ConfigQuery in src-tauri/src/system_config.rs. Each source only exists on some
+// platforms; elsewhere the error code is `platform_unsupported`. Except for os_release, a
+// key must be on the agent's allow-list, otherwise the error code is `access_denied`.
+export type ConfigQuery =
+  | { source: 'registry'; hive?: RegistryHive; path: string; key: string } // Windows
+  | { source: 'os_release'; key: string } // Linux
+  | { source: 'systemd_unit'; unit: string; property: string } // Linux
+  | { source: 'gsettings'; schema: string; key: string } // Linux
+  | { source: 'dconf'; key: string } // Linux
+  | { source: 'sysctl'; name: string } // Linux, macOS
+  | { source: 'defaults'; domain: string; key: string }; // macOS
+
+export type ConfigValue =
+  | { type: 'string'; value: string }
+  | { type: 'integer'; value: number }
+  | { type: 'float'; value: number }
+  | { type: 'bool'; value: boolean }
+  | { type: 'list'; value: ConfigValue[] }
+  | { type: 'map'; value: Record<string, ConfigValue> }
+  | { type: 'binary'; value: number[] };
+
+/**
+ * Reads one configuration setting, e.g. a registry value on Windows,
+ * a sysctl on Linux or a plist preference on macOS.
+ */
+export async function querySystemConfig(query: ConfigQuery): Promise<APIResponse<ConfigValue>> {
+  try {
+    const result = await invoke<ConfigValue>('query_system_config', { query });
+
+    return {
+      data: result,
+    };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Config',
+        context: 'querySystemConfig',
+        message: `Failed to query ${query.source} config`,
+      },
+      err
+    );
+  }
+}
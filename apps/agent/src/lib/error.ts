@@ -0,0 +1,43 @@
+import { Logger, LogInfo } from '@workspace/shared/lib/utils/logger';
+
+// Mirrors AgentError in src-tauri/src/error.rs
+export type AgentErrorCode =
+  | 'settings_missing'
+  | 'settings_corrupt'
+  | 'not_registered'
+  | 'network'
+  | 'http_status'
+  | 'parse'
+  | 'io'
+  | 'platform_unsupported'
+  | 'access_denied';
+
+export type AgentError = {
+  code: AgentErrorCode;
+  message: string;
+  status?: number;
+};
+
+export function isAgentError(err: unknown): err is AgentError {
+  return (
+    typeof err === 'object' &&
+    err !== null &&
+    typeof (err as AgentError).code === 'string' &&
+    typeof (err as AgentError).message === 'string'
+  );
+}
+
+export function errorMessage(err: unknown): string {
+  return isAgentError(err) ? err.message : String(err);
+}
+
+/**
+ * Logs a failed command and returns it as an APIResponse error,
+ * keeping the agent error code in `meta` so callers can branch on it.
+ */
+export function commandError(info: LogInfo, err: unknown) {
+  return {
+    ...Logger.error({ ...info, message: `${info.message}: ${errorMessage(err)}` }),
+    meta: isAgentError(err) ? { code: err.code, status: err.status } : undefined,
+  };
+}
@@ -1,35 +1,119 @@
 import { invoke } from '@tauri-apps/api/core';
-import { open } from '@tauri-apps/plugin-dialog';
-import { Logger, APIResponse } from '@workspace/shared/lib/utils/logger';
+import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import { commandError } from '@/lib/error.ts';
 
-export async function takeScreenshot(): Promise<APIResponse<string>> {
+// Mirrors src-tauri/src/screenshot.rs
+export type MonitorInfo = {
+  id: number;
+  name: string;
+  x: number;
+  y: number;
+  width: number;
+  height: number;
+  scale_factor: number;
+  is_primary: boolean;
+};
+
+export type ScreenshotOptions = {
+  monitor_id?: number;
+  stitch?: boolean;
+};
+
+export type ScreenshotResult = {
+  captures: { monitor: MonitorInfo; path: string }[];
+  stitched_path?: string;
+};
+
+export async function takeScreenshot(
+  options?: ScreenshotOptions
+): Promise<APIResponse<ScreenshotResult>> {
   try {
-    const result = await invoke<string>('take_screenshot');
+    const result = await invoke<ScreenshotResult>('take_screenshot', { options });
 
     return {
       data: result,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'File',
-      context: 'takeScreenshot',
-      message: `Failed to take screenshot: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'File',
+        context: 'takeScreenshot',
+        message: 'Failed to take screenshot',
+      },
+      err
+    );
   }
 }
 
-export async function chooseImageDialog(): Promise<APIResponse<string>> {
+// Mirrors ScreenshotEdit in src-tauri/src/screenshot_edit.rs, coordinates are image pixels
+export type Region = { x: number; y: number; width: number; height: number };
+export type Point = { x: number; y: number };
+export type RGBA = [number, number, number, number];
+
+export type ScreenshotEdit =
+  | { kind: 'blur'; region: Region }
+  | { kind: 'black_out'; region: Region }
+  | { kind: 'highlight'; region: Region; color?: RGBA; line_width?: number }
+  | { kind: 'arrow'; from: Point; to: Point; color?: RGBA; line_width?: number }
+  | { kind: 'text'; position: Point; text: string; color?: RGBA; size?: number };
+
+/**
+ * Applies redactions and annotations and returns the path of the edited copy.
+ * Attach only the returned path; the original capture is removed by the agent.
+ */
+export async function editScreenshot(
+  file: FileRef,
+  edits: ScreenshotEdit[]
+): Promise<APIResponse<string>> {
   try {
-    const file = await open({
-      multiple: false,
-      directory: false,
-      filters: [
-        {
-          name: 'Image',
-          extensions: ['png', 'jpeg', 'jpg'],
-        },
-      ],
-    });
+    const result = await invoke<string>('edit_screenshot', { ...file, edits });
+
+    return {
+      data: result,
+    };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'File',
+        context: 'editScreenshot',
+        message: 'Failed to edit screenshot',
+      },
+      err
+    );
+  }
+}
+
+export async function getMonitors(): Promise<APIResponse<MonitorInfo[]>> {
+  try {
+    const result = await invoke<MonitorInfo[]>('get_monitors');
+
+    return {
+      data: result,
+    };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'File',
+        context: 'getMonitors',
+        message: 'Failed to list monitors',
+      },
+      err
+    );
+  }
+}
+
+// A file picked in the agent's dialog; the token keeps it readable until released
+export type PickedFile = {
+  token: string;
+  path: string;
+};
+
+// A file to have the agent read: one picked in its dialog by token, anything else by path
+export type FileRef = { path: string; token?: never } | { token: string; path?: never };
+
+export async function chooseImageDialog(): Promise<APIResponse<PickedFile>> {
+  try {
+    const file = await invoke<PickedFile | null>('pick_image_file');
 
     if (!file) {
       throw 'Failed to open file. Please try again.';
@@ -39,11 +123,33 @@ export async function chooseImageDialog(): Promise<APIResponse<string>> {
       data: file,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'File',
-      context: 'chooseImageDialog',
-      message: String(err),
-    });
+    return commandError(
+      {
+        module: 'File',
+        context: 'chooseImageDialog',
+        message: 'Failed to choose image',
+      },
+      err
+    );
+  }
+}
+
+export async function releaseFile(token: string): Promise<APIResponse<undefined>> {
+  try {
+    await invoke('release_file', { token });
+
+    return {
+      data: undefined,
+    };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'File',
+        context: 'releaseFile',
+        message: 'Failed to release file',
+      },
+      err
+    );
   }
 }
 
@@ -75,56 +181,59 @@ export async function logToFile(
   }
 }
 
-export async function readFileText(path: string): Promise<APIResponse<string>> {
+export async function readFileText(file: FileRef): Promise<APIResponse<string>> {
   try {
-    const content = await invoke<string>('read_file_text', {
-      path: path,
-    });
+    const content = await invoke<string>('read_file_text', { ...file });
 
     return {
       data: content,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'File',
-      context: 'readFileBase64',
-      message: `Failed to read file: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'File',
+        context: 'readFileBase64',
+        message: 'Failed to read file',
+      },
+      err
+    );
   }
 }
 
-export async function readFileBase64(path: string): Promise<APIResponse<string>> {
+export async function readFileBase64(file: FileRef): Promise<APIResponse<string>> {
   try {
-    const content = await invoke<string>('read_file_base64', {
-      path: path,
-    });
+    const content = await invoke<string>('read_file_base64', { ...file });
 
     return {
       data: content,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'File',
-      context: 'readFileBase64',
-      message: `Failed to read file: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'File',
+        context: 'readFileBase64',
+        message: 'Failed to read file',
+      },
+      err
+    );
   }
 }
 
-export async function readFileBinary(path: string): Promise<APIResponse<number[]>> {
+export async function readFileBinary(file: FileRef): Promise<APIResponse<number[]>> {
   try {
-    const content = await invoke<number[]>('read_file_binary', {
-      path,
-    });
+    const content = await invoke<number[]>('read_file_binary', { ...file });
 
     return {
       data: content,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'File',
-      context: 'readFileBinary',
-      message: `Failed to read file: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'File',
+        context: 'readFileBinary',
+        message: 'Failed to read file',
+      },
+      err
+    );
   }
 }
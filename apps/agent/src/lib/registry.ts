@@ -1,22 +1,45 @@
 import { invoke } from '@tauri-apps/api/core';
-import { Logger, APIResponse } from '@workspace/shared/lib/utils/logger';
+import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import { commandError } from '@/lib/error.ts';
 
-export async function getRegistryValue(path: string, key: string): Promise<APIResponse<string>> {
+export type RegistryHive = 'HKLM' | 'HKCU' | 'HKU';
+
+// Mirrors RegistryValue in src-tauri/src/registry.rs
+export type RegistryValue =
+  | { type: 'string'; value: string }
+  | { type: 'expand_string'; value: string }
+  | { type: 'dword'; value: number }
+  | { type: 'qword'; value: number }
+  | { type: 'multi_string'; value: string[] }
+  | { type: 'binary'; value: number[] };
+
+/**
+ * Reads a registry value. The key must be on the agent's registry allow-list,
+ * otherwise the error code is `access_denied`.
+ */
+export async function getRegistryValue(
+  path: string,
+  key: string,
+  hive: RegistryHive = 'HKLM'
+): Promise<APIResponse<RegistryValue>> {
   try {
-    const result = await invoke<string>('read_registry_value', {
+    const result = await invoke<RegistryValue>('read_registry_value', {
+      hive,
       path,
       key,
     });
-    if (!result) throw `Invalid key path`;
 
     return {
       data: result,
     };
   } catch (err) {
-    return Logger.error({
-      module: 'Registry',
-      context: 'getRegistryKey',
-      message: `Failed to get registry value: ${err}`,
-    });
+    return commandError(
+      {
+        module: 'Registry',
+        context: 'getRegistryKey',
+        message: 'Failed to get registry value',
+      },
+      err
+    );
   }
 }
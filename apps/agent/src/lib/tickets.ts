@@ -0,0 +1,88 @@
+import { invoke } from '@tauri-apps/api/core';
+import { APIResponse } from '@workspace/shared/lib/utils/logger';
+import { commandError } from '@/lib/error.ts';
+import { RmmAgent } from '@/lib/agent.ts';
+
+export type TicketRequest = {
+  summary: string;
+  description?: string;
+  impact: string;
+  urgency: string;
+  name: string;
+  email: string;
+  phone: string;
+  screenshot_path?: string;
+  // Token of an image picked in the agent's dialog, sent instead of screenshot_path
+  screenshot_token?: string;
+  include_logs?: boolean;
+};
+
+// Mirrors TicketStatus in src-tauri/src/tickets.rs
+export type TicketStatus =
+  | { state: 'queued' }
+  | { state: 'sending' }
+  | { state: 'submitted'; ticket_id: string }
+  | { state: 'rejected'; error: string };
+
+export type QueuedTicket = {
+  id: string;
+  request: TicketRequest;
+  status: TicketStatus;
+  rmm_agents: RmmAgent[];
+  log_bundle_id?: string;
+  attempts: number;
+  last_error?: string;
+  created_at: string;
+  next_attempt_at?: string;
+};
+
+export async function submitTicket(ticket: TicketRequest): Promise<APIResponse<QueuedTicket>> {
+  try {
+    const content = await invoke<QueuedTicket>('submit_ticket', { ticket });
+
+    return { data: content };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Tickets',
+        context: 'submitTicket',
+        message: 'Failed to submit ticket',
+      },
+      err
+    );
+  }
+}
+
+export async function getQueuedTickets(): Promise<APIResponse<QueuedTicket[]>> {
+  try {
+    const content = await invoke<QueuedTicket[]>('get_queued_tickets');
+
+    return { data: content };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Tickets',
+        context: 'getQueuedTickets',
+        message: 'Failed to read queued tickets',
+      },
+      err
+    );
+  }
+}
+
+export async function dismissTicket(id: string): Promise<APIResponse<boolean>> {
+  try {
+    await invoke('dismiss_queued_ticket', { id });
+
+    return { data: true };
+  } catch (err) {
+    return commandError(
+      {
+        module: 'Tickets',
+        context: 'dismissTicket',
+        message: 'Failed to dismiss ticket',
+      },
+      err
+    );
+  }
+}